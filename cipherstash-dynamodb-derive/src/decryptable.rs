@@ -1,6 +1,6 @@
-use crate::settings::Settings;
+use crate::settings::{field_member, CollectionMode, Settings};
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
+use quote::quote;
 use syn::DeriveInput;
 
 pub(crate) fn derive_decryptable(input: DeriveInput) -> Result<TokenStream, syn::Error> {
@@ -11,6 +11,9 @@ pub(crate) fn derive_decryptable(input: DeriveInput) -> Result<TokenStream, syn:
 
     let protected_excluding_handlers = settings.protected_attributes_excluding_handlers();
     let plaintext_attributes = settings.plaintext_attributes();
+    let nested_attributes = settings.nested_attributes();
+    let collection_attributes = settings.collection_attributes();
+    let serde_attributes = settings.serde_attributes();
 
     let protected_attributes_cow = settings
         .protected_attributes()
@@ -25,36 +28,110 @@ pub(crate) fn derive_decryptable(input: DeriveInput) -> Result<TokenStream, syn:
     let skipped_attributes = settings.skipped_attributes();
     let ident = settings.ident();
 
-    let from_unsealed_impl = protected_excluding_handlers
-        .iter()
-        .map(|attr| {
-            let attr_ident = format_ident!("{attr}");
+    // Generates the initializers of the fields for which `fields` returns true
+    let field_values = |fields: &dyn Fn(&str) -> bool| {
+        protected_excluding_handlers
+            .iter()
+            .filter(|attr| fields(attr))
+            .map(|attr| {
+                let attr_member = field_member(attr);
 
-            quote! {
-                #attr_ident: ::cipherstash_dynamodb::traits::TryFromPlaintext::try_from_optional_plaintext(unsealed.take_protected(#attr))?
-            }
-        })
-        .chain(plaintext_attributes.iter().map(|attr| {
-            let attr_ident = format_ident!("{attr}");
+                quote! {
+                    #attr_member: ::cipherstash_dynamodb::traits::TryFromPlaintext::try_from_optional_plaintext(unsealed.take_protected(#attr))?
+                }
+            })
+            .chain(plaintext_attributes.iter().filter(|attr| fields(attr)).map(|attr| {
+                let attr_member = field_member(attr);
 
-            quote! {
-                #attr_ident: ::cipherstash_dynamodb::traits::TryFromTableAttr::try_from_table_attr(unsealed.take_unprotected(#attr))?
-            }
-        }))
-        .chain(skipped_attributes.iter().map(|attr| {
-            let attr_ident = format_ident!("{attr}");
+                quote! {
+                    #attr_member: ::cipherstash_dynamodb::traits::TryFromTableAttr::try_from_table_attr(unsealed.take_unprotected(#attr))?
+                }
+            }))
+            .chain(nested_attributes.iter().filter(|attr| fields(attr)).map(|attr| {
+                let attr_member = field_member(attr);
+
+                quote! {
+                    #attr_member: unsealed.take_protected_nested(#attr)?
+                }
+            }))
+            .chain(collection_attributes.iter().filter(|(attr, _)| fields(attr)).map(|(attr, mode)| {
+                let attr_member = field_member(attr);
+
+                match mode {
+                    CollectionMode::Elements => quote! {
+                        #attr_member: unsealed.take_protected_list(#attr)?
+                    },
+                    CollectionMode::Blob => quote! {
+                        #attr_member: unsealed.take_protected_list_blob(#attr)?
+                    },
+                }
+            }))
+            .chain(serde_attributes.iter().filter(|attr| fields(attr)).map(|attr| {
+                let attr_member = field_member(attr);
+
+                quote! {
+                    #attr_member: unsealed.take_protected_serde(#attr)?
+                }
+            }))
+            .chain(skipped_attributes.iter().filter(|attr| fields(attr)).map(|attr| {
+                let attr_member = field_member(attr);
+
+                quote! {
+                    #attr_member: Default::default()
+                }
+            }))
+            .chain(
+                settings
+                    .decrypt_handlers()
+                    .iter()
+                    .filter(|(attr, _)| fields(attr))
+                    .map(|(attr, handler)| {
+                        let attr_member = field_member(attr);
+
+                        quote! {
+                            #attr_member: #handler(&mut unsealed)?
+                        }
+                    }),
+            )
+            .collect::<Vec<_>>()
+    };
+
+    let from_unsealed_impl = if let Some(tag) = &settings.tag {
+        let type_name = &settings.type_name;
+
+        let variants_impl = settings.variants.iter().map(|variant| {
+            let variant_ident = &variant.ident;
+            let variant_name = variant_ident.to_string();
+            let field_values_impl =
+                field_values(&|attr| variant.fields.iter().any(|field| field == attr));
 
             quote! {
-                #attr_ident: Default::default()
+                #variant_name => Ok(Self::#variant_ident {
+                    #(#field_values_impl,)*
+                })
             }
-        }))
-        .chain(settings.decrypt_handlers().iter().map(|(attr, handler)| {
-            let attr_ident = format_ident!("{attr}");
+        });
 
-            quote! {
-                #attr_ident: #handler(&mut unsealed)?
+        quote! {
+            let variant: String = ::cipherstash_dynamodb::traits::TryFromTableAttr::try_from_table_attr(unsealed.take_unprotected(#tag))?;
+
+            match variant.as_str() {
+                #(#variants_impl,)*
+                _ => Err(cipherstash_dynamodb::crypto::SealError::UnknownVariant {
+                    type_name: #type_name.to_string(),
+                    variant,
+                }),
             }
-        }));
+        }
+    } else {
+        let field_values_impl = field_values(&|_| true);
+
+        quote! {
+            Ok(Self {
+                #(#field_values_impl,)*
+            })
+        }
+    };
 
     let expanded = quote! {
         #[automatically_derived]
@@ -68,12 +145,124 @@ pub(crate) fn derive_decryptable(input: DeriveInput) -> Result<TokenStream, syn:
             }
 
             fn from_unsealed(mut unsealed: cipherstash_dynamodb::crypto::Unsealed) -> Result<Self, cipherstash_dynamodb::crypto::SealError> {
-                Ok(Self {
-                    #(#from_unsealed_impl,)*
-                })
+                #from_unsealed_impl
             }
         }
     };
 
     Ok(expanded)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{contains, method};
+    use syn::parse_quote;
+
+    fn expand(input: DeriveInput) -> String {
+        derive_decryptable(input)
+            .expect("Failed to derive Decryptable")
+            .to_string()
+    }
+
+    #[test]
+    fn test_tagged_enum() {
+        let expanded = expand(parse_quote! {
+            #[cipherstash(tag = "kind")]
+            enum PaymentMethod {
+                Card {
+                    #[partition_key]
+                    id: String,
+                },
+                BankAccount {
+                    #[partition_key]
+                    id: String,
+                },
+            }
+        });
+
+        let from_unsealed = method(&expanded, "from_unsealed");
+
+        assert!(contains(
+            from_unsealed,
+            quote! { unsealed.take_unprotected("kind") }
+        ));
+        let card = quote! {
+            "Card" => Ok(Self::Card {
+                id: ::cipherstash_dynamodb::traits::TryFromPlaintext::try_from_optional_plaintext(unsealed.take_protected("id"))?,
+            }),
+        };
+
+        assert!(contains(from_unsealed, card));
+        assert!(contains(
+            from_unsealed,
+            quote! {
+                _ => Err(cipherstash_dynamodb::crypto::SealError::UnknownVariant {
+                    type_name: "paymentmethod".to_string(),
+                    variant,
+                }),
+            }
+        ));
+    }
+
+    #[test]
+    fn test_tuple_struct() {
+        let expanded = expand(parse_quote! {
+            struct Note(
+                #[partition_key] String,
+                #[cipherstash(plaintext)] u32,
+            );
+        });
+
+        let from_unsealed = quote! {
+            Ok(Self {
+                0: ::cipherstash_dynamodb::traits::TryFromPlaintext::try_from_optional_plaintext(unsealed.take_protected("0"))?,
+                1: ::cipherstash_dynamodb::traits::TryFromTableAttr::try_from_table_attr(unsealed.take_unprotected("1"))?,
+            })
+        };
+
+        assert!(contains(method(&expanded, "from_unsealed"), from_unsealed));
+    }
+
+    #[test]
+    fn test_nested_field() {
+        let expanded = expand(parse_quote! {
+            struct Customer {
+                #[partition_key]
+                email: String,
+                #[cipherstash(nested)]
+                address: Address,
+            }
+        });
+
+        assert!(contains(
+            method(&expanded, "from_unsealed"),
+            quote! { address: unsealed.take_protected_nested("address")?, }
+        ));
+    }
+
+    #[test]
+    fn test_collections() {
+        let expanded = expand(parse_quote! {
+            struct Customer {
+                #[partition_key]
+                email: String,
+                #[cipherstash(collection)]
+                tags: Vec<String>,
+                #[cipherstash(collection = "blob")]
+                aliases: BTreeSet<String>,
+            }
+        });
+
+        let from_unsealed = method(&expanded, "from_unsealed");
+
+        assert!(contains(
+            from_unsealed,
+            quote! { tags: unsealed.take_protected_list("tags")?, }
+        ));
+        assert!(contains(
+            from_unsealed,
+            quote! { aliases: unsealed.take_protected_list_blob("aliases")?, }
+        ));
+    }
+}
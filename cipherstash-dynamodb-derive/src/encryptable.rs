@@ -1,4 +1,4 @@
-use crate::settings::Settings;
+use crate::settings::{field_member, CollectionMode, Settings};
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use syn::DeriveInput;
@@ -11,6 +11,9 @@ pub(crate) fn derive_encryptable(input: DeriveInput) -> Result<TokenStream, syn:
 
     let protected_excluding_handlers = settings.protected_attributes_excluding_handlers();
     let plaintext_attributes = settings.plaintext_attributes();
+    let nested_attributes = settings.nested_attributes();
+    let collection_attributes = settings.collection_attributes();
+    let serde_attributes = settings.serde_attributes();
 
     let protected_attributes_cow = settings
         .protected_attributes()
@@ -22,31 +25,130 @@ pub(crate) fn derive_encryptable(input: DeriveInput) -> Result<TokenStream, syn:
         .into_iter()
         .map(|x| quote! { std::borrow::Cow::Borrowed(#x) });
 
+    let skipped_attributes = settings.skipped_attributes();
     let ident = settings.ident();
 
-    let into_unsealed_impl = protected_excluding_handlers
-        .iter()
-        .map(|attr| {
-            let attr_ident = format_ident!("{attr}");
+    // Generates the statements which add the attributes in `fields` to `unsealed`
+    let add_attributes = |fields: &dyn Fn(&str) -> bool, access: &dyn Fn(&str) -> TokenStream| {
+        protected_excluding_handlers
+            .iter()
+            .filter(|attr| fields(attr))
+            .map(|attr| {
+                let attr_access = access(attr);
 
-            quote! {
-                unsealed.add_protected(#attr, self.#attr_ident);
-            }
-        })
-        .chain(plaintext_attributes.iter().map(|attr| {
-            let attr_ident = format_ident!("{attr}");
+                quote! {
+                    unsealed.add_protected(#attr, #attr_access);
+                }
+            })
+            .chain(
+                plaintext_attributes
+                    .iter()
+                    .filter(|attr| fields(attr))
+                    .map(|attr| {
+                        let attr_access = access(attr);
+
+                        quote! {
+                            unsealed.add_unprotected(#attr, #attr_access);
+                        }
+                    }),
+            )
+            .chain(
+                nested_attributes
+                    .iter()
+                    .filter(|attr| fields(attr))
+                    .map(|attr| {
+                        let attr_access = access(attr);
+
+                        quote! {
+                            unsealed.add_protected_nested(#attr, #attr_access);
+                        }
+                    }),
+            )
+            .chain(
+                collection_attributes
+                    .iter()
+                    .filter(|(attr, _)| fields(attr))
+                    .map(|(attr, mode)| {
+                        let attr_access = access(attr);
+
+                        match mode {
+                            CollectionMode::Elements => quote! {
+                                unsealed.add_protected_list(#attr, #attr_access);
+                            },
+                            CollectionMode::Blob => quote! {
+                                unsealed.add_protected_list_blob(#attr, #attr_access);
+                            },
+                        }
+                    }),
+            )
+            .chain(
+                serde_attributes
+                    .iter()
+                    .filter(|attr| fields(attr))
+                    .map(|attr| {
+                        let attr_access = access(attr);
+
+                        quote! {
+                            unsealed.add_protected_serde(#attr, &#attr_access);
+                        }
+                    }),
+            )
+            .chain(
+                settings
+                    .encrypt_handlers()
+                    .iter()
+                    .filter(|(attr, _)| fields(attr))
+                    .map(|(attr, handler)| {
+                        let attr_access = access(attr);
+
+                        quote! {
+                            #handler(&mut unsealed, #attr_access);
+                        }
+                    }),
+            )
+            .collect::<Vec<_>>()
+    };
+
+    let into_unsealed_impl = if let Some(tag) = &settings.tag {
+        let variants_impl = settings.variants.iter().map(|variant| {
+            let variant_ident = &variant.ident;
+            let variant_name = variant_ident.to_string();
+            let in_variant = |attr: &str| variant.fields.iter().any(|field| field == attr);
+            let add_attributes_impl = add_attributes(&in_variant, &|attr| {
+                let binding = format_ident!("{attr}");
+                quote! { #binding }
+            });
+
+            // Skipped fields aren't bound to avoid unused variable warnings
+            let bindings = variant
+                .fields
+                .iter()
+                .filter(|field| !skipped_attributes.contains(&field.as_str()))
+                .map(|field| format_ident!("{field}"));
 
             quote! {
-                unsealed.add_unprotected(#attr, self.#attr_ident);
+                Self::#variant_ident { #(#bindings,)* .. } => {
+                    unsealed.add_unprotected(#tag, #variant_name);
+                    #(#add_attributes_impl)*
+                }
             }
-        }))
-        .chain(settings.encrypt_handlers().iter().map(|(attr, handler)| {
-            let attr_ident = format_ident!("{attr}");
+        });
 
-            quote! {
-                #handler(&mut unsealed, self.#attr_ident);
+        quote! {
+            match self {
+                #(#variants_impl)*
             }
-        }));
+        }
+    } else {
+        let add_attributes_impl = add_attributes(&|_| true, &|attr| {
+            let member = field_member(attr);
+            quote! { self.#member }
+        });
+
+        quote! {
+            #(#add_attributes_impl)*
+        }
+    };
 
     let expanded = quote! {
         #[automatically_derived]
@@ -61,9 +163,9 @@ pub(crate) fn derive_encryptable(input: DeriveInput) -> Result<TokenStream, syn:
 
             #[allow(clippy::needless_question_mark)]
             fn into_unsealed(self) -> cipherstash_dynamodb::crypto::Unsealed {
-                let mut unsealed = cipherstash_dynamodb::crypto::Unsealed::new_with_descriptor(<Self as cipherstash_dynamodb::traits::Identifiable>::type_name());
+                let mut unsealed = cipherstash_dynamodb::crypto::Unsealed::new();
 
-                #(#into_unsealed_impl)*
+                #into_unsealed_impl
 
                 unsealed
             }
@@ -72,3 +174,124 @@ pub(crate) fn derive_encryptable(input: DeriveInput) -> Result<TokenStream, syn:
 
     Ok(expanded)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{contains, method};
+    use syn::parse_quote;
+
+    fn expand(input: DeriveInput) -> String {
+        derive_encryptable(input)
+            .expect("Failed to derive Encryptable")
+            .to_string()
+    }
+
+    #[test]
+    fn test_tagged_enum() {
+        let expanded = expand(parse_quote! {
+            #[cipherstash(tag = "kind")]
+            enum PaymentMethod {
+                Card {
+                    #[partition_key]
+                    id: String,
+                    #[cipherstash(plaintext)]
+                    expiry: String,
+                },
+                BankAccount {
+                    #[partition_key]
+                    id: String,
+                    account_number: String,
+                },
+            }
+        });
+
+        let card = quote! {
+            Self::Card { id, expiry, .. } => {
+                unsealed.add_unprotected("kind", "Card");
+                unsealed.add_protected("id", id);
+                unsealed.add_unprotected("expiry", expiry);
+            }
+        };
+
+        let bank_account = quote! {
+            Self::BankAccount { id, account_number, .. } => {
+                unsealed.add_unprotected("kind", "BankAccount");
+                unsealed.add_protected("account_number", account_number);
+                unsealed.add_protected("id", id);
+            }
+        };
+
+        let into_unsealed = method(&expanded, "into_unsealed");
+
+        assert!(contains(into_unsealed, card));
+        assert!(contains(into_unsealed, bank_account));
+        assert!(contains(
+            method(&expanded, "plaintext_attributes"),
+            quote! { std::borrow::Cow::Borrowed("kind") }
+        ));
+    }
+
+    #[test]
+    fn test_tuple_struct() {
+        let expanded = expand(parse_quote! {
+            struct Note(
+                #[partition_key] String,
+                #[cipherstash(plaintext)] u32,
+            );
+        });
+
+        let into_unsealed = quote! {
+            unsealed.add_protected("0", self.0);
+            unsealed.add_unprotected("1", self.1);
+        };
+
+        assert!(contains(method(&expanded, "into_unsealed"), into_unsealed));
+    }
+
+    #[test]
+    fn test_nested_field() {
+        let expanded = expand(parse_quote! {
+            struct Customer {
+                #[partition_key]
+                email: String,
+                #[cipherstash(nested)]
+                address: Address,
+            }
+        });
+
+        assert!(contains(
+            method(&expanded, "into_unsealed"),
+            quote! { unsealed.add_protected_nested("address", self.address); }
+        ));
+        assert!(contains(
+            method(&expanded, "protected_attributes"),
+            quote! { std::borrow::Cow::Borrowed("address") }
+        ));
+    }
+
+    #[test]
+    fn test_collections() {
+        let expanded = expand(parse_quote! {
+            struct Customer {
+                #[partition_key]
+                email: String,
+                #[cipherstash(collection)]
+                tags: Vec<String>,
+                #[cipherstash(collection = "blob")]
+                aliases: BTreeSet<String>,
+            }
+        });
+
+        let into_unsealed = method(&expanded, "into_unsealed");
+
+        assert!(contains(
+            into_unsealed,
+            quote! { unsealed.add_protected_list("tags", self.tags); }
+        ));
+        assert!(contains(
+            into_unsealed,
+            quote! { unsealed.add_protected_list_blob("aliases", self.aliases); }
+        ));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::settings::Settings;
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
+use quote::quote;
 use syn::DeriveInput;
 
 pub(crate) fn derive_identifiable(input: DeriveInput) -> Result<TokenStream, syn::Error> {
@@ -16,44 +16,129 @@ pub(crate) fn derive_identifiable(input: DeriveInput) -> Result<TokenStream, syn
         ));
     };
 
-    let partition_key_attr = format_ident!("{partition_key_field}");
-
     let protected_attributes = settings.protected_attributes();
     let ident = settings.ident();
 
     let is_partition_key_encrypted = protected_attributes.contains(&partition_key_field.as_str());
 
-    let is_sort_key_encrypted = settings
-        .sort_key_field
-        .as_ref()
-        .map(|x| protected_attributes.contains(&x.as_str()))
-        .unwrap_or(true);
+    // The sort key is only stored in plaintext if every field it is made of is
+    let is_sort_key_encrypted = settings.sort_key_fields.is_empty()
+        || settings
+            .sort_key_fields
+            .iter()
+            .any(|x| protected_attributes.contains(&x.as_str()));
+
+    let key_type = |field: &str| {
+        settings.field_type(field).ok_or_else(|| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!("key field '{field}' does not exist"),
+            )
+        })
+    };
+
+    let pk_type = key_type(&partition_key_field)?;
 
-    let primary_key_impl = if let Some(sort_key_field) = &settings.sort_key_field {
-        let sort_key_attr = format_ident!("{sort_key_field}");
+    let primary_key_impl = match settings.sort_key_fields.as_slice() {
+        [] => {
+            let primary_key = settings.with_fields(
+                &[&partition_key_field],
+                |fields| {
+                    let pk = &fields[0];
 
-        quote! {
-            type PrimaryKey = cipherstash_dynamodb::PkSk;
+                    quote! {
+                        cipherstash_dynamodb::Pk(#pk.clone())
+                    }
+                },
+                quote! { unreachable!() },
+            );
 
-            fn get_primary_key(&self) -> Self::PrimaryKey {
-                cipherstash_dynamodb::PkSk(
-                    self.#partition_key_attr.to_string(),
-                    self.#sort_key_attr.to_string()
-                )
+            quote! {
+                type PrimaryKey = cipherstash_dynamodb::Pk<#pk_type>;
+
+                fn get_primary_key(&self) -> Self::PrimaryKey {
+                    #primary_key
+                }
             }
         }
-    } else {
-        quote! {
-            type PrimaryKey = cipherstash_dynamodb::Pk;
+        [sort_key_field] => {
+            let sk_type = key_type(sort_key_field)?;
+
+            let primary_key = settings.with_fields(
+                &[&partition_key_field, sort_key_field],
+                |fields| {
+                    let (pk, sk) = (&fields[0], &fields[1]);
 
-            fn get_primary_key(&self) -> Self::PrimaryKey {
-                cipherstash_dynamodb::Pk(
-                    self.#partition_key_attr.to_string()
-                )
+                    quote! {
+                        cipherstash_dynamodb::PkSk(#pk.clone(), #sk.clone())
+                    }
+                },
+                quote! { unreachable!() },
+            );
+
+            quote! {
+                type PrimaryKey = cipherstash_dynamodb::PkSk<#pk_type, #sk_type>;
+
+                fn get_primary_key(&self) -> Self::PrimaryKey {
+                    #primary_key
+                }
+            }
+        }
+        sort_key_fields => {
+            for field in sort_key_fields {
+                key_type(field)?;
+            }
+
+            let key_fields = std::iter::once(partition_key_field.as_str())
+                .chain(sort_key_fields.iter().map(|x| x.as_str()))
+                .collect::<Vec<_>>();
+
+            // Composite sort keys are the encoded fields joined with the separator
+            let separator = settings
+                .sort_key_separator
+                .replace('{', "{{")
+                .replace('}', "}}");
+            let sk_format = vec!["{}"; sort_key_fields.len()].join(&separator);
+
+            let primary_key = settings.with_fields(
+                &key_fields,
+                |fields| {
+                    let (pk, sk) = (&fields[0], &fields[1..]);
+
+                    quote! {
+                        {
+                            use cipherstash_dynamodb::traits::KeyComponent as _;
+
+                            cipherstash_dynamodb::PkSk(
+                                #pk.clone(),
+                                format!(#sk_format, #(#sk.to_key()),*)
+                            )
+                        }
+                    }
+                },
+                quote! { unreachable!() },
+            );
+
+            quote! {
+                type PrimaryKey = cipherstash_dynamodb::PkSk<#pk_type, std::string::String>;
+
+                fn get_primary_key(&self) -> Self::PrimaryKey {
+                    #primary_key
+                }
             }
         }
     };
+
     let type_name = &settings.type_name;
+    let is_bound_to_primary_key = settings.bind_to_primary_key;
+    let has_record_mac = settings.record_mac;
+    let has_soft_delete = settings.soft_delete;
+
+    let ttl_attribute_impl = if let Some(ttl_attribute) = &settings.ttl_attribute {
+        quote! { Some(std::borrow::Cow::Borrowed(#ttl_attribute)) }
+    } else {
+        quote! { None }
+    };
 
     let sort_key_prefix_impl = if let Some(prefix) = &settings.sort_key_prefix {
         quote! { Some(std::borrow::Cow::Borrowed(#prefix)) }
@@ -82,8 +167,128 @@ pub(crate) fn derive_identifiable(input: DeriveInput) -> Result<TokenStream, syn
             fn is_sk_encrypted() -> bool {
                 #is_sort_key_encrypted
             }
+
+            fn is_bound_to_primary_key() -> bool {
+                #is_bound_to_primary_key
+            }
+
+            fn has_record_mac() -> bool {
+                #has_record_mac
+            }
+
+            fn has_soft_delete() -> bool {
+                #has_soft_delete
+            }
+
+            fn ttl_attribute() -> Option<std::borrow::Cow<'static, str>> {
+                #ttl_attribute_impl
+            }
         }
     };
 
     Ok(expanded)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{contains, method};
+    use syn::parse_quote;
+
+    fn expand(input: DeriveInput) -> String {
+        derive_identifiable(input)
+            .expect("Failed to derive Identifiable")
+            .to_string()
+    }
+
+    #[test]
+    fn test_tagged_enum() {
+        let expanded = expand(parse_quote! {
+            #[cipherstash(tag = "kind")]
+            enum PaymentMethod {
+                Card {
+                    #[partition_key]
+                    id: String,
+                },
+                BankAccount {
+                    #[partition_key]
+                    id: String,
+                },
+            }
+        });
+
+        let get_primary_key = quote! {
+            match self {
+                Self::Card { id, .. } => cipherstash_dynamodb::Pk(id.clone()),
+                Self::BankAccount { id, .. } => cipherstash_dynamodb::Pk(id.clone()),
+            }
+        };
+
+        assert!(contains(
+            method(&expanded, "get_primary_key"),
+            get_primary_key
+        ));
+    }
+
+    #[test]
+    fn test_tuple_struct() {
+        let expanded = expand(parse_quote! {
+            struct Note(#[partition_key] String, String);
+        });
+
+        assert!(contains(
+            method(&expanded, "get_primary_key"),
+            quote! { cipherstash_dynamodb::Pk(self.0.clone()) }
+        ));
+    }
+
+    #[test]
+    fn test_composite_sort_key() {
+        let expanded = expand(parse_quote! {
+            #[sort_key(fields = ["day", "title"], separator = "/")]
+            struct Entry {
+                #[partition_key]
+                user: String,
+                #[cipherstash(plaintext)]
+                day: String,
+                title: String,
+            }
+        });
+
+        let get_primary_key = quote! {
+            cipherstash_dynamodb::PkSk(
+                self.user.clone(),
+                format!("{}/{}", self.day.to_key(), self.title.to_key())
+            )
+        };
+
+        assert!(contains(
+            &expanded,
+            quote! { type PrimaryKey = cipherstash_dynamodb::PkSk<String, std::string::String>; }
+        ));
+        assert!(contains(
+            method(&expanded, "get_primary_key"),
+            get_primary_key
+        ));
+    }
+
+    #[test]
+    fn test_composite_sort_key_default_separator() {
+        let expanded = expand(parse_quote! {
+            #[sort_key(fields = ["created_at", "id"])]
+            struct Post {
+                #[partition_key]
+                user: String,
+                #[cipherstash(plaintext)]
+                created_at: String,
+                #[cipherstash(plaintext)]
+                id: u32,
+            }
+        });
+
+        assert!(contains(
+            method(&expanded, "get_primary_key"),
+            quote! { format!("{}#{}", self.created_at.to_key(), self.id.to_key()) }
+        ));
+    }
+}
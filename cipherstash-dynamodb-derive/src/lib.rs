@@ -7,6 +7,9 @@ mod encryptable;
 mod identifiable;
 mod searchable;
 mod settings;
+#[cfg(test)]
+mod test_utils;
+mod typed_query;
 
 use proc_macro::TokenStream;
 use syn::{parse_macro_input, DeriveInput};
@@ -1,4 +1,4 @@
-use crate::settings::Settings;
+use crate::{settings::Settings, typed_query::derive_typed_query};
 use proc_macro2::TokenStream;
 use quote::quote;
 use syn::DeriveInput;
@@ -17,9 +17,17 @@ pub(crate) fn derive_searchable(input: DeriveInput) -> Result<TokenStream, syn::
         .map(|index| {
             let index_name = index.index_name();
             let index_type = index.to_cipherstash_dynamodb_type()?;
+            let max_terms = match settings.max_terms(index) {
+                Some(max_terms) => quote! { #max_terms },
+                None => quote! { cipherstash_dynamodb::ProtectedIndex::DEFAULT_MAX_TERMS },
+            };
 
             Ok::<_, syn::Error>(quote! {
-                ( std::borrow::Cow::Borrowed(#index_name), #index_type )
+                cipherstash_dynamodb::ProtectedIndex {
+                    name: std::borrow::Cow::Borrowed(#index_name),
+                    index_type: #index_type,
+                    max_terms: #max_terms,
+                }
             })
         })
         .collect::<Result<Vec<_>, _>>()?;
@@ -28,7 +36,7 @@ pub(crate) fn derive_searchable(input: DeriveInput) -> Result<TokenStream, syn::
         .iter()
         .map(|index| {
             let index_name = index.index_name();
-            let indexer = index.to_cipherstash_dynamodb_indexer()?;
+            let indexer = index.to_cipherstash_dynamodb_indexer(settings.prefix_lengths())?;
             let index_type = index.to_cipherstash_dynamodb_type()?;
 
             Ok::<_, syn::Error>(quote! {
@@ -37,11 +45,19 @@ pub(crate) fn derive_searchable(input: DeriveInput) -> Result<TokenStream, syn::
         })
         .collect::<Result<Vec<_>, _>>()?;
 
-    let attributes_for_index_impl = indexes
+    let (collection_indexes, indexes_excluding_collections): (Vec<_>, Vec<_>) =
+        indexes.iter().partition(|index| {
+            index
+                .fields()
+                .iter()
+                .any(|field| settings.is_collection(field))
+        });
+
+    let attributes_for_index_impl = indexes_excluding_collections
         .iter()
         .map(|index| {
             let index_name = index.index_name();
-            let field_access = index.to_compound_plaintext_access()?;
+            let field_access = index.to_compound_plaintext_access(&settings)?;
             let index_type = index.to_cipherstash_dynamodb_type()?;
 
             Ok::<_, syn::Error>(quote! {
@@ -50,10 +66,79 @@ pub(crate) fn derive_searchable(input: DeriveInput) -> Result<TokenStream, syn::
         })
         .collect::<Result<Vec<_>, _>>()?;
 
+    // Collections are indexed with a plaintext for each of their elements
+    let collection_attributes_for_index_impl = (!collection_indexes.is_empty()).then(|| {
+        let indexes_impl = collection_indexes
+            .iter()
+            .map(|index| {
+                let index_name = index.index_name();
+                let index_type = index.to_cipherstash_dynamodb_type()?;
+                let elements_access = index.to_compound_plaintext_elements_access(&settings);
+
+                Ok::<_, syn::Error>(quote! {
+                    ( #index_name, #index_type ) => #elements_access
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok::<_, syn::Error>(quote! {
+            fn attributes_for_index(&self, index_name: &str, index_type: cipherstash_dynamodb::IndexType) -> Option<Vec<cipherstash_dynamodb::traits::ComposablePlaintext>> {
+                match ( index_name, index_type ) {
+                    #(#indexes_impl,)*
+                    _ => self.attribute_for_index(index_name, index_type).map(|attr| vec![attr]),
+                }
+            }
+        })
+    }).transpose()?;
+
+    // Variants of an enum which don't have the fields of an index aren't indexed by it
+    let has_attribute_for_index_impl = (!settings.variants.is_empty()).then(|| {
+        let indexes_impl = indexes
+            .iter()
+            .map(|index| {
+                let index_name = index.index_name();
+                let index_type = index.to_cipherstash_dynamodb_type()?;
+                let has_fields =
+                    settings.with_fields(&index.fields(), |_| quote! { true }, quote! { false });
+
+                Ok::<_, syn::Error>(quote! {
+                    ( #index_name, #index_type ) => #has_fields
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok::<_, syn::Error>(quote! {
+            #[allow(unused_variables)]
+            fn has_attribute_for_index(&self, index_name: &str, index_type: cipherstash_dynamodb::IndexType) -> bool {
+                match ( index_name, index_type ) {
+                    #(#indexes_impl,)*
+                    _ => true,
+                }
+            }
+        })
+    }).transpose()?;
+
+    let typed_query_impl = derive_typed_query(&input, &settings);
+
+    let default_term_shards = settings.term_shards;
+
+    let term_shards_impl = indexes
+        .iter()
+        .filter_map(|index| settings.term_shards(index).map(|shards| (index, shards)))
+        .map(|(index, shards)| {
+            let index_name = index.index_name();
+            let index_type = index.to_cipherstash_dynamodb_type()?;
+
+            Ok::<_, syn::Error>(quote! {
+                ( #index_name, #index_type ) => #shards
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
     let expanded = quote! {
         #[automatically_derived]
         impl cipherstash_dynamodb::traits::Searchable for #ident {
-            fn protected_indexes() -> std::borrow::Cow<'static, [( std::borrow::Cow<'static, str>, cipherstash_dynamodb::IndexType )]> {
+            fn protected_indexes() -> std::borrow::Cow<'static, [cipherstash_dynamodb::ProtectedIndex]> {
                 std::borrow::Cow::Borrowed(&[#(#protected_indexes_impl,)*])
             }
 
@@ -70,8 +155,143 @@ pub(crate) fn derive_searchable(input: DeriveInput) -> Result<TokenStream, syn::
                     _ => None,
                 }
             }
+
+            #collection_attributes_for_index_impl
+
+            #has_attribute_for_index_impl
+
+            fn term_shards(index_name: &str, index_type: cipherstash_dynamodb::IndexType) -> u8 {
+                match ( index_name, index_type ) {
+                    #(#term_shards_impl,)*
+                    _ => #default_term_shards,
+                }
+            }
         }
+
+        #typed_query_impl
     };
 
     Ok(expanded)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{contains, method};
+    use syn::parse_quote;
+
+    fn expand(input: DeriveInput) -> String {
+        derive_searchable(input)
+            .expect("Failed to derive Searchable")
+            .to_string()
+    }
+
+    #[test]
+    fn test_term_shards_match_index_type() {
+        let expanded = expand(parse_quote! {
+            #[cipherstash(sort_key_prefix = "user", term_shards = 2)]
+            struct User {
+                #[partition_key]
+                #[cipherstash(query = "exact", term_shards = 8)]
+                #[cipherstash(query = "prefix")]
+                email: String,
+            }
+        });
+
+        let exact = quote! {
+            ( "email" , cipherstash_dynamodb::IndexType::Single(cipherstash_dynamodb::SingleIndex::Exact) ) => 8u8
+        };
+
+        let prefix = quote! {
+            ( "email" , cipherstash_dynamodb::IndexType::Single(cipherstash_dynamodb::SingleIndex::Prefix) ) =>
+        };
+
+        let default = quote! {
+            _ => 2u8
+        };
+
+        let term_shards = method(&expanded, "term_shards");
+
+        assert!(contains(term_shards, exact));
+        assert!(!contains(term_shards, prefix));
+        assert!(contains(term_shards, default));
+    }
+
+    #[test]
+    fn test_prefix_lengths_kept_with_exact_index() {
+        let expanded = expand(parse_quote! {
+            #[cipherstash(sort_key_prefix = "product")]
+            struct Product {
+                #[partition_key]
+                id: String,
+
+                #[cipherstash(query = "prefix", min_length = 1, max_length = 64, max_terms = 64)]
+                #[cipherstash(query = "exact")]
+                title: String,
+            }
+        });
+
+        let prefix = quote! {
+            cipherstash_dynamodb::encryption::compound_indexer::PrefixIndex::new_with_opts(vec![], 1usize, 64usize)
+        };
+
+        let exact = quote! {
+            cipherstash_dynamodb::encryption::compound_indexer::ExactIndex::new(vec![])
+        };
+
+        let index_by_name = method(&expanded, "index_by_name");
+
+        assert!(contains(index_by_name, prefix));
+        assert!(contains(index_by_name, exact));
+    }
+
+    #[test]
+    fn test_nested_path_index() {
+        let expanded = expand(parse_quote! {
+            struct Customer {
+                #[partition_key]
+                email: String,
+                #[cipherstash(nested)]
+                #[cipherstash(query = "exact", path = "geo.source")]
+                address: Address,
+            }
+        });
+
+        let attribute_for_index = quote! {
+            ( "address.geo.source" , cipherstash_dynamodb::IndexType::Single(cipherstash_dynamodb::SingleIndex::Exact) ) =>
+                self.address.geo.source.clone().try_into().ok()
+        };
+
+        assert!(contains(
+            method(&expanded, "protected_indexes"),
+            quote! { name: std::borrow::Cow::Borrowed("address.geo.source") }
+        ));
+        assert!(contains(
+            method(&expanded, "attribute_for_index"),
+            attribute_for_index
+        ));
+    }
+
+    #[test]
+    fn test_collection_terms_per_element() {
+        let expanded = expand(parse_quote! {
+            struct Customer {
+                #[partition_key]
+                email: String,
+                #[cipherstash(collection, query = "exact")]
+                tags: Vec<String>,
+            }
+        });
+
+        let attributes_for_index = quote! {
+            ( "tags" , cipherstash_dynamodb::IndexType::Single(cipherstash_dynamodb::SingleIndex::Exact) ) =>
+                self.tags.iter().cloned().map(|element| element.try_into()).collect::<Result<Vec<_>, _>>().ok()
+        };
+
+        assert!(contains(
+            method(&expanded, "attributes_for_index"),
+            attributes_for_index
+        ));
+        assert!(!method(&expanded, "attribute_for_index").contains("\"tags\""));
+    }
+}
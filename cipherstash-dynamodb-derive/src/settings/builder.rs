@@ -1,7 +1,11 @@
-use super::{index_type::IndexType, AttributeMode, Settings};
+use super::{
+    index_type::{IndexType, PrefixLengths},
+    AttributeMode, CollectionMode, Settings, Variant,
+};
 use proc_macro2::{Ident, Span};
+use quote::ToTokens;
 use std::collections::HashMap;
-use syn::{Data, DeriveInput, ExprPath, Fields, LitStr};
+use syn::{Data, DataEnum, DeriveInput, ExprPath, Field, Fields, LitInt, LitStr, Type};
 
 enum SortKeyPrefix {
     Default,
@@ -21,21 +25,71 @@ impl SortKeyPrefix {
 
 const RESERVED_FIELD_NAMES: &[&str] = &["term"];
 
+/// The name of the attribute which stores the variant of an enum unless overridden with
+/// `#[cipherstash(tag = "...")]`.
+const DEFAULT_TAG: &str = "type";
+
+/// The separator between the fields of a composite sort key unless overridden with
+/// `#[sort_key(fields = [...], separator = "...")]`.
+const DEFAULT_SORT_KEY_SEPARATOR: &str = "#";
+
 pub(crate) struct SettingsBuilder {
     ident: Ident,
     type_name: String,
     sort_key_prefix: SortKeyPrefix,
     sort_key_field: Option<String>,
+    composite_sort_key: Option<(Vec<String>, Span)>,
+    sort_key_separator: String,
     partition_key_field: Option<String>,
     protected_attributes: Vec<String>,
     unprotected_attributes: Vec<String>,
     skipped_attributes: Vec<String>,
+    nested_attributes: Vec<String>,
+    collection_attributes: Vec<(String, CollectionMode)>,
+    serde_attributes: Vec<String>,
+    field_types: HashMap<String, Type>,
     indexes: Vec<IndexType>,
+    term_shards: u8,
+    bind_to_primary_key: bool,
+    record_mac: bool,
+    soft_delete: bool,
+    ttl_attribute: Option<String>,
+    tag: Option<(String, Span)>,
+    variants: Vec<Variant>,
+    index_term_shards: HashMap<(String, String), u8>,
+    index_max_terms: HashMap<(String, String), usize>,
+    prefix_lengths: HashMap<(String, String), PrefixLengths>,
     encrypt_handlers: HashMap<String, ExprPath>,
     decrypt_handlers: HashMap<String, ExprPath>,
 }
 
 impl SettingsBuilder {
+    fn parse_term_shards(value: &LitInt) -> Result<u8, syn::Error> {
+        let shards = value.base10_parse::<u8>()?;
+
+        if shards == 0 {
+            return Err(syn::Error::new_spanned(
+                value,
+                "term_shards must be at least 1",
+            ));
+        }
+
+        Ok(shards)
+    }
+
+    fn parse_positive(value: &LitInt, name: &str) -> Result<usize, syn::Error> {
+        let value_usize = value.base10_parse::<usize>()?;
+
+        if value_usize == 0 {
+            return Err(syn::Error::new_spanned(
+                value,
+                format!("{name} must be at least 1"),
+            ));
+        }
+
+        Ok(value_usize)
+    }
+
     fn validate_index_type(index_type: &str, index_type_span: Span) -> Result<(), syn::Error> {
         if matches!(index_type, "exact" | "prefix") {
             Ok(())
@@ -55,11 +109,27 @@ impl SettingsBuilder {
             type_name,
             sort_key_prefix: SortKeyPrefix::Default,
             sort_key_field: None,
+            composite_sort_key: None,
+            sort_key_separator: DEFAULT_SORT_KEY_SEPARATOR.to_string(),
             partition_key_field: None,
             protected_attributes: Vec::new(),
             unprotected_attributes: Vec::new(),
             skipped_attributes: Vec::new(),
+            nested_attributes: Vec::new(),
+            collection_attributes: Vec::new(),
+            serde_attributes: Vec::new(),
+            field_types: HashMap::new(),
             indexes: Vec::new(),
+            term_shards: 1,
+            bind_to_primary_key: false,
+            record_mac: false,
+            soft_delete: false,
+            ttl_attribute: None,
+            tag: None,
+            variants: Vec::new(),
+            index_term_shards: HashMap::new(),
+            index_max_terms: HashMap::new(),
+            prefix_lengths: HashMap::new(),
             encrypt_handlers: HashMap::new(),
             decrypt_handlers: HashMap::new(),
         }
@@ -98,10 +168,81 @@ impl SettingsBuilder {
                             let t: LitStr = value.parse()?;
                             self.set_partition_key(t.value().to_string())
                         }
+                        Some("term_shards") => {
+                            let value = meta.value()?;
+                            self.term_shards = Self::parse_term_shards(&value.parse()?)?;
+                            Ok(())
+                        }
+                        Some("bind_to_primary_key") => {
+                            self.bind_to_primary_key = true;
+                            Ok(())
+                        }
+                        Some("record_mac") => {
+                            self.record_mac = true;
+                            Ok(())
+                        }
+                        Some("soft_delete") => {
+                            self.soft_delete = true;
+                            Ok(())
+                        }
+                        Some("tag") => {
+                            let value = meta.value()?;
+                            let span = value.span();
+                            let t: LitStr = value.parse()?;
+                            self.tag = Some((t.value(), span));
+                            Ok(())
+                        }
                         _ => Err(meta.error("unsupported attribute")),
                     }
                 })?;
             }
+
+            if attr.path().is_ident("sort_key") {
+                let mut fields: Option<(Vec<String>, Span)> = None;
+
+                attr.parse_nested_meta(|meta| {
+                    let ident = meta.path.get_ident().map(|i| i.to_string());
+                    match ident.as_deref() {
+                        Some("fields") => {
+                            let value = meta.value()?;
+                            let span = value.span();
+                            let content;
+                            syn::bracketed!(content in value);
+                            let names = content
+                                .parse_terminated(|input| input.parse::<LitStr>(), syn::Token![,])?
+                                .into_iter()
+                                .map(|name| name.value())
+                                .collect::<Vec<_>>();
+
+                            if names.is_empty() {
+                                return Err(syn::Error::new(
+                                    span,
+                                    "a composite sort key must have at least one field",
+                                ));
+                            }
+
+                            fields = Some((names, span));
+                            Ok(())
+                        }
+                        Some("separator") => {
+                            let value = meta.value()?;
+                            let t: LitStr = value.parse()?;
+                            self.sort_key_separator = t.value();
+                            Ok(())
+                        }
+                        _ => Err(meta.error("unsupported sort_key attribute")),
+                    }
+                })?;
+
+                let Some(fields) = fields else {
+                    return Err(syn::Error::new_spanned(
+                        attr,
+                        "the fields of the sort key must be specified with #[sort_key(fields = [...])]",
+                    ));
+                };
+
+                self.composite_sort_key = Some(fields);
+            }
         }
 
         Ok(self)
@@ -111,129 +252,227 @@ impl SettingsBuilder {
         mut self,
         DeriveInput { data, .. }: &DeriveInput,
     ) -> Result<Self, syn::Error> {
-        // Only support structs
-        if let Data::Struct(data_struct) = data {
-            if let Fields::Named(fields_named) = &data_struct.fields {
-                let all_field_names: Vec<String> = fields_named
-                    .named
+        match data {
+            Data::Struct(data_struct) => {
+                // Fields of tuple structs are named after their position (e.g. "0")
+                let fields = data_struct
+                    .fields
                     .iter()
-                    .flat_map(|x| x.ident.as_ref().map(|x| x.to_string()))
-                    .collect();
+                    .enumerate()
+                    .map(|(i, field)| (Self::field_name(field, i), field))
+                    .collect::<Vec<_>>();
 
-                let explicit_pk = all_field_names.contains(&String::from("pk"));
-                let explicit_sk = all_field_names.contains(&String::from("sk"));
+                self.fields(&fields)?;
+            }
+            Data::Enum(data_enum) => self.variants(data_enum)?,
+            Data::Union(data_union) => {
+                return Err(syn::Error::new_spanned(
+                    data_union.union_token,
+                    "unions are not supported",
+                ));
+            }
+        }
 
-                let mut compound_indexes: HashMap<String, Vec<(String, String, Span)>> =
-                    Default::default();
+        Ok(self)
+    }
 
-                for field in &fields_named.named {
-                    let ident = &field.ident;
-                    let mut attr_mode = AttributeMode::Protected;
+    fn field_name(field: &Field, position: usize) -> String {
+        field
+            .ident
+            .as_ref()
+            .map(|ident| ident.to_string())
+            .unwrap_or_else(|| position.to_string())
+    }
 
-                    let field_name = ident
-                        .as_ref()
-                        .ok_or_else(|| {
-                            syn::Error::new_spanned(
-                                field,
-                                "internal error: identifier was not Some",
-                            )
-                        })?
-                        .to_string();
+    /// Parse the attributes of the variants of an enum.
+    ///
+    /// Each variant is stored as a record tagged with the name of the variant. Fields are
+    /// configured by name so a field which appears in more than one variant must be declared with
+    /// the same attributes each time.
+    fn variants(&mut self, data_enum: &DataEnum) -> Result<(), syn::Error> {
+        if data_enum.variants.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &self.ident,
+                "enums must have at least one variant",
+            ));
+        }
 
-                    if field_name.starts_with("__") {
-                        return Err(syn::Error::new_spanned(
-                            field,
-                            format!(
-                                "Invalid field '{field_name}': fields must not be prefixed with __"
-                            ),
-                        ));
-                    }
+        let mut fields: Vec<(String, &Field)> = Vec::new();
 
-                    if RESERVED_FIELD_NAMES.contains(&field_name.as_str()) {
-                        return Err(syn::Error::new_spanned(
-                            field,
-                            format!(
-                                "Invalid field '{field_name}': name is reserved for internal use"
-                            ),
-                        ));
-                    }
+        for variant in &data_enum.variants {
+            let Fields::Named(fields_named) = &variant.fields else {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    format!(
+                        "Invalid variant '{}': enum variants must have named fields",
+                        variant.ident
+                    ),
+                ));
+            };
 
-                    if field_name == "pk" {
-                        let has_partition_key_attr = field
-                            .attrs
-                            .iter()
-                            .any(|x| x.path().is_ident("partition_key"));
+            let mut variant_fields = Vec::with_capacity(fields_named.named.len());
 
-                        if !has_partition_key_attr {
-                            return Err(syn::Error::new_spanned(
-                                field,
-                                "field named 'pk' must be annotated with #[partition_key]"
-                                    .to_string(),
-                            ));
-                        }
-                    }
+            for (i, field) in fields_named.named.iter().enumerate() {
+                let field_name = Self::field_name(field, i);
 
-                    if field_name == "sk" {
-                        let has_partition_key_attr =
-                            field.attrs.iter().any(|x| x.path().is_ident("sort_key"));
+                match fields.iter().find(|(name, _)| name == &field_name) {
+                    Some((_, existing)) => {
+                        let attrs = |field: &Field| {
+                            field
+                                .attrs
+                                .iter()
+                                .map(|attr| attr.to_token_stream().to_string())
+                                .collect::<Vec<_>>()
+                        };
 
-                        if !has_partition_key_attr {
+                        if attrs(existing) != attrs(field) {
                             return Err(syn::Error::new_spanned(
                                 field,
-                                "field named 'sk' must be annotated with #[sort_key]".to_string(),
+                                format!("field '{field_name}' must have the same attributes in every variant"),
                             ));
                         }
                     }
+                    None => fields.push((field_name.clone(), field)),
+                }
+
+                variant_fields.push(field_name);
+            }
+
+            self.variants.push(Variant {
+                ident: variant.ident.clone(),
+                fields: variant_fields,
+            });
+        }
+
+        self.fields(&fields)
+    }
 
-                    // Parse the meta for the field
-                    for attr in &field.attrs {
-                        if attr.path().is_ident("sort_key") {
-                            if explicit_sk && field_name != "sk" {
-                                return Err(syn::Error::new_spanned(
+    fn fields(&mut self, fields: &[(String, &Field)]) -> Result<(), syn::Error> {
+        let all_field_names: Vec<String> = fields.iter().map(|(name, _)| name.clone()).collect();
+
+        let explicit_pk = all_field_names.contains(&String::from("pk"));
+        let explicit_sk = all_field_names.contains(&String::from("sk"));
+
+        let mut compound_indexes: HashMap<String, Vec<(String, String, Span)>> = Default::default();
+        let mut compound_max_terms: HashMap<String, (usize, Span)> = Default::default();
+        let mut compound_term_shards: HashMap<String, (u8, Span)> = Default::default();
+
+        for (field_name, field) in fields {
+            let field_name = field_name.clone();
+            let mut attr_mode = AttributeMode::Protected;
+            let mut path_span: Option<Span> = None;
+            let mut compound_span: Option<Span> = None;
+            let mut query_span: Option<Span> = None;
+            let mut ttl_span: Option<Span> = None;
+
+            if field_name.starts_with("__") {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    format!("Invalid field '{field_name}': fields must not be prefixed with __"),
+                ));
+            }
+
+            if RESERVED_FIELD_NAMES.contains(&field_name.as_str()) {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    format!("Invalid field '{field_name}': name is reserved for internal use"),
+                ));
+            }
+
+            if field_name == "pk" {
+                let has_partition_key_attr = field
+                    .attrs
+                    .iter()
+                    .any(|x| x.path().is_ident("partition_key"));
+
+                if !has_partition_key_attr {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        "field named 'pk' must be annotated with #[partition_key]".to_string(),
+                    ));
+                }
+            }
+
+            if field_name == "sk" && self.composite_sort_key.is_some() {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "field named 'sk' cannot be used with a composite sort key",
+                ));
+            }
+
+            if field_name == "sk" {
+                let has_partition_key_attr =
+                    field.attrs.iter().any(|x| x.path().is_ident("sort_key"));
+
+                if !has_partition_key_attr {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        "field named 'sk' must be annotated with #[sort_key]".to_string(),
+                    ));
+                }
+            }
+
+            // Parse the meta for the field
+            for attr in &field.attrs {
+                if attr.path().is_ident("sort_key") {
+                    if explicit_sk && field_name != "sk" {
+                        return Err(syn::Error::new_spanned(
                                     field,
                                     format!("field '{field_name}' cannot be used as sort key as struct contains field named 'sk' which must be used")
                                 ));
-                            }
+                    }
 
-                            if explicit_sk {
-                                // if the 'sk' field is set then there should be no prefix
-                                // otherwise when deserialising the sk value would be incorrect
-                                self.sort_key_prefix = SortKeyPrefix::None;
-                            }
+                    if explicit_sk {
+                        // if the 'sk' field is set then there should be no prefix
+                        // otherwise when deserialising the sk value would be incorrect
+                        self.sort_key_prefix = SortKeyPrefix::None;
+                    }
 
-                            if let Some(f) = &self.sort_key_field {
-                                return Err(syn::Error::new_spanned(
-                                    field,
-                                    format!("sort key was already specified to be '{f}'"),
-                                ));
-                            }
+                    if self.composite_sort_key.is_some() {
+                        return Err(syn::Error::new_spanned(
+                            field,
+                            format!("field '{field_name}' cannot be used as sort key as a composite sort key was specified with #[sort_key(fields = [...])]"),
+                        ));
+                    }
 
-                            self.sort_key_field = Some(field_name.clone());
-                        }
+                    if let Some(f) = &self.sort_key_field {
+                        return Err(syn::Error::new_spanned(
+                            field,
+                            format!("sort key was already specified to be '{f}'"),
+                        ));
+                    }
 
-                        if attr.path().is_ident("partition_key") {
-                            if explicit_pk && field_name != "pk" {
-                                return Err(syn::Error::new_spanned(
+                    self.sort_key_field = Some(field_name.clone());
+                }
+
+                if attr.path().is_ident("partition_key") {
+                    if explicit_pk && field_name != "pk" {
+                        return Err(syn::Error::new_spanned(
                                     field,
                                     format!("field '{field_name}' cannot be used as partition key as struct contains field named 'pk' which must be used")
                                 ));
-                            }
+                    }
 
-                            if let Some(f) = &self.partition_key_field {
-                                return Err(syn::Error::new_spanned(
-                                    field,
-                                    format!("partition key was already specified to be '{f}'"),
-                                ));
-                            }
+                    if let Some(f) = &self.partition_key_field {
+                        return Err(syn::Error::new_spanned(
+                            field,
+                            format!("partition key was already specified to be '{f}'"),
+                        ));
+                    }
 
-                            self.partition_key_field = Some(field_name.clone());
-                        }
+                    self.partition_key_field = Some(field_name.clone());
+                }
 
-                        if attr.path().is_ident("cipherstash") {
-                            let mut query: Option<(String, String, Span)> = None;
-                            let mut compound_index_name: Option<(String, Span)> = None;
+                if attr.path().is_ident("cipherstash") {
+                    let mut query: Option<(String, String, Span)> = None;
+                    let mut compound_index_name: Option<(String, Span)> = None;
+                    let mut term_shards: Option<(u8, Span)> = None;
+                    let mut max_terms: Option<(usize, Span)> = None;
+                    let mut min_length: Option<(usize, Span)> = None;
+                    let mut max_length: Option<(usize, Span)> = None;
+                    let mut path: Option<(String, Span)> = None;
 
-                            attr.parse_nested_meta(|meta| {
+                    attr.parse_nested_meta(|meta| {
                             let directive = meta.path.get_ident().map(|i| i.to_string());
                             match directive.as_deref() {
 
@@ -247,27 +486,67 @@ impl SettingsBuilder {
                                     attr_mode = AttributeMode::Skipped;
                                     Ok(())
                                 }
+                                Some("ttl") => {
+                                    // The time the record expires for DynamoDB's time-to-live
+                                    ttl_span = meta.path.get_ident().map(|ident| ident.span());
+                                    Ok(())
+                                }
+                                Some("nested") => {
+                                    // Flatten the attributes of the field into a protected map
+                                    attr_mode = AttributeMode::Nested;
+                                    Ok(())
+                                }
+                                Some("collection") => {
+                                    // Protect each element of the field or the whole field as one blob
+                                    let mode = if meta.input.peek(syn::Token![=]) {
+                                        let value = meta.value()?.parse::<LitStr>()?;
+
+                                        match value.value().as_str() {
+                                            "elements" => CollectionMode::Elements,
+                                            "blob" => CollectionMode::Blob,
+                                            other => {
+                                                return Err(syn::Error::new_spanned(
+                                                    value,
+                                                    format!("Unsupported collection mode: {other}. Use \"elements\" or \"blob\""),
+                                                ));
+                                            }
+                                        }
+                                    } else {
+                                        CollectionMode::Elements
+                                    };
+
+                                    attr_mode = AttributeMode::Collection(mode);
+                                    Ok(())
+                                }
+                                Some("serde") => {
+                                    // Serialize the field with serde before it's encrypted
+                                    if !cfg!(feature = "serde") {
+                                        return Err(meta.error("the serde feature of cipherstash-dynamodb must be enabled to use #[cipherstash(serde)]"));
+                                    }
+
+                                    attr_mode = AttributeMode::Serde;
+                                    Ok(())
+                                }
+                                Some("path") => {
+                                    let value = meta.value()?;
+                                    let span = value.span();
+                                    path = Some(( value.parse::<LitStr>()?.value(), span ));
+                                    Ok(())
+                                }
                                 Some("query") => {
                                     let value = meta.value()?;
                                     let index_type_span = value.span();
                                     let index_type = value.parse::<LitStr>()?.value();
-                                    let index_name = ident
-                                        .as_ref()
-                                        .ok_or(meta.error("no index type specified"))?
-                                        .to_string();
+                                    let index_name = field_name.clone();
 
                                     query = Some(( index_name, index_type, index_type_span ));
+                                    query_span = Some(index_type_span);
 
                                     Ok(())
                                 }
                                 Some("compound") => {
                                     let value = meta.value()?;
 
-                                    let field_name = ident
-                                        .as_ref()
-                                        .ok_or(meta.error("no index type specified"))?
-                                        .to_string();
-
                                     let index_name = value.parse::<LitStr>()?.value();
 
                                     let is_valid_index = index_name
@@ -289,6 +568,30 @@ impl SettingsBuilder {
 
                                     Ok(())
                                 }
+                                Some("term_shards") => {
+                                    let value = meta.value()?;
+                                    let span = value.span();
+                                    term_shards = Some(( Self::parse_term_shards(&value.parse()?)?, span ));
+                                    Ok(())
+                                }
+                                Some("max_terms") => {
+                                    let value = meta.value()?;
+                                    let span = value.span();
+                                    max_terms = Some(( Self::parse_positive(&value.parse()?, "max_terms")?, span ));
+                                    Ok(())
+                                }
+                                Some("min_length") => {
+                                    let value = meta.value()?;
+                                    let span = value.span();
+                                    min_length = Some(( Self::parse_positive(&value.parse()?, "min_length")?, span ));
+                                    Ok(())
+                                }
+                                Some("max_length") => {
+                                    let value = meta.value()?;
+                                    let span = value.span();
+                                    max_length = Some(( Self::parse_positive(&value.parse()?, "max_length")?, span ));
+                                    Ok(())
+                                }
                                 Some("encryptable_with") => {
                                     let value = meta.value()?;
                                     let handler = value.parse::<ExprPath>()?;
@@ -305,49 +608,214 @@ impl SettingsBuilder {
                             }
                         })?;
 
-                            match (query, compound_index_name) {
-                                (
-                                    Some((index_name, index_type, span)),
-                                    Some((compound_index_name, _)),
-                                ) => {
-                                    compound_indexes
-                                        .entry(compound_index_name)
-                                        .or_default()
-                                        .push((index_name, index_type, span));
+                    // Index a field of a nested type
+                    if let Some((path, span)) = path {
+                        let Some((index_name, _, _)) = &mut query else {
+                            return Err(syn::Error::new(
+                                span,
+                                "path was specified but no query options were. Specify how the nested field should be queried with the attribute #[cipherstash(query = <option>, path = <path>)]",
+                            ));
+                        };
+
+                        if compound_index_name.is_some() {
+                            return Err(syn::Error::new(
+                                span,
+                                "path is not supported for compound indexes",
+                            ));
+                        }
+
+                        *index_name = format!("{index_name}.{path}");
+                        path_span = Some(span);
+                    }
+
+                    if let Some((_, span)) = term_shards {
+                        if query.is_none() && compound_index_name.is_none() {
+                            return Err(syn::Error::new(
+                                        span,
+                                        "term_shards was specified but no query options were. Specify how this field should be queried with the attribute #[cipherstash(query = <option>, term_shards = <shards>)]"
+                                    ));
+                        }
+                    }
+
+                    if let Some((_, span)) = max_terms.or(min_length).or(max_length) {
+                        if query.is_none() {
+                            return Err(syn::Error::new(
+                                        span,
+                                        "Index options were specified but no query options were. Specify how this field should be queried with the attribute #[cipherstash(query = <option>, ...)]"
+                                    ));
+                        }
+                    }
+
+                    if let Some((_, span)) = min_length.or(max_length) {
+                        if !matches!(&query, Some((_, index_type, _)) if index_type == "prefix") {
+                            return Err(syn::Error::new(
+                                span,
+                                "min_length and max_length are only supported for prefix indexes",
+                            ));
+                        }
+                    }
+
+                    if let (Some((min, span)), Some((max, _))) = (min_length, max_length) {
+                        if min > max {
+                            return Err(syn::Error::new(
+                                        span,
+                                        format!("min_length ({min}) must not be greater than max_length ({max})"),
+                                    ));
+                        }
+                    }
+
+                    let prefix_lengths = PrefixLengths {
+                        min: min_length.map(|(min, _)| min),
+                        max: max_length.map(|(max, _)| max),
+                    };
+
+                    match (query, compound_index_name) {
+                        (
+                            Some((index_name, index_type, span)),
+                            Some((compound_index_name, compound_index_span)),
+                        ) => {
+                            compound_span = Some(compound_index_span);
+
+                            if let Some((max_terms, max_terms_span)) = max_terms {
+                                if let Some((existing, _)) = compound_max_terms.insert(
+                                    compound_index_name.clone(),
+                                    (max_terms, max_terms_span),
+                                ) {
+                                    if existing != max_terms {
+                                        return Err(syn::Error::new(
+                                                    max_terms_span,
+                                                    format!("max_terms for compound index '{compound_index_name}' was already specified as {existing}"),
+                                                ));
+                                    }
                                 }
+                            }
 
-                                (Some((index_name, index_type, span)), None) => {
-                                    self.add_index(index_name, index_type.as_ref(), span)?;
+                            if let Some((shards, shards_span)) = term_shards {
+                                if let Some((existing, _)) = compound_term_shards
+                                    .insert(compound_index_name.clone(), (shards, shards_span))
+                                {
+                                    if existing != shards {
+                                        return Err(syn::Error::new(
+                                                    shards_span,
+                                                    format!("term_shards for compound index '{compound_index_name}' was already specified as {existing}"),
+                                                ));
+                                    }
                                 }
+                            }
+
+                            self.prefix_lengths.insert(
+                                (compound_index_name.clone(), index_name.clone()),
+                                prefix_lengths,
+                            );
+
+                            compound_indexes
+                                .entry(compound_index_name)
+                                .or_default()
+                                .push((index_name, index_type, span));
+                        }
+
+                        (Some((index_name, index_type, span)), None) => {
+                            if let Some((max_terms, _)) = max_terms {
+                                self.index_max_terms
+                                    .insert((index_name.clone(), index_type.clone()), max_terms);
+                            }
 
-                                (None, Some((compound_index_name, span))) => {
-                                    return Err(syn::Error::new(
+                            if let Some((shards, _)) = term_shards {
+                                self.index_term_shards
+                                    .insert((index_name.clone(), index_type.clone()), shards);
+                            }
+
+                            // Only prefix indexes take lengths so don't let another index on the
+                            // same field overwrite them.
+                            if index_type == "prefix" {
+                                self.prefix_lengths.insert(
+                                    (index_name.clone(), index_name.clone()),
+                                    prefix_lengths,
+                                );
+                            }
+
+                            self.add_index(index_name, index_type.as_ref(), span)?;
+                        }
+
+                        (None, Some((compound_index_name, span))) => {
+                            return Err(syn::Error::new(
                                         span,
                                         format!("Compound attribute was specified but no query options were. Specify how this field should be queried with the attribute #[cipherstash(query = <option>, compound = \"{compound_index_name}\")]"))
                                     );
-                                }
-
-                                (None, None) => {}
-                            };
                         }
-                    }
 
-                    self.add_attribute(
-                        ident
-                            .as_ref()
-                            .ok_or(syn::Error::new_spanned(field, "missing field"))?
-                            .to_string(),
-                        attr_mode,
-                    );
+                        (None, None) => {}
+                    };
+                }
+            }
+
+            if let Some(span) = path_span {
+                if !matches!(attr_mode, AttributeMode::Nested) {
+                    return Err(syn::Error::new(
+                        span,
+                        format!("path is only supported on nested fields. Annotate '{field_name}' with #[cipherstash(nested)]"),
+                    ));
+                }
+            }
+
+            if let Some(span) = query_span {
+                if matches!(attr_mode, AttributeMode::Serde) {
+                    return Err(syn::Error::new(
+                        span,
+                        format!("serde fields can't be queried. Remove the query options from '{field_name}'"),
+                    ));
+                }
+            }
+
+            if let Some(span) = compound_span {
+                if matches!(attr_mode, AttributeMode::Collection(_)) {
+                    return Err(syn::Error::new(
+                        span,
+                        format!("compound indexes are not supported on collection fields. Remove the compound attribute from '{field_name}'"),
+                    ));
+                }
+            }
+
+            if let Some(span) = ttl_span {
+                if !matches!(attr_mode, AttributeMode::Plaintext) {
+                    return Err(syn::Error::new(
+                        span,
+                        format!("ttl fields must be stored in plaintext so DynamoDB can read them. Annotate '{field_name}' with #[cipherstash(plaintext)]"),
+                    ));
                 }
 
-                for (name, parts) in compound_indexes.into_iter() {
-                    self.add_compound_index(name, parts)?;
+                if let Some(f) = &self.ttl_attribute {
+                    return Err(syn::Error::new(
+                        span,
+                        format!("ttl was already specified to be '{f}'"),
+                    ));
                 }
+
+                self.ttl_attribute = Some(field_name.clone());
+            }
+
+            self.field_types
+                .entry(field_name.clone())
+                .or_insert_with(|| field.ty.clone());
+
+            self.add_attribute(field_name, attr_mode);
+        }
+
+        for (name, parts) in compound_indexes.into_iter() {
+            let index = self.add_compound_index(name.clone(), parts)?;
+
+            if let Some((max_terms, _)) = compound_max_terms.remove(&name) {
+                self.index_max_terms
+                    .insert((name.clone(), index.to_string()), max_terms);
+            }
+
+            if let Some((shards, _)) = compound_term_shards.remove(&name) {
+                self.index_term_shards
+                    .insert((name, index.to_string()), shards);
             }
         }
 
-        Ok(self)
+        Ok(())
     }
 
     pub(crate) fn build(self) -> Result<Settings, syn::Error> {
@@ -356,27 +824,120 @@ impl SettingsBuilder {
             type_name,
             sort_key_prefix,
             sort_key_field,
+            composite_sort_key,
+            sort_key_separator,
             partition_key_field,
             protected_attributes,
-            unprotected_attributes,
+            mut unprotected_attributes,
             skipped_attributes,
+            nested_attributes,
+            collection_attributes,
+            serde_attributes,
+            field_types,
             indexes,
+            term_shards,
+            bind_to_primary_key,
+            record_mac,
+            soft_delete,
+            ttl_attribute,
+            tag,
+            variants,
+            index_term_shards,
+            index_max_terms,
+            prefix_lengths,
             encrypt_handlers,
             decrypt_handlers,
         } = self;
 
         let sort_key_prefix = sort_key_prefix.into_prefix(&type_name);
 
+        let sort_key_fields = match composite_sort_key {
+            Some((fields, span)) => {
+                for field in &fields {
+                    if !field_types.contains_key(field) {
+                        return Err(syn::Error::new(
+                            span,
+                            format!("sort key field '{field}' does not exist"),
+                        ));
+                    }
+
+                    if skipped_attributes.contains(field) {
+                        return Err(syn::Error::new(
+                            span,
+                            format!("sort key field '{field}' must not be skipped"),
+                        ));
+                    }
+                }
+
+                fields
+            }
+            None => sort_key_field.into_iter().collect(),
+        };
+
+        let tag = match (tag, variants.is_empty()) {
+            (Some((_, span)), true) => {
+                return Err(syn::Error::new(span, "tag is only supported on enums"));
+            }
+            (_, true) => None,
+            (tag, false) => {
+                let (tag, span) =
+                    tag.unwrap_or_else(|| (DEFAULT_TAG.to_string(), Span::call_site()));
+
+                if protected_attributes
+                    .iter()
+                    .chain(unprotected_attributes.iter())
+                    .chain(skipped_attributes.iter())
+                    .any(|field| field == &tag)
+                {
+                    return Err(syn::Error::new(
+                        span,
+                        format!("tag '{tag}' must not be the name of a field. Use #[cipherstash(tag = \"...\")] to choose a different name"),
+                    ));
+                }
+
+                // Every variant must provide the primary key
+                for variant in &variants {
+                    for key in partition_key_field.iter().chain(sort_key_fields.iter()) {
+                        if !variant.fields.contains(key) {
+                            return Err(syn::Error::new_spanned(
+                                &variant.ident,
+                                format!("variant '{}' is missing key field '{key}'", variant.ident),
+                            ));
+                        }
+                    }
+                }
+
+                unprotected_attributes.push(tag.clone());
+
+                Some(tag)
+            }
+        };
+
         Ok(Settings {
             ident,
             sort_key_prefix,
             type_name,
-            sort_key_field,
+            sort_key_fields,
+            sort_key_separator,
             partition_key_field,
             protected_attributes,
             unprotected_attributes,
             skipped_attributes,
+            nested_attributes,
+            collection_attributes,
+            serde_attributes,
+            field_types,
             indexes,
+            term_shards,
+            bind_to_primary_key,
+            record_mac,
+            soft_delete,
+            ttl_attribute,
+            tag,
+            variants,
+            index_term_shards,
+            index_max_terms,
+            prefix_lengths,
             encrypt_handlers,
             decrypt_handlers,
         })
@@ -397,6 +958,18 @@ impl SettingsBuilder {
             AttributeMode::Protected => self.protected_attributes.push(value),
             AttributeMode::Plaintext => self.unprotected_attributes.push(value),
             AttributeMode::Skipped => self.skipped_attributes.push(value),
+            AttributeMode::Nested => {
+                self.nested_attributes.push(value.clone());
+                self.protected_attributes.push(value);
+            }
+            AttributeMode::Serde => {
+                self.serde_attributes.push(value.clone());
+                self.protected_attributes.push(value);
+            }
+            AttributeMode::Collection(mode) => {
+                self.collection_attributes.push((value.clone(), mode));
+                self.protected_attributes.push(value);
+            }
         }
     }
 
@@ -432,7 +1005,7 @@ impl SettingsBuilder {
         &mut self,
         name: String,
         parts: Vec<(String, String, Span)>,
-    ) -> Result<(), syn::Error> {
+    ) -> Result<IndexType, syn::Error> {
         let name_parts = name.split('#').collect::<Vec<_>>();
 
         if name_parts.len() > parts.len() {
@@ -515,8 +1088,8 @@ impl SettingsBuilder {
             ));
         }
 
-        self.indexes.push(index);
+        self.indexes.push(index.clone());
 
-        Ok(())
+        Ok(index)
     }
 }
@@ -1,8 +1,20 @@
-use std::fmt::Display;
+use std::{collections::HashMap, fmt::Display};
 
+use super::Settings;
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 
+/// These match the defaults used by `PrefixIndex::new`
+const DEFAULT_PREFIX_MIN_LENGTH: usize = 3;
+const DEFAULT_PREFIX_MAX_LENGTH: usize = 10;
+
+/// Token lengths for a prefix index when they differ from the defaults.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct PrefixLengths {
+    pub(crate) min: Option<usize>,
+    pub(crate) max: Option<usize>,
+}
+
 #[derive(Clone, PartialEq)]
 pub(crate) enum IndexType {
     Single(String, String),
@@ -73,47 +85,118 @@ impl IndexType {
         }
     }
 
-    pub(crate) fn to_compound_plaintext_access(&self) -> Result<TokenStream, syn::Error> {
+    /// Returns the names of the fields included in the index.
+    pub(crate) fn fields(&self) -> Vec<&str> {
         match self {
-            Self::Single(field, _) => {
-                let field = format_ident!("{field}");
+            Self::Single(field, _) => vec![field],
+            Self::Compound2((field_a, _), (field_b, _)) => vec![field_a, field_b],
+        }
+    }
 
-                Ok(quote! {
-                    self.#field.clone().try_into().ok()
-                })
-            }
+    pub(crate) fn to_compound_plaintext_access(
+        &self,
+        settings: &Settings,
+    ) -> Result<TokenStream, syn::Error> {
+        match self {
+            Self::Single(field, _) => Ok(settings.with_fields(
+                &[field],
+                |fields| {
+                    let field = &fields[0];
+
+                    quote! {
+                        #field.clone().try_into().ok()
+                    }
+                },
+                quote! { None },
+            )),
+
+            Self::Compound2((field_a, _), (field_b, _)) => Ok(settings.with_fields(
+                &[field_a, field_b],
+                |fields| {
+                    let (field_a, field_b) = (&fields[0], &fields[1]);
+
+                    quote! {
+                        ( #field_a.clone(), #field_b.clone() ).try_into().ok()
+                    }
+                },
+                quote! { None },
+            )),
+        }
+    }
 
-            Self::Compound2((field_a, _), (field_b, _)) => {
-                let field_a = format_ident!("{field_a}");
-                let field_b = format_ident!("{field_b}");
+    /// Returns an expression which evaluates to the plaintexts of the elements of a collection
+    /// field. Compound indexes aren't supported on collections so this only applies to single
+    /// indexes.
+    pub(crate) fn to_compound_plaintext_elements_access(&self, settings: &Settings) -> TokenStream {
+        settings.with_fields(
+            &self.fields(),
+            |fields| {
+                let field = &fields[0];
+
+                quote! {
+                    #field
+                        .iter()
+                        .cloned()
+                        .map(|element| element.try_into())
+                        .collect::<Result<Vec<_>, _>>()
+                        .ok()
+                }
+            },
+            quote! { None },
+        )
+    }
+
+    fn part_to_cipherstash_dynamodb_indexer(
+        index_type: &str,
+        prefix_lengths: Option<&PrefixLengths>,
+    ) -> Result<TokenStream, syn::Error> {
+        let index_type_ident = Self::type_to_ident(index_type)?;
+
+        // Lengths are keyed by field so only apply them to the prefix index of that field
+        match prefix_lengths.filter(|_| index_type == "prefix") {
+            Some(PrefixLengths { min, max }) if min.is_some() || max.is_some() => {
+                let min = min.unwrap_or(DEFAULT_PREFIX_MIN_LENGTH);
+                let max = max.unwrap_or(DEFAULT_PREFIX_MAX_LENGTH);
 
                 Ok(quote! {
-                    ( self.#field_a.clone(), self.#field_b.clone() ).try_into().ok()
+                    cipherstash_dynamodb::encryption::compound_indexer::#index_type_ident::new_with_opts(vec![], #min, #max)
                 })
             }
+            _ => Ok(quote! {
+                cipherstash_dynamodb::encryption::compound_indexer::#index_type_ident::new(vec![])
+            }),
         }
     }
 
-    pub(crate) fn to_cipherstash_dynamodb_indexer(&self) -> Result<TokenStream, syn::Error> {
+    pub(crate) fn to_cipherstash_dynamodb_indexer(
+        &self,
+        prefix_lengths: &HashMap<(String, String), PrefixLengths>,
+    ) -> Result<TokenStream, syn::Error> {
+        let index_name = self.index_name();
+        let lengths_for = |field: &String| prefix_lengths.get(&(index_name.clone(), field.clone()));
+
         match self {
-            Self::Single(_, index_type) => {
-                let index_type = Self::type_to_ident(index_type)?;
+            Self::Single(field, index_type) => {
+                let indexer =
+                    Self::part_to_cipherstash_dynamodb_indexer(index_type, lengths_for(field))?;
 
                 Ok(quote! {
-                    Box::new(cipherstash_dynamodb::encryption::compound_indexer::#index_type::new(vec![]))
+                    Box::new(#indexer)
                 })
             }
 
-            Self::Compound2((_field_a, index_a), (_field_b, index_b)) => {
-                let index_a = Self::type_to_ident(index_a)?;
-                let index_b = Self::type_to_ident(index_b)?;
+            Self::Compound2((field_a, index_a), (field_b, index_b)) => {
+                let indexer_a =
+                    Self::part_to_cipherstash_dynamodb_indexer(index_a, lengths_for(field_a))?;
+                let indexer_b =
+                    Self::part_to_cipherstash_dynamodb_indexer(index_b, lengths_for(field_b))?;
 
                 Ok(quote! {
                     Box::new(
                         cipherstash_dynamodb::encryption::compound_indexer::CompoundIndex::new(
-                            cipherstash_dynamodb::encryption::compound_indexer::#index_a::new(vec![])
+                            #indexer_a
                         ).and(
-                            cipherstash_dynamodb::encryption::compound_indexer::#index_b::new(vec![])
+                            #indexer_b
                         ))
                 })
             }
@@ -2,22 +2,53 @@ mod builder;
 pub mod index_type;
 use std::collections::HashMap;
 
-use self::{builder::SettingsBuilder, index_type::IndexType};
+use self::{
+    builder::SettingsBuilder,
+    index_type::{IndexType, PrefixLengths},
+};
 use itertools::Itertools;
-use proc_macro2::Ident;
-use syn::{DeriveInput, ExprPath};
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+use syn::{DeriveInput, ExprPath, Type};
 
 pub(crate) enum AttributeMode {
     Protected,
     Plaintext,
     Skipped,
+    Nested,
+    Collection(CollectionMode),
+    Serde,
+}
+
+/// How the elements of a protected collection are encrypted.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum CollectionMode {
+    /// Each element is encrypted separately.
+    Elements,
+
+    /// The whole collection is encrypted as a single plaintext.
+    Blob,
+}
+
+/// A variant of an enum which is stored as a record tagged with the name of the variant.
+pub(crate) struct Variant {
+    pub(crate) ident: Ident,
+
+    /// Names of the fields of the variant.
+    pub(crate) fields: Vec<String>,
 }
 
 pub(crate) struct Settings {
     ident: Ident,
     pub(crate) sort_key_prefix: Option<String>,
     pub(crate) type_name: String,
-    pub(crate) sort_key_field: Option<String>,
+
+    /// Fields which make up the sort key. Empty if the record only has a partition key.
+    pub(crate) sort_key_fields: Vec<String>,
+
+    /// Separator between the fields of a composite sort key.
+    pub(crate) sort_key_separator: String,
+
     pub(crate) partition_key_field: Option<String>,
     protected_attributes: Vec<String>,
     unprotected_attributes: Vec<String>,
@@ -31,7 +62,51 @@ pub(crate) struct Settings {
     /// Skipped attributes are never encrypted by the `DecryptedRecord` trait will
     /// use these to reconstruct the struct via `Default` (like serde).
     skipped_attributes: Vec<String>,
+
+    /// Protected attributes whose type is itself `Encryptable` and `Decryptable` and which are
+    /// flattened into a protected map.
+    nested_attributes: Vec<String>,
+
+    /// Protected attributes which are collections (e.g. `Vec<String>`) along with how their
+    /// elements are encrypted.
+    collection_attributes: Vec<(String, CollectionMode)>,
+
+    /// Protected attributes which are serialized with serde.
+    serde_attributes: Vec<String>,
+
+    /// Map of field names to their types.
+    field_types: HashMap<String, Type>,
     indexes: Vec<IndexType>,
+
+    /// Number of shards to spread index terms across unless overridden for an index.
+    pub(crate) term_shards: u8,
+
+    /// Whether encrypted attributes are bound to the primary key of the record.
+    pub(crate) bind_to_primary_key: bool,
+
+    /// Whether a MAC covering the unprotected attributes is stored with each record.
+    pub(crate) record_mac: bool,
+
+    /// Whether deleting a record marks it as deleted instead of removing it.
+    pub(crate) soft_delete: bool,
+
+    /// Name of the plaintext attribute which holds the time the record expires.
+    pub(crate) ttl_attribute: Option<String>,
+
+    /// Name of the plaintext attribute which stores the variant of an enum.
+    pub(crate) tag: Option<String>,
+
+    /// Variants of an enum. Empty for structs.
+    pub(crate) variants: Vec<Variant>,
+
+    /// Map of index name and type to the number of shards to spread its terms across.
+    index_term_shards: HashMap<(String, String), u8>,
+
+    /// Map of index name and type to the maximum number of terms stored for that index.
+    index_max_terms: HashMap<(String, String), usize>,
+
+    /// Map of index name and field to the token lengths used when that field is prefix indexed.
+    prefix_lengths: HashMap<(String, String), PrefixLengths>,
 }
 
 impl Settings {
@@ -56,6 +131,9 @@ impl Settings {
             .iter()
             .filter(|s| !self.encrypt_handlers.contains_key(s.as_str()))
             .filter(|s| !self.decrypt_handlers.contains_key(s.as_str()))
+            .filter(|s| !self.nested_attributes.contains(s))
+            .filter(|s| !self.is_collection(s))
+            .filter(|s| !self.serde_attributes.contains(s))
             .map(|s| s.as_str())
             .sorted()
             .collect::<Vec<_>>()
@@ -77,6 +155,41 @@ impl Settings {
             .collect::<Vec<_>>()
     }
 
+    pub(crate) fn nested_attributes(&self) -> Vec<&str> {
+        self.nested_attributes
+            .iter()
+            .map(|s| s.as_str())
+            .sorted()
+            .collect::<Vec<_>>()
+    }
+
+    pub(crate) fn collection_attributes(&self) -> Vec<(&str, CollectionMode)> {
+        self.collection_attributes
+            .iter()
+            .map(|(s, mode)| (s.as_str(), *mode))
+            .sorted_by_key(|(s, _)| *s)
+            .collect::<Vec<_>>()
+    }
+
+    pub(crate) fn serde_attributes(&self) -> Vec<&str> {
+        self.serde_attributes
+            .iter()
+            .map(|s| s.as_str())
+            .sorted()
+            .collect::<Vec<_>>()
+    }
+
+    pub(crate) fn field_type(&self, name: &str) -> Option<&Type> {
+        self.field_types.get(name)
+    }
+
+    /// Returns true if the field, `name`, is a protected collection.
+    pub(crate) fn is_collection(&self, name: &str) -> bool {
+        self.collection_attributes
+            .iter()
+            .any(|(attr, _)| attr == name)
+    }
+
     pub(crate) fn skipped_attributes(&self) -> Vec<&str> {
         self.skipped_attributes
             .iter()
@@ -95,7 +208,106 @@ impl Settings {
             .collect()
     }
 
+    pub(crate) fn max_terms(&self, index: &IndexType) -> Option<usize> {
+        self.index_max_terms
+            .get(&(index.index_name(), index.to_string()))
+            .copied()
+    }
+
+    pub(crate) fn term_shards(&self, index: &IndexType) -> Option<u8> {
+        self.index_term_shards
+            .get(&(index.index_name(), index.to_string()))
+            .copied()
+    }
+
+    pub(crate) fn prefix_lengths(&self) -> &HashMap<(String, String), PrefixLengths> {
+        &self.prefix_lengths
+    }
+
     pub(crate) fn get_partition_key(&self) -> Option<String> {
         self.partition_key_field.clone()
     }
+
+    /// Generate an expression which evaluates `f` with the `fields` of `self`.
+    ///
+    /// A field may be a path to a field of a nested type (e.g. `address.postcode`).
+    /// For enums this matches on each variant which has all of the `fields` and evaluates to
+    /// `default` for the other variants.
+    pub(crate) fn with_fields(
+        &self,
+        fields: &[&str],
+        f: impl Fn(Vec<TokenStream>) -> TokenStream,
+        default: TokenStream,
+    ) -> TokenStream {
+        let split = |field: &str| {
+            let mut segments = field.split('.');
+            let root = segments.next().unwrap_or_default().to_string();
+            let rest = segments.map(field_member).collect::<Vec<_>>();
+            (root, rest)
+        };
+
+        if self.variants.is_empty() {
+            return f(fields
+                .iter()
+                .map(|field| {
+                    let (root, rest) = split(field);
+                    let member = field_member(&root);
+                    quote! { self.#member #(.#rest)* }
+                })
+                .collect());
+        }
+
+        let roots = fields
+            .iter()
+            .map(|field| split(field).0)
+            .unique()
+            .collect::<Vec<_>>();
+
+        let matching = self
+            .variants
+            .iter()
+            .filter(|variant| roots.iter().all(|root| variant.fields.contains(root)))
+            .collect::<Vec<_>>();
+
+        let arms = matching.iter().map(|variant| {
+            let variant_ident = &variant.ident;
+            let bindings = roots.iter().map(|root| format_ident!("{root}"));
+            let body = f(fields
+                .iter()
+                .map(|field| {
+                    let (root, rest) = split(field);
+                    let binding = format_ident!("{root}");
+                    quote! { #binding #(.#rest)* }
+                })
+                .collect());
+
+            quote! {
+                Self::#variant_ident { #(#bindings,)* .. } => #body
+            }
+        });
+
+        let default_arm = (matching.len() < self.variants.len()).then(|| quote! { _ => #default });
+
+        quote! {
+            match self {
+                #(#arms,)*
+                #default_arm
+            }
+        }
+    }
+}
+
+/// Returns the tokens used to access the field, `name`, of a struct. Fields of tuple structs are
+/// named after their position.
+pub(crate) fn field_member(name: &str) -> TokenStream {
+    match name.parse::<usize>() {
+        Ok(position) => {
+            let index = syn::Index::from(position);
+            quote! { #index }
+        }
+        Err(_) => {
+            let ident = format_ident!("{name}");
+            quote! { #ident }
+        }
+    }
 }
@@ -0,0 +1,15 @@
+use proc_macro2::TokenStream;
+
+/// Return the generated body of the method `name` up to the next method.
+pub(crate) fn method<'a>(expanded: &'a str, name: &str) -> &'a str {
+    let (_, body) = expanded
+        .split_once(&format!("fn {name} "))
+        .unwrap_or_else(|| panic!("Method {name} was not generated"));
+
+    body.split(" fn ").next().unwrap_or(body)
+}
+
+/// Returns true if the `expected` tokens appear in the `expanded` output of a derive.
+pub(crate) fn contains(expanded: &str, expected: TokenStream) -> bool {
+    expanded.contains(&expected.to_string())
+}
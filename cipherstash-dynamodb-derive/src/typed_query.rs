@@ -0,0 +1,257 @@
+use crate::settings::{index_type::IndexType, Settings};
+use itertools::Itertools;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{DeriveInput, Ident};
+
+/// A field and the operator used to query it in one of the declared indexes.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Part {
+    field: String,
+    index_type: String,
+}
+
+impl Part {
+    fn method_ident(&self) -> Ident {
+        let field = self.field.replace('.', "_");
+        let operator = match self.index_type.as_str() {
+            "prefix" => "starts_with",
+            _ => "eq",
+        };
+
+        // Fields of tuple structs are named after their position
+        if field.starts_with(|c: char| c.is_ascii_digit()) {
+            format_ident!("field_{field}_{operator}")
+        } else {
+            format_ident!("{field}_{operator}")
+        }
+    }
+
+    fn single_index(&self) -> TokenStream {
+        match self.index_type.as_str() {
+            "prefix" => quote! { cipherstash_dynamodb::SingleIndex::Prefix },
+            _ => quote! { cipherstash_dynamodb::SingleIndex::Exact },
+        }
+    }
+}
+
+/// Generate a query builder for the type which only allows the queries supported by its indexes.
+///
+/// The builder is a struct called `<Type>Query` whose const parameters are the ids of the parts
+/// of the query added so far (0 for none). Each method adds a part and moves to the state with
+/// that part so only combinations which make up an index can be built. States which match an
+/// index implement `TypedQuery`.
+pub(crate) fn derive_typed_query(input: &DeriveInput, settings: &Settings) -> TokenStream {
+    let indexes = settings.indexes();
+
+    if indexes.is_empty() {
+        return quote! {};
+    }
+
+    let ident = settings.ident();
+    let vis = &input.vis;
+    let query_ident = format_ident!("{ident}Query");
+
+    let index_parts = indexes
+        .iter()
+        .map(|index| match index {
+            IndexType::Single(field, index_type) => vec![Part {
+                field: field.clone(),
+                index_type: index_type.clone(),
+            }],
+            IndexType::Compound2((field_a, index_a), (field_b, index_b)) => vec![
+                Part {
+                    field: field_a.clone(),
+                    index_type: index_a.clone(),
+                },
+                Part {
+                    field: field_b.clone(),
+                    index_type: index_b.clone(),
+                },
+            ],
+        })
+        .collect::<Vec<_>>();
+
+    let parts = index_parts
+        .iter()
+        .flatten()
+        .cloned()
+        .unique()
+        .sorted()
+        .collect::<Vec<_>>();
+    let part_id = |part: &Part| {
+        parts
+            .iter()
+            .position(|p| p == part)
+            .expect("parts include the parts of every index")
+            + 1
+    };
+
+    // The state reached after adding both parts of a compound index, regardless of their order
+    let state = |a: usize, b: usize| if b != 0 && b < a { (b, a) } else { (a, b) };
+
+    let method = |part: &Part, from: (usize, usize), to: (usize, usize)| {
+        let method_ident = part.method_ident();
+        let field = &part.field;
+        let single_index = part.single_index();
+        let (from_a, from_b) = from;
+        let (to_a, to_b) = to;
+
+        // Fields of nested types and elements of collections accept any plaintext
+        let (value_type, plaintext) = match settings.field_type(field) {
+            Some(ty) if !settings.is_collection(field) => (
+                quote! { impl Into<#ty> },
+                quote! { cipherstash_dynamodb::traits::Plaintext::from(value.into()) },
+            ),
+            _ => (
+                quote! { impl Into<cipherstash_dynamodb::traits::Plaintext> },
+                quote! { value.into() },
+            ),
+        };
+
+        quote! {
+            impl #query_ident<#from_a, #from_b> {
+                pub fn #method_ident(mut self, value: #value_type) -> #query_ident<#to_a, #to_b> {
+                    self.parts.push((#field.to_string(), #single_index, #plaintext));
+                    #query_ident { parts: self.parts }
+                }
+            }
+        }
+    };
+
+    let mut methods = vec![];
+    let mut complete = vec![];
+
+    for part in index_parts.iter().flatten().unique() {
+        let id = part_id(part);
+        methods.push(method(part, (0, 0), (id, 0)));
+    }
+
+    for parts in &index_parts {
+        match parts.as_slice() {
+            [part] => complete.push((part_id(part), 0)),
+            [part_a, part_b] => {
+                let (a, b) = (part_id(part_a), part_id(part_b));
+                let to = state(a, b);
+
+                methods.push(method(part_b, (a, 0), to));
+                methods.push(method(part_a, (b, 0), to));
+                complete.push(to);
+            }
+            _ => {}
+        }
+    }
+
+    let typed_query_impls = complete.iter().map(|(a, b)| {
+        quote! {
+            impl cipherstash_dynamodb::TypedQuery for #query_ident<#a, #b> {
+                type Searchable = #ident;
+
+                fn into_parts(self) -> Vec<(String, cipherstash_dynamodb::SingleIndex, cipherstash_dynamodb::traits::Plaintext)> {
+                    self.parts
+                }
+            }
+        }
+    });
+
+    let doc = format!(
+        "A query for [`{ident}`] which can only be built for the indexes declared on it. Start one with [`{ident}::query`] and run it with `EncryptedTable::typed_query`."
+    );
+
+    quote! {
+        #[doc = #doc]
+        #[allow(dead_code)]
+        #vis struct #query_ident<const A: usize = 0, const B: usize = 0> {
+            parts: Vec<(String, cipherstash_dynamodb::SingleIndex, cipherstash_dynamodb::traits::Plaintext)>,
+        }
+
+        #[allow(dead_code)]
+        impl #ident {
+            /// Start a query which can only be built for the indexes declared on this type.
+            #vis fn query() -> #query_ident {
+                #query_ident { parts: vec![] }
+            }
+        }
+
+        #(
+            #[allow(dead_code)]
+            #methods
+        )*
+
+        #(#typed_query_impls)*
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::contains;
+    use syn::parse_quote;
+
+    fn expand(input: DeriveInput) -> String {
+        let settings = Settings::builder(&input)
+            .container_attributes(&input)
+            .and_then(|builder| builder.field_attributes(&input))
+            .and_then(|builder| builder.build())
+            .expect("Failed to parse settings");
+
+        derive_typed_query(&input, &settings).to_string()
+    }
+
+    #[test]
+    fn test_compound_index_states() {
+        let expanded = expand(parse_quote! {
+            pub struct User {
+                #[partition_key]
+                #[cipherstash(query = "exact")]
+                #[cipherstash(query = "exact", compound = "email#name")]
+                email: String,
+
+                #[cipherstash(query = "prefix")]
+                #[cipherstash(query = "prefix", compound = "email#name")]
+                name: String,
+            }
+        });
+
+        // Either part of the compound index can be added first
+        let name_after_email = quote! {
+            impl UserQuery<1usize, 0usize> {
+                pub fn name_starts_with(mut self, value: impl Into<String>) -> UserQuery<1usize, 2usize> {
+                    self.parts.push(("name".to_string(), cipherstash_dynamodb::SingleIndex::Prefix, cipherstash_dynamodb::traits::Plaintext::from(value.into())));
+                    UserQuery { parts: self.parts }
+                }
+            }
+        };
+
+        let email_after_name = quote! {
+            pub fn email_eq(mut self, value: impl Into<String>) -> UserQuery<1usize, 2usize>
+        };
+
+        assert!(contains(&expanded, name_after_email));
+        assert!(contains(&expanded, email_after_name));
+
+        for (a, b) in [(1usize, 0usize), (2, 0), (1, 2)] {
+            assert!(contains(
+                &expanded,
+                quote! { impl cipherstash_dynamodb::TypedQuery for UserQuery<#a, #b> }
+            ));
+        }
+
+        assert!(!contains(
+            &expanded,
+            quote! { impl cipherstash_dynamodb::TypedQuery for UserQuery<0usize, 0usize> }
+        ));
+    }
+
+    #[test]
+    fn test_no_indexes() {
+        let expanded = expand(parse_quote! {
+            struct User {
+                #[partition_key]
+                email: String,
+            }
+        });
+
+        assert!(expanded.is_empty());
+    }
+}
@@ -30,8 +30,7 @@ impl User {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cipherstash_dynamodb::{IndexType, SingleIndex};
-    use std::borrow::Cow;
+    use cipherstash_dynamodb::{IndexType, ProtectedIndex, SingleIndex};
 
     #[test]
     fn test_cipherstash_typename() {
@@ -52,18 +51,12 @@ mod tests {
         assert_eq!(
             User::protected_indexes(),
             vec![
-                (
-                    Cow::Borrowed("email"),
-                    IndexType::Single(SingleIndex::Exact)
-                ),
-                (
-                    Cow::Borrowed("email#name"),
+                ProtectedIndex::new("email", IndexType::Single(SingleIndex::Exact)),
+                ProtectedIndex::new(
+                    "email#name",
                     IndexType::Compound2((SingleIndex::Exact, SingleIndex::Prefix))
                 ),
-                (
-                    Cow::Borrowed("name"),
-                    IndexType::Single(SingleIndex::Prefix)
-                ),
+                ProtectedIndex::new("name", IndexType::Single(SingleIndex::Prefix)),
             ]
         );
     }
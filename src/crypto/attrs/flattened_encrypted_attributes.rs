@@ -1,7 +1,7 @@
 use crate::{
     crypto::{attrs::flattened_protected_attributes::FlattenedAttrName, SealError},
     encrypted_table::{TableAttributes, ZeroKmsCipher},
-    traits::TableAttribute,
+    traits::{PrimaryKeyParts, TableAttribute},
 };
 use cipherstash_client::{encryption::Plaintext, zerokms::EncryptedRecord};
 use itertools::Itertools;
@@ -28,6 +28,11 @@ impl FlattenedEncryptedAttributes {
         self.attrs.len()
     }
 
+    /// Move all the records of `other` to the end of `self`.
+    pub(crate) fn append(&mut self, mut other: Self) {
+        self.attrs.append(&mut other.attrs);
+    }
+
     // TODO: Test this
     /// Decrypt self, returning a [FlattenedProtectedAttributes].
     pub(crate) async fn decrypt_all(
@@ -90,28 +95,40 @@ impl FlattenedEncryptedAttributes {
     ///
     /// Bytes data is converted to an [EncryptedRecord] using [TableAttribute::as_encrypted_record]
     /// which validates that the descriptor matches the key and subkey.
+    /// The `primary_key` of the record identifies it if the validation fails and, if
+    /// `is_bound_to_primary_key` is set, is expected to be part of the descriptor.
     ///
     /// This method is used during decrypt and load operations.
     pub(crate) fn try_extend(
         &mut self,
         attributes: TableAttributes,
         prefix: String,
+        primary_key: &PrimaryKeyParts,
+        is_bound_to_primary_key: bool,
     ) -> Result<(), SealError> {
+        let attr_name = |name| {
+            let mut attr_key = FlattenedAttrName::new(Some(prefix.clone()), name);
+            if is_bound_to_primary_key {
+                attr_key.bind_to_primary_key(primary_key);
+            }
+            attr_key
+        };
+
         for (name, value) in attributes.into_iter() {
             match value {
                 TableAttribute::Map(map) => {
                     for (subkey, value) in map.into_iter() {
-                        let attr_key = FlattenedAttrName::new(Some(prefix.clone()), name.clone())
-                            .with_subkey(subkey);
+                        let attr_key = attr_name(name.clone()).with_subkey(subkey);
                         // Load the bytes and check for a confused deputy attack
-                        let record = value.as_encrypted_record(&attr_key.descriptor())?;
+                        let record =
+                            value.as_encrypted_record(&attr_key.descriptor(), primary_key)?;
                         self.attrs.push(record);
                     }
                 }
                 TableAttribute::Bytes(_) => {
-                    let attr_key = FlattenedAttrName::new(Some(prefix.clone()), name);
+                    let attr_key = attr_name(name);
                     // Load the bytes and check for a confused deputy attack
-                    let record = value.as_encrypted_record(&attr_key.descriptor())?;
+                    let record = value.as_encrypted_record(&attr_key.descriptor(), primary_key)?;
                     self.attrs.push(record);
                 }
                 _ => {
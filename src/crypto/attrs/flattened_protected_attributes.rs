@@ -3,8 +3,9 @@ use super::{
     normalized_protected_attributes::NormalizedKey,
 };
 use crate::{
-    crypto::SealError,
+    crypto::{b64_encode, SealError},
     encrypted_table::{AttributeName, ScopedZeroKmsCipher},
+    traits::PrimaryKeyParts,
 };
 use cipherstash_client::{
     encryption::{BytesWithDescriptor, Plaintext},
@@ -26,10 +27,22 @@ impl FlattenedProtectedAttributes {
         self.0.is_empty()
     }
 
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
     pub(crate) fn into_iter(self) -> impl Iterator<Item = FlattenedProtectedAttribute> {
         self.0.into_iter()
     }
 
+    /// Include the primary key of the record in the descriptor of every attribute.
+    /// See [FlattenedAttrName::bind_to_primary_key].
+    pub(crate) fn bind_to_primary_key(&mut self, primary_key: &PrimaryKeyParts) {
+        for attr in self.0.iter_mut() {
+            attr.key.bind_to_primary_key(primary_key);
+        }
+    }
+
     // TODO: Do some more testing with the chunking
     /// Encrypt all attributes in the set and return a list of [FlattenedEncryptedAttributes] objects.
     /// The output is a vec of `chunk_into` [FlattenedEncryptedAttributes] objects.
@@ -86,6 +99,25 @@ impl FlattenedProtectedAttribute {
         }
     }
 
+    /// The name of the attribute, not including the prefix or subkey.
+    pub(crate) fn name(&self) -> &str {
+        self.key.name.as_external_name()
+    }
+
+    /// The [AttributeName] of the attribute, as used to decide whether it is masked.
+    pub(crate) fn attribute_name(&self) -> &AttributeName {
+        &self.key.name
+    }
+
+    /// Replace the [Plaintext] of the attribute with the result of `f`.
+    pub(crate) fn try_map_plaintext<E>(
+        mut self,
+        f: impl FnOnce(Plaintext) -> Result<Plaintext, E>,
+    ) -> Result<Self, E> {
+        self.plaintext = f(self.plaintext)?;
+        Ok(self)
+    }
+
     /// Consume and return the [Plaintext], key and subkey (if one is set) of the attribute.
     pub(crate) fn normalize_into_parts(self) -> (Plaintext, NormalizedKey, Option<String>) {
         let (normalized, subkey) = self.key.normalize();
@@ -168,6 +200,23 @@ impl FlattenedAttrName {
         self
     }
 
+    /// Append the `pk` and `sk` of a record to the prefix so that the descriptor (and therefore
+    /// the ciphertext) is only valid for that record.
+    ///
+    /// Both are base64 encoded so the descriptor can still be parsed.
+    pub(super) fn bind_to_primary_key(&mut self, primary_key: &PrimaryKeyParts) {
+        let binding = format!(
+            "{}#{}",
+            b64_encode(&primary_key.pk),
+            b64_encode(&primary_key.sk)
+        );
+
+        self.prefix = Some(match self.prefix.take() {
+            Some(prefix) => format!("{prefix}#{binding}"),
+            None => binding,
+        });
+    }
+
     pub(crate) fn descriptor(&self) -> String {
         match (self.prefix.as_ref(), self.subkey.as_ref()) {
             (Some(prefix), Some(subkey)) => {
@@ -270,6 +319,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_flattened_key_bound_to_primary_key() {
+        let primary_key = PrimaryKeyParts {
+            pk: "a/b.c".to_string(),
+            sk: "user".to_string(),
+        };
+
+        let mut key = FlattenedAttrName::new(Some("pref".to_string()), "foo").with_subkey("x");
+        key.bind_to_primary_key(&primary_key);
+
+        let descriptor = key.descriptor();
+        assert_eq!(descriptor, "pref#YS9iLmM#dXNlcg/foo.x");
+        // The binding must not change how the descriptor is parsed
+        assert_eq!(FlattenedAttrName::parse(&descriptor), key);
+
+        let mut other = FlattenedAttrName::new(Some("pref".to_string()), "foo").with_subkey("x");
+        other.bind_to_primary_key(&PrimaryKeyParts {
+            pk: "a/b.c".to_string(),
+            sk: "user#2".to_string(),
+        });
+
+        assert_ne!(other.descriptor(), descriptor);
+    }
+
     // TODO: Test normalize the FlattenedAttrName
 
     #[test]
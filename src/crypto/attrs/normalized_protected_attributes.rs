@@ -27,6 +27,13 @@ impl NormalizedProtectedAttributes {
         }
     }
 
+    /// Set the prefix of the descriptors unless one has already been set.
+    pub(crate) fn set_default_prefix(&mut self, prefix: impl Into<String>) {
+        if self.prefix.is_none() {
+            self.prefix = Some(prefix.into());
+        }
+    }
+
     pub fn insert(&mut self, key: impl Into<String>, value: Plaintext) {
         self.values.insert(
             NormalizedKey::Scalar(key.into()),
@@ -73,6 +80,13 @@ impl NormalizedProtectedAttributes {
             .and_then(|v| v.into_map())
     }
 
+    /// Consume and return the names and values of the attributes.
+    pub(crate) fn into_values(self) -> impl Iterator<Item = (String, NormalizedValue)> {
+        self.values
+            .into_iter()
+            .map(|(key, value)| (String::from(key), value))
+    }
+
     pub(crate) fn flatten(self) -> FlattenedProtectedAttributes {
         let inner: Vec<FlattenedProtectedAttribute> = self
             .values
@@ -4,3 +4,8 @@ use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 pub fn b64_encode(x: impl AsRef<[u8]>) -> String {
     URL_SAFE_NO_PAD.encode(x)
 }
+
+/// Decode a buffer encoded with [b64_encode]. Returns `None` if it isn't valid base64.
+pub(crate) fn b64_decode(x: impl AsRef<[u8]>) -> Option<Vec<u8>> {
+    URL_SAFE_NO_PAD.decode(x).ok()
+}
@@ -0,0 +1,163 @@
+use super::SealError;
+use crate::encrypted_table::AccessContext;
+use cipherstash_client::encryption::Plaintext;
+
+/// How a protected attribute is returned when a record is decrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeAccess {
+    /// Decrypt the attribute and return its value.
+    Decrypt,
+
+    /// Decrypt the attribute and return the value from [`DecryptionPolicy::mask`] instead.
+    Mask,
+
+    /// Don't decrypt the attribute. It is left out of the [`Unsealed`](super::Unsealed) record.
+    Omit,
+}
+
+/// Decides which protected attributes of a record can be decrypted, for example based on the
+/// [`AccessContext`] of the caller.
+///
+/// The policy of a table is set with
+/// [`EncryptedTable::with_decryption_policy`](crate::EncryptedTable::with_decryption_policy) and
+/// is evaluated for every protected attribute before anything is sent to ZeroKMS, so omitted
+/// attributes are never decrypted.
+///
+/// Omitted attributes are missing from the decrypted record, so records with omitted attributes
+/// can only be decrypted into types where those fields are optional.
+///
+/// ```
+/// use cipherstash_dynamodb::{AccessContext, crypto::{AttributeAccess, DecryptionPolicy}};
+///
+/// struct SupportPolicy;
+///
+/// impl DecryptionPolicy for SupportPolicy {
+///     fn access(
+///         &self,
+///         _type_name: &str,
+///         attribute: &str,
+///         context: Option<&AccessContext>,
+///     ) -> AttributeAccess {
+///         match (attribute, context) {
+///             (_, Some(context)) if context.purpose == "fraud investigation" => AttributeAccess::Decrypt,
+///             ("email", _) => AttributeAccess::Mask,
+///             ("name", _) => AttributeAccess::Decrypt,
+///             _ => AttributeAccess::Omit,
+///         }
+///     }
+/// }
+/// ```
+pub trait DecryptionPolicy: Send + Sync {
+    /// Decide how the protected `attribute` of a record of type `type_name` is returned when it
+    /// is decrypted with `context`.
+    ///
+    /// Attributes of nested types and collections are decided by the name of the field which
+    /// stores them.
+    fn access(
+        &self,
+        type_name: &str,
+        attribute: &str,
+        context: Option<&AccessContext>,
+    ) -> AttributeAccess;
+
+    /// Mask the decrypted `plaintext` of an attribute for which [`DecryptionPolicy::access`]
+    /// returned [`AttributeAccess::Mask`].
+    ///
+    /// The masked value must have the same type as `plaintext` so that it can still be
+    /// converted into the field of the record. Maps and lists are masked one value at a time.
+    /// See [`mask_plaintext`] for the default, which only masks strings.
+    fn mask(
+        &self,
+        _type_name: &str,
+        attribute: &str,
+        plaintext: Plaintext,
+    ) -> Result<Plaintext, SealError> {
+        mask_plaintext(attribute, plaintext)
+    }
+}
+
+/// The default mask used by [`DecryptionPolicy::mask`].
+///
+/// Strings are replaced with `****`, keeping the domain of email addresses (e.g.
+/// `****@coderdan.co`) and null values are left as they are. There is no value of other types
+/// which can't be mistaken for real data, so masking them returns [`SealError::CannotMask`].
+/// Override [`DecryptionPolicy::mask`] to mask them with a value of your choice.
+pub fn mask_plaintext(attribute: &str, plaintext: Plaintext) -> Result<Plaintext, SealError> {
+    match &plaintext {
+        Plaintext::Utf8Str(Some(value)) => Ok(Plaintext::Utf8Str(Some(mask_string(value)))),
+        Plaintext::Utf8Str(None)
+        | Plaintext::BigInt(None)
+        | Plaintext::BigUInt(None)
+        | Plaintext::Boolean(None)
+        | Plaintext::Decimal(None)
+        | Plaintext::Float(None)
+        | Plaintext::Int(None)
+        | Plaintext::NaiveDate(None)
+        | Plaintext::SmallInt(None)
+        | Plaintext::Timestamp(None)
+        | Plaintext::JsonB(None) => Ok(plaintext),
+        _ => Err(SealError::CannotMask {
+            attribute: attribute.to_string(),
+            value_type: plaintext_type_name(&plaintext),
+        }),
+    }
+}
+
+fn plaintext_type_name(plaintext: &Plaintext) -> &'static str {
+    match plaintext {
+        Plaintext::BigInt(_) => "i64",
+        Plaintext::BigUInt(_) => "u64",
+        Plaintext::Boolean(_) => "bool",
+        Plaintext::Decimal(_) => "Decimal",
+        Plaintext::Float(_) => "f64",
+        Plaintext::Int(_) => "i32",
+        Plaintext::NaiveDate(_) => "NaiveDate",
+        Plaintext::SmallInt(_) => "i16",
+        Plaintext::Timestamp(_) => "DateTime<Utc>",
+        Plaintext::Utf8Str(_) => "String",
+        Plaintext::JsonB(_) => "JSON",
+    }
+}
+
+fn mask_string(value: &str) -> String {
+    match value.rsplit_once('@') {
+        Some((_, domain)) => format!("****@{domain}"),
+        None => "****".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_plaintext() {
+        assert_eq!(
+            mask_plaintext("email", Plaintext::from("dan@coderdan.co")).unwrap(),
+            Plaintext::from("****@coderdan.co")
+        );
+        assert_eq!(
+            mask_plaintext("name", Plaintext::from("Dan Draper")).unwrap(),
+            Plaintext::from("****")
+        );
+        assert_eq!(
+            mask_plaintext("age", Plaintext::BigInt(None)).unwrap(),
+            Plaintext::BigInt(None)
+        );
+    }
+
+    #[test]
+    fn test_mask_plaintext_rejects_other_types() {
+        assert!(matches!(
+            mask_plaintext("age", Plaintext::from(42_i64)),
+            Err(SealError::CannotMask { attribute, value_type: "i64" }) if attribute == "age"
+        ));
+        assert!(matches!(
+            mask_plaintext("verified", Plaintext::from(true)),
+            Err(SealError::CannotMask {
+                value_type: "bool",
+                ..
+            })
+        ));
+    }
+}
@@ -1,10 +1,14 @@
 mod attrs;
 mod b64_encode;
+mod decryption_policy;
+mod record_mac;
 mod sealed;
 mod sealer;
 mod unsealed;
 use crate::{
-    traits::{PrimaryKeyError, PrimaryKeyParts, ReadConversionError, WriteConversionError},
+    traits::{
+        PrimaryKeyError, PrimaryKeyParts, ProtectedIndex, ReadConversionError, WriteConversionError,
+    },
     Identifiable, IndexType, PrimaryKey,
 };
 use cipherstash_client::{
@@ -12,20 +16,15 @@ use cipherstash_client::{
     zerokms,
 };
 use miette::Diagnostic;
-use std::borrow::Cow;
 use thiserror::Error;
 
 // Re-exports
 pub use b64_encode::*;
+pub use decryption_policy::{mask_plaintext, AttributeAccess, DecryptionPolicy};
 pub use sealed::{SealedTableEntry, UnsealSpec};
-pub use sealer::{Sealer, UnsealedIndex};
+pub use sealer::{Sealer, TruncatedIndex, UnsealedIndex};
 pub use unsealed::Unsealed;
 
-/// In order to stop indexes from exploding with indexes on large strings, cap the number of terms
-/// generated per index. Since there is a fixed number of terms per index it is also possible to
-/// delete all index terms for a particular record.
-const MAX_TERMS_PER_INDEX: usize = 25;
-
 #[derive(Debug, Error, Diagnostic)]
 pub enum SealError {
     #[error("Error when creating primary key: {0}")]
@@ -42,6 +41,31 @@ pub enum SealError {
     InvalidCiphertext(String),
     #[error("Assertion failed: {0}")]
     AssertionFailed(String),
+    #[error("Record with pk '{pk}' and sk '{sk}' has been tampered with: expected descriptor '{expected}' but got '{actual}'")]
+    TamperDetected {
+        pk: String,
+        sk: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("Integrity check failed for record with pk '{pk}' and sk '{sk}': the record MAC is missing or invalid")]
+    IntegrityCheckFailed { pk: String, sk: String },
+    #[error("Failed to serialize or deserialize attribute '{name}': {message}")]
+    SerdeError { name: String, message: String },
+    #[error("Unknown variant '{variant}' for type '{type_name}'")]
+    UnknownVariant { type_name: String, variant: String },
+    #[error("Attribute '{attribute}' of type {value_type} can't be masked: only strings are masked by default, override DecryptionPolicy::mask to mask other types")]
+    CannotMask {
+        attribute: String,
+        value_type: &'static str,
+    },
+    #[error("Index '{index_name}' ({index_type}) generated {total_terms} terms but at most {max_terms} can be stored")]
+    TooManyTerms {
+        index_name: String,
+        index_type: IndexType,
+        max_terms: usize,
+        total_terms: usize,
+    },
 
     #[error(transparent)]
     CryptoError(#[from] zerokms::Error),
@@ -71,20 +95,34 @@ pub fn format_term_key(
     format!("{sort_key}#{index_name}#{index_type}#{counter}")
 }
 
+/// Append a shard number to an index term.
+///
+/// Used when an index is sharded (see [`crate::traits::Searchable::term_shards`]) so that the
+/// same term is stored under different `TermIndex` partitions.
+pub fn sharded_term(term: &[u8], shard: u8) -> Vec<u8> {
+    let mut sharded = Vec::with_capacity(term.len() + 1);
+    sharded.extend_from_slice(term);
+    sharded.push(shard);
+    sharded
+}
+
 /// Get all the term index keys for a particular sort key and index definitions
 ///
 /// This is used to delete any index items that shouldn't exist during either an update or
-pub(crate) fn all_index_keys<'a>(
+///
+/// Since the number of terms for each index is capped by [`ProtectedIndex::max_terms`] it is
+/// possible to know every key that could have been used for a record.
+pub(crate) fn all_index_keys(
     sort_key: &str,
-    protected_indexes: impl AsRef<[(Cow<'a, str>, IndexType)]>,
+    protected_indexes: impl AsRef<[ProtectedIndex]>,
 ) -> Vec<String> {
     protected_indexes
         .as_ref()
         .iter()
-        .flat_map(|(index_name, index_type)| {
+        .flat_map(|index| {
             (0..)
-                .take(MAX_TERMS_PER_INDEX)
-                .map(|i| format_term_key(sort_key, index_name, *index_type, i))
+                .take(index.max_terms)
+                .map(|i| format_term_key(sort_key, &index.name, index.index_type, i))
                 .collect::<Vec<String>>()
         })
         .collect()
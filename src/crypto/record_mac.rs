@@ -0,0 +1,270 @@
+use super::SealError;
+use crate::{
+    encrypted_table::{ScopedZeroKmsCipher, TableAttribute, TableAttributes},
+    traits::PrimaryKeyParts,
+};
+use itertools::Itertools;
+
+/// The name of the attribute used to store the record MAC.
+pub(crate) const RECORD_MAC_ATTRIBUTE: &str = "__mac";
+
+/// Separates record MACs from the other values MAC'd with the same key (e.g. encrypted primary keys).
+const RECORD_MAC_CONTEXT: &str = "cipherstash-dynamodb/record-mac/v1";
+
+/// Compute the MAC of a record covering its stored `pk` and `sk` and all of its `unprotected`
+/// attributes.
+pub(crate) fn record_mac(
+    cipher: &ScopedZeroKmsCipher,
+    primary_key: &PrimaryKeyParts,
+    unprotected: &TableAttributes,
+) -> Vec<u8> {
+    let message = canonicalize(primary_key, unprotected);
+
+    cipher
+        .mac::<32>(&hex::encode(message), Some(RECORD_MAC_CONTEXT))
+        .to_vec()
+}
+
+/// Remove the record MAC from `unprotected` and check that it matches the MAC of the remaining
+/// attributes.
+///
+/// Returns [SealError::IntegrityCheckFailed] if the MAC is missing or invalid.
+pub(crate) fn verify_record_mac(
+    cipher: &ScopedZeroKmsCipher,
+    primary_key: &PrimaryKeyParts,
+    unprotected: &mut TableAttributes,
+) -> Result<(), SealError> {
+    let stored = unprotected.remove(RECORD_MAC_ATTRIBUTE);
+    let expected = record_mac(cipher, primary_key, unprotected);
+
+    match stored {
+        Some(TableAttribute::Bytes(stored)) if constant_time_eq(&stored, &expected) => Ok(()),
+        _ => Err(SealError::IntegrityCheckFailed {
+            pk: primary_key.pk.clone(),
+            sk: primary_key.sk.clone(),
+        }),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Encode the primary key and attributes of a record unambiguously.
+///
+/// Attributes are sorted by their stored name, map entries by key and sets by value so that the
+/// encoding doesn't depend on the order DynamoDB returns them in.
+fn canonicalize(primary_key: &PrimaryKeyParts, attributes: &TableAttributes) -> Vec<u8> {
+    let mut buf = Vec::new();
+
+    write_bytes(&mut buf, primary_key.pk.as_bytes());
+    write_bytes(&mut buf, primary_key.sk.as_bytes());
+
+    let attributes = attributes
+        .iter()
+        .map(|(name, value)| (name.as_stored_name(), value))
+        .sorted_by_key(|(name, _)| *name)
+        .collect_vec();
+
+    write_len(&mut buf, attributes.len());
+
+    for (name, value) in attributes {
+        write_bytes(&mut buf, name.as_bytes());
+        write_attribute(&mut buf, value);
+    }
+
+    buf
+}
+
+fn write_attribute(buf: &mut Vec<u8>, attribute: &TableAttribute) {
+    match attribute {
+        TableAttribute::String(s) => {
+            buf.push(b'S');
+            write_bytes(buf, s.as_bytes());
+        }
+        TableAttribute::Number(n) => {
+            buf.push(b'N');
+            write_bytes(buf, normalize_number(n).as_bytes());
+        }
+        TableAttribute::Bool(b) => {
+            buf.push(b'T');
+            buf.push(*b as u8);
+        }
+        TableAttribute::Bytes(b) => {
+            buf.push(b'B');
+            write_bytes(buf, b);
+        }
+        TableAttribute::StringVec(values) => {
+            buf.push(b's');
+            write_set(buf, values.iter().map(|s| s.as_bytes().to_vec()));
+        }
+        TableAttribute::NumberVec(values) => {
+            buf.push(b'n');
+            write_set(buf, values.iter().map(|n| normalize_number(n).into_bytes()));
+        }
+        TableAttribute::ByteVec(values) => {
+            buf.push(b'b');
+            write_set(buf, values.iter().cloned());
+        }
+        TableAttribute::Map(map) => {
+            buf.push(b'M');
+            write_len(buf, map.len());
+
+            for (key, value) in map.iter().sorted_by_key(|(key, _)| *key) {
+                write_bytes(buf, key.as_bytes());
+                write_attribute(buf, value);
+            }
+        }
+        TableAttribute::List(values) => {
+            buf.push(b'L');
+            write_len(buf, values.len());
+
+            for value in values {
+                write_attribute(buf, value);
+            }
+        }
+        TableAttribute::Null => buf.push(b'0'),
+    }
+}
+
+fn write_set(buf: &mut Vec<u8>, values: impl Iterator<Item = Vec<u8>>) {
+    let values = values.sorted().collect_vec();
+    write_len(buf, values.len());
+
+    for value in values {
+        write_bytes(buf, &value);
+    }
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_len(buf, bytes.len());
+    buf.extend_from_slice(bytes);
+}
+
+fn write_len(buf: &mut Vec<u8>, len: usize) {
+    buf.extend_from_slice(&(len as u64).to_be_bytes());
+}
+
+/// DynamoDB doesn't preserve the formatting of numbers (e.g. `1.50` is returned as `1.5`) so
+/// trailing zeros of the fractional part are removed before they are MAC'd.
+fn normalize_number(number: &str) -> String {
+    let number = number.trim_start_matches('+');
+
+    if number.contains('.') && !number.contains(['e', 'E']) {
+        number
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    } else {
+        number.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn primary_key() -> PrimaryKeyParts {
+        PrimaryKeyParts {
+            pk: "pk".to_string(),
+            sk: "sk".to_string(),
+        }
+    }
+
+    fn attributes(
+        values: impl IntoIterator<Item = (&'static str, TableAttribute)>,
+    ) -> TableAttributes {
+        let mut attributes = TableAttributes::new();
+
+        for (name, value) in values {
+            attributes.insert(name, value);
+        }
+
+        attributes
+    }
+
+    #[test]
+    fn test_canonicalize_is_order_independent() {
+        let a = attributes([
+            ("status", TableAttribute::String("active".to_string())),
+            (
+                "tags",
+                TableAttribute::StringVec(vec!["a".to_string(), "b".to_string()]),
+            ),
+            (
+                "meta",
+                TableAttribute::Map(HashMap::from([
+                    ("x".to_string(), TableAttribute::Number("1.50".to_string())),
+                    ("y".to_string(), TableAttribute::Bool(true)),
+                ])),
+            ),
+        ]);
+
+        let b = attributes([
+            (
+                "meta",
+                TableAttribute::Map(HashMap::from([
+                    ("y".to_string(), TableAttribute::Bool(true)),
+                    ("x".to_string(), TableAttribute::Number("1.5".to_string())),
+                ])),
+            ),
+            (
+                "tags",
+                TableAttribute::StringVec(vec!["b".to_string(), "a".to_string()]),
+            ),
+            ("status", TableAttribute::String("active".to_string())),
+        ]);
+
+        assert_eq!(
+            canonicalize(&primary_key(), &a),
+            canonicalize(&primary_key(), &b)
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_detects_changes() {
+        let original = canonicalize(
+            &primary_key(),
+            &attributes([("status", TableAttribute::String("active".to_string()))]),
+        );
+
+        let changed_value = canonicalize(
+            &primary_key(),
+            &attributes([("status", TableAttribute::String("admin".to_string()))]),
+        );
+
+        let changed_type = canonicalize(
+            &primary_key(),
+            &attributes([("status", TableAttribute::Bytes(b"active".to_vec()))]),
+        );
+
+        let changed_key = canonicalize(
+            &PrimaryKeyParts {
+                pk: "pk".to_string(),
+                sk: "sk2".to_string(),
+            },
+            &attributes([("status", TableAttribute::String("active".to_string()))]),
+        );
+
+        // Moving bytes between the name and value must not produce the same encoding
+        let shifted = canonicalize(
+            &primary_key(),
+            &attributes([("statu", TableAttribute::String("sactive".to_string()))]),
+        );
+
+        for other in [changed_value, changed_type, changed_key, shifted] {
+            assert_ne!(original, other);
+        }
+    }
+
+    #[test]
+    fn test_normalize_number() {
+        assert_eq!(normalize_number("42"), "42");
+        assert_eq!(normalize_number("+42"), "42");
+        assert_eq!(normalize_number("1.50"), "1.5");
+        assert_eq!(normalize_number("1.0"), "1");
+        assert_eq!(normalize_number("100"), "100");
+        assert_eq!(normalize_number("1.0E10"), "1.0E10");
+    }
+}
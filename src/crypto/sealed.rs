@@ -1,14 +1,23 @@
 use crate::{
     crypto::attrs::FlattenedEncryptedAttributes,
-    encrypted_table::{TableEntry, ZeroKmsCipher},
-    traits::{ReadConversionError, WriteConversionError},
+    encrypted_table::{
+        AccessContext, AttributeName, ScopedZeroKmsCipher, TableAttribute, TableAttributes,
+        TableEntry, ZeroKmsCipher, DELETED_AT_ATTRIBUTE,
+    },
+    traits::{PrimaryKeyParts, ReadConversionError, WriteConversionError},
     Decryptable, Identifiable,
 };
 use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
 use itertools::Itertools;
-use std::{borrow::Cow, collections::HashMap};
-
-use super::{attrs::NormalizedProtectedAttributes, SealError, Unsealed};
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+use tracing::{instrument, Span};
+
+use super::{
+    attrs::NormalizedProtectedAttributes,
+    record_mac::{verify_record_mac, RECORD_MAC_ATTRIBUTE},
+    sealer::ROOT_SK_ATTRIBUTE,
+    AttributeAccess, DecryptionPolicy, SealError, Unsealed,
+};
 
 // FIXME: Move this to a separate file
 /// Wrapped to indicate that the value is encrypted
@@ -17,12 +26,62 @@ pub struct SealedTableEntry(pub(super) TableEntry);
 pub struct UnsealSpec<'a> {
     pub(crate) protected_attributes: Cow<'a, [Cow<'a, str>]>,
 
+    /// The name of the type being unsealed, passed to the [DecryptionPolicy].
+    pub(crate) type_name: String,
+
     /// The prefix used for sort keys.
     /// If None, the type name will be used.
     /// This *must* be the same as the value used when encrypting the data
     /// so that descriptors can be correctly matched.
     /// See [TableAttribute::as_encrypted_record]
     pub(crate) sort_key_prefix: String,
+
+    /// Whether the primary key of each record is expected to be part of the descriptors.
+    /// See [Identifiable::is_bound_to_primary_key]
+    pub(crate) is_bound_to_primary_key: bool,
+
+    /// Whether each record is expected to have a valid record MAC.
+    /// See [Identifiable::has_record_mac]
+    pub(crate) has_record_mac: bool,
+
+    /// Decides which protected attributes are decrypted, masked or omitted.
+    pub(crate) decryption_policy: Option<Arc<dyn DecryptionPolicy>>,
+
+    /// Who is decrypting the records and why, passed to the [DecryptionPolicy].
+    pub(crate) context: Option<AccessContext>,
+
+    /// Whether the records may be index term entries, e.g. when they were returned by a query
+    /// of the TermIndex.
+    pub(crate) allow_term_entries: bool,
+}
+
+impl UnsealSpec<'_> {
+    /// Returns true if a [ScopedZeroKmsCipher] is needed to verify the records.
+    pub(crate) fn requires_scoped_cipher(&self) -> bool {
+        self.has_record_mac
+    }
+
+    /// Apply `decryption_policy` to the protected attributes unless a policy is already set.
+    pub(crate) fn with_decryption_policy(
+        mut self,
+        decryption_policy: Option<Arc<dyn DecryptionPolicy>>,
+    ) -> Self {
+        if self.decryption_policy.is_none() {
+            self.decryption_policy = decryption_policy;
+        }
+        self
+    }
+
+    pub(crate) fn with_context(mut self, context: Option<AccessContext>) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Accept index term entries and verify them against the primary key of their root entry.
+    pub(crate) fn allowing_term_entries(mut self) -> Self {
+        self.allow_term_entries = true;
+        self
+    }
 }
 
 impl UnsealSpec<'static> {
@@ -32,10 +91,16 @@ impl UnsealSpec<'static> {
     {
         Self {
             protected_attributes: D::protected_attributes(),
+            type_name: D::type_name().to_string(),
             sort_key_prefix: D::sort_key_prefix()
                 .as_deref()
                 .map(ToOwned::to_owned)
                 .unwrap_or(D::type_name().to_string()),
+            is_bound_to_primary_key: D::is_bound_to_primary_key(),
+            has_record_mac: D::has_record_mac(),
+            decryption_policy: None,
+            context: None,
+            allow_term_entries: false,
         }
     }
 }
@@ -65,63 +130,174 @@ impl SealedTableEntry {
     /// decryptions
     ///
     /// This should be used over [`Sealed::unseal`] when multiple values need to be unsealed.
+    ///
+    /// The `scoped_cipher` is used to verify record MACs and must be set if
+    /// [`UnsealSpec::requires_scoped_cipher`] is true.
     pub(crate) async fn unseal_all(
         items: Vec<Self>,
         spec: UnsealSpec<'_>,
         cipher: &ZeroKmsCipher,
+        scoped_cipher: Option<&ScopedZeroKmsCipher>,
     ) -> Result<Vec<Unsealed>, SealError> {
-        let UnsealSpec {
-            protected_attributes,
-            sort_key_prefix,
-        } = spec;
-
-        let items_len = items.len();
-        if items_len == 0 {
-            return Ok(Vec::new());
+        Self::unseal_all_with_count(items, spec, cipher, scoped_cipher)
+            .await
+            .map(|(unsealed, _)| unsealed)
+    }
+
+    /// Unseal a list of [`Sealed`] values like [`Sealed::unseal_all`] and also return the number
+    /// of attributes that were decrypted.
+    #[instrument(
+        name = "unseal_all",
+        skip_all,
+        fields(
+            sort_key_prefix = %spec.sort_key_prefix,
+            records = items.len(),
+            decrypted_attributes,
+        )
+    )]
+    pub(crate) async fn unseal_all_with_count(
+        items: Vec<Self>,
+        spec: UnsealSpec<'_>,
+        cipher: &ZeroKmsCipher,
+        scoped_cipher: Option<&ScopedZeroKmsCipher>,
+    ) -> Result<(Vec<Unsealed>, usize), SealError> {
+        // Validate every record before anything is sent to ZeroKMS
+        let entries = items
+            .into_iter()
+            .map(|item| item.prepare(&spec, scoped_cipher).map(Ok))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (unsealed, decrypted_attributes) = decrypt_prepared(entries, &spec, cipher).await?;
+
+        unsealed
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .map(|unsealed| (unsealed, decrypted_attributes))
+    }
+
+    /// Unseal a list of [`Sealed`] values like [`Sealed::unseal_all_with_count`] but return a
+    /// result for each record (in the same order as `items`) instead of failing on the first
+    /// invalid record.
+    ///
+    /// Records which could not be read or fail validation (e.g. with [`SealError::TamperDetected`])
+    /// are excluded from decryption. An error is only returned for the whole list if the
+    /// decryption itself fails.
+    #[instrument(
+        name = "unseal_each",
+        skip_all,
+        fields(
+            sort_key_prefix = %spec.sort_key_prefix,
+            records = items.len(),
+            decrypted_attributes,
+        )
+    )]
+    pub(crate) async fn unseal_each_with_count(
+        items: Vec<Result<Self, SealError>>,
+        spec: UnsealSpec<'_>,
+        cipher: &ZeroKmsCipher,
+        scoped_cipher: Option<&ScopedZeroKmsCipher>,
+    ) -> Result<(Vec<Result<Unsealed, SealError>>, usize), SealError> {
+        let entries = items
+            .into_iter()
+            .map(|item| item.and_then(|item| item.prepare(&spec, scoped_cipher)))
+            .collect();
+
+        decrypt_prepared(entries, &spec, cipher).await
+    }
+
+    /// Split the attributes of the record into protected and unprotected attributes and validate
+    /// that the descriptors of the protected attributes (and the record MAC if required) match
+    /// the `spec`.
+    ///
+    /// Protected attributes omitted by the [DecryptionPolicy] of the `spec` are dropped so they
+    /// aren't sent to ZeroKMS.
+    fn prepare(
+        self,
+        spec: &UnsealSpec<'_>,
+        scoped_cipher: Option<&ScopedZeroKmsCipher>,
+    ) -> Result<PreparedEntry, SealError> {
+        let TableEntry {
+            pk,
+            sk,
+            term,
+            attributes,
+        } = self.into_inner();
+        // A term entry is a copy of its record stored under a different sort key so it must not
+        // be read as the record stored under its own primary key (e.g. by `get`)
+        if term.is_some() && !spec.allow_term_entries {
+            return Err(SealError::TamperDetected {
+                pk,
+                sk,
+                expected: "record".to_string(),
+                actual: "index term".to_string(),
+            });
         }
 
-        let mut protected_items = {
-            let capacity = items.len() * protected_attributes.len();
-            FlattenedEncryptedAttributes::with_capacity(capacity)
+        let (mut protected, mut unprotected) =
+            attributes.partition(spec.protected_attributes.as_ref());
+
+        // Term entries are verified against the primary key of their root entry. The root sk is
+        // only trusted where term entries are expected, otherwise a copy of another record could
+        // be read in place of the record stored under this key. Term entries written before the
+        // root sk was stored fall back to their own sk.
+        let sk = match unprotected.remove(ROOT_SK_ATTRIBUTE) {
+            Some(TableAttribute::String(root_sk)) if term.is_some() && spec.allow_term_entries => {
+                root_sk
+            }
+            _ => sk,
         };
-        let mut unprotected_items = Vec::with_capacity(items.len());
+        let primary_key = PrimaryKeyParts { pk, sk };
 
-        for item in items.into_iter() {
-            let (protected, unprotected) = item
-                .into_inner()
-                .attributes
-                .partition(protected_attributes.as_ref());
+        // Records are marked as soft deleted after they're sealed so the marker isn't part of the
+        // MAC. The MAC doesn't protect the deletion state of a record.
+        unprotected.remove(DELETED_AT_ATTRIBUTE);
 
-            protected_items.try_extend(protected, sort_key_prefix.clone())?;
-            unprotected_items.push(unprotected);
-        }
+        if spec.has_record_mac {
+            let scoped_cipher = scoped_cipher.ok_or_else(|| {
+                SealError::AssertionFailed(
+                    "A scoped cipher is required to verify record MACs".to_string(),
+                )
+            })?;
 
-        if protected_items.is_empty() {
-            unprotected_items
-                .into_iter()
-                .map(|unprotected| Ok(Unsealed::new_from_unprotected(unprotected)))
-                .collect()
+            verify_record_mac(scoped_cipher, &primary_key, &mut unprotected)?;
         } else {
-            let chunk_size =
-                protected_items
-                    .len()
-                    .checked_div(items_len)
-                    .ok_or(SealError::AssertionFailed(
-                        "Division by zero when calculating chunk size".to_string(),
-                    ))?;
-
-            protected_items
-                .decrypt_all(cipher)
-                .await?
-                .into_iter()
-                // TODO: Can we make decrypt_all return a Vec of FlattenedProtectedAttributes? (like the mirror of encrypt_all)
-                .chunks(chunk_size)
-                .into_iter()
-                .map(|fpa| fpa.into_iter().collect::<NormalizedProtectedAttributes>())
-                .zip_eq(unprotected_items.into_iter())
-                .map(|(fpa, unprotected)| Ok(Unsealed::new_from_parts(fpa, unprotected)))
-                .collect()
+            unprotected.remove(RECORD_MAC_ATTRIBUTE);
+        }
+
+        let mut masked = Vec::new();
+
+        if let Some(policy) = &spec.decryption_policy {
+            let names = protected.iter().map(|(name, _)| name.clone()).collect_vec();
+
+            for name in names {
+                match policy.access(
+                    &spec.type_name,
+                    name.as_external_name(),
+                    spec.context.as_ref(),
+                ) {
+                    AttributeAccess::Decrypt => {}
+                    AttributeAccess::Mask => masked.push(name),
+                    AttributeAccess::Omit => {
+                        protected.remove(name);
+                    }
+                }
+            }
         }
+
+        let mut encrypted =
+            FlattenedEncryptedAttributes::with_capacity(spec.protected_attributes.len());
+        encrypted.try_extend(
+            protected,
+            spec.sort_key_prefix.clone(),
+            &primary_key,
+            spec.is_bound_to_primary_key,
+        )?;
+
+        Ok(PreparedEntry {
+            protected: encrypted,
+            masked,
+            unprotected,
+        })
     }
 
     /// Unseal the current value and return it's plaintext representation
@@ -131,8 +307,9 @@ impl SealedTableEntry {
         self,
         spec: UnsealSpec<'_>,
         cipher: &ZeroKmsCipher,
+        scoped_cipher: Option<&ScopedZeroKmsCipher>,
     ) -> Result<Unsealed, SealError> {
-        let mut vec = Self::unseal_all(vec![self], spec, cipher).await?;
+        let mut vec = Self::unseal_all(vec![self], spec, cipher, scoped_cipher).await?;
 
         if vec.len() != 1 {
             let actual = vec.len();
@@ -146,6 +323,102 @@ impl SealedTableEntry {
     }
 }
 
+/// A record which has been validated and is ready to be decrypted.
+struct PreparedEntry {
+    protected: FlattenedEncryptedAttributes,
+
+    /// The protected attributes to mask once they're decrypted.
+    masked: Vec<AttributeName>,
+
+    unprotected: TableAttributes,
+}
+
+/// Decrypt the protected attributes of all valid `entries` with a single call to ZeroKMS.
+/// Entries which failed validation are returned as is.
+///
+/// Also returns the number of attributes that were decrypted.
+async fn decrypt_prepared(
+    entries: Vec<Result<PreparedEntry, SealError>>,
+    spec: &UnsealSpec<'_>,
+    cipher: &ZeroKmsCipher,
+) -> Result<(Vec<Result<Unsealed, SealError>>, usize), SealError> {
+    let mut protected_items = FlattenedEncryptedAttributes::with_capacity(entries.len());
+
+    // Keep track of the number of protected attributes of each entry so the decrypted
+    // attributes can be assigned back to the right entry
+    let entries = entries
+        .into_iter()
+        .map(|entry| {
+            entry.map(
+                |PreparedEntry {
+                     protected,
+                     masked,
+                     unprotected,
+                 }| {
+                    let num_protected = protected.len();
+                    protected_items.append(protected);
+                    (num_protected, masked, unprotected)
+                },
+            )
+        })
+        .collect_vec();
+
+    let num_protected_items = protected_items.len();
+    Span::current().record("decrypted_attributes", num_protected_items);
+
+    let mut decrypted = if protected_items.is_empty() {
+        Vec::new().into_iter()
+    } else {
+        let decrypted = protected_items
+            .decrypt_all(cipher)
+            .await?
+            .into_iter()
+            .collect_vec();
+
+        if decrypted.len() != num_protected_items {
+            return Err(SealError::AssertionFailed(format!(
+                "Expected {num_protected_items} decrypted attributes but got {}",
+                decrypted.len()
+            )));
+        }
+
+        decrypted.into_iter()
+    };
+
+    let unsealed = entries
+        .into_iter()
+        .map(|entry| {
+            entry.and_then(|(num_protected, masked, unprotected)| {
+                if num_protected == 0 {
+                    Ok(Unsealed::new_from_unprotected(unprotected))
+                } else {
+                    // Take every attribute of the entry before masking so that a failed mask
+                    // doesn't shift the attributes of the following entries
+                    let protected = decrypted
+                        .by_ref()
+                        .take(num_protected)
+                        .collect_vec()
+                        .into_iter()
+                        .map(|attr| match &spec.decryption_policy {
+                            Some(policy) if masked.contains(attr.attribute_name()) => {
+                                let name = attr.name().to_string();
+                                attr.try_map_plaintext(|plaintext| {
+                                    policy.mask(&spec.type_name, &name, plaintext)
+                                })
+                            }
+                            _ => Ok(attr),
+                        })
+                        .collect::<Result<NormalizedProtectedAttributes, _>>()?;
+
+                    Ok(Unsealed::new_from_parts(protected, unprotected))
+                }
+            })
+        })
+        .collect();
+
+    Ok((unsealed, num_protected_items))
+}
+
 impl TryFrom<HashMap<String, AttributeValue>> for SealedTableEntry {
     type Error = ReadConversionError;
 
@@ -166,6 +439,12 @@ impl TryFrom<HashMap<String, AttributeValue>> for SealedTableEntry {
 
         let mut table_entry = TableEntry::new(pk, sk);
 
+        // Only kept to tell term entries apart from root entries
+        table_entry.term = item
+            .get("term")
+            .and_then(|term| term.as_b().ok())
+            .map(|term| term.as_ref().to_vec());
+
         // This prevents loading special columns when retrieving records
         // pk/sk are handled specially or will be called __sk and __pk
         // We never want to read term during queries
@@ -203,9 +482,12 @@ impl TryFrom<SealedTableEntry> for HashMap<String, AttributeValue> {
 
 #[cfg(test)]
 mod tests {
-    use crate::encrypted_table::ZeroKmsCipher;
+    use crate::{
+        crypto::SealError,
+        encrypted_table::{TableAttribute, TableAttributes, TableEntry, ZeroKmsCipher},
+    };
 
-    use super::SealedTableEntry;
+    use super::{SealedTableEntry, UnsealSpec};
     use cipherstash_client::{
         credentials::auto_refresh::AutoRefresh, ConsoleConfig, ZeroKMS, ZeroKMSConfig,
     };
@@ -237,12 +519,18 @@ mod tests {
 
     #[tokio::test]
     async fn test_unseal_all_empty() -> Result<(), Box<dyn std::error::Error>> {
-        let spec = super::UnsealSpec {
+        let spec = UnsealSpec {
             protected_attributes: Cow::Borrowed(&[]),
+            type_name: "test".to_string(),
             sort_key_prefix: "test".to_string(),
+            is_bound_to_primary_key: false,
+            has_record_mac: false,
+            decryption_policy: None,
+            context: None,
+            allow_term_entries: false,
         };
         let cipher = get_cipher().await?;
-        let results = SealedTableEntry::unseal_all(vec![], spec, &cipher)
+        let results = SealedTableEntry::unseal_all(vec![], spec, &cipher, None)
             .await
             .into_diagnostic()?;
 
@@ -250,4 +538,48 @@ mod tests {
 
         Ok(())
     }
+
+    fn term_entry() -> SealedTableEntry {
+        let mut attributes = TableAttributes::new();
+        attributes.insert("__root_sk", TableAttribute::String("user#root".to_string()));
+
+        SealedTableEntry(TableEntry::new_with_attributes(
+            "pk".to_string(),
+            "sk".to_string(),
+            Some(vec![1, 2, 3]),
+            attributes,
+        ))
+    }
+
+    fn spec() -> UnsealSpec<'static> {
+        UnsealSpec {
+            protected_attributes: Cow::Borrowed(&[]),
+            type_name: "user".to_string(),
+            sort_key_prefix: "user".to_string(),
+            is_bound_to_primary_key: false,
+            has_record_mac: false,
+            decryption_policy: None,
+            context: None,
+            allow_term_entries: false,
+        }
+    }
+
+    #[test]
+    fn test_prepare_rejects_term_entries() {
+        let result = term_entry().prepare(&spec(), None);
+
+        assert!(matches!(
+            result,
+            Err(SealError::TamperDetected { pk, sk, .. }) if pk == "pk" && sk == "sk"
+        ));
+    }
+
+    #[test]
+    fn test_prepare_term_entries_when_allowed() {
+        let prepared = term_entry()
+            .prepare(&spec().allowing_term_entries(), None)
+            .expect("Expected term entry to be accepted");
+
+        assert!(prepared.unprotected.get("__root_sk").is_none());
+    }
 }
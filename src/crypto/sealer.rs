@@ -1,12 +1,14 @@
 use super::{
-    attrs::FlattenedProtectedAttributes, b64_encode, format_term_key, SealError, SealedTableEntry,
-    Unsealed, MAX_TERMS_PER_INDEX,
+    attrs::FlattenedProtectedAttributes,
+    b64_encode, format_term_key,
+    record_mac::{record_mac, RECORD_MAC_ATTRIBUTE},
+    sharded_term, SealError, SealedTableEntry, Unsealed,
 };
 use crate::{
     encrypted_table::{
         AttributeName, ScopedZeroKmsCipher, TableAttribute, TableAttributes, TableEntry,
     },
-    traits::PrimaryKeyParts,
+    traits::{PrimaryKeyParts, ProtectedIndex},
     IndexType,
 };
 use cipherstash_client::encryption::{
@@ -15,13 +17,17 @@ use cipherstash_client::encryption::{
 };
 use itertools::Itertools;
 use std::{borrow::Cow, collections::HashMap};
+use tracing::{instrument, Span};
 
-/// The combination of plaintext, index, name and index type for a particular field
+/// The name of the attribute of term entries which holds the `sk` of the root entry of the
+/// record, so that term entries can be verified against the primary key of their record.
+pub(crate) const ROOT_SK_ATTRIBUTE: &str = "__root_sk";
+
+/// The combination of plaintext, index and index definition for a particular field
 pub type UnsealedIndex = (
     ComposablePlaintext,
     Box<dyn ComposableIndex + Send>,
-    Cow<'static, str>,
-    IndexType,
+    ProtectedIndex,
 );
 
 /// Builder pattern for sealing a record of type, `T`.
@@ -31,14 +37,30 @@ pub struct Sealer {
 
     pub(crate) is_pk_encrypted: bool,
     pub(crate) is_sk_encrypted: bool,
+    pub(crate) is_bound_to_primary_key: bool,
+    pub(crate) has_record_mac: bool,
+    pub(crate) ttl_attribute: Option<Cow<'static, str>>,
 
     pub(crate) type_name: Cow<'static, str>,
 
     pub(crate) unsealed_indexes: Vec<UnsealedIndex>,
+    pub(crate) term_shards: fn(&str, IndexType) -> u8,
 
     pub(crate) unsealed: Unsealed,
 }
 
+/// An index which generated more terms than could be stored for a record.
+///
+/// Only the first [`ProtectedIndex::max_terms`] terms are stored so queries that would only match
+/// one of the dropped terms (for example a long prefix) will not find the record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TruncatedIndex {
+    pub index_name: String,
+    pub index_type: IndexType,
+    pub max_terms: usize,
+    pub total_terms: usize,
+}
+
 struct RecordsWithTerms {
     num_protected_attributes: usize,
     records: Vec<RecordWithTerms>,
@@ -52,6 +74,11 @@ impl RecordsWithTerms {
         }
     }
 
+    fn num_terms(&self) -> usize {
+        self.records.iter().map(|record| record.terms.len()).sum()
+    }
+
+    #[instrument(skip_all, fields(records = self.records.len(), encrypted_attributes))]
     async fn encrypt(self, cipher: &ScopedZeroKmsCipher) -> Result<Vec<Sealed>, SealError> {
         let num_records = self.records.len();
         let mut pksks = Vec::with_capacity(num_records);
@@ -62,14 +89,24 @@ impl RecordsWithTerms {
         );
 
         for sealer_with_terms in self.records {
-            let (pksk, terms, flattened_protected, unprotected) = sealer_with_terms.into_parts();
+            let has_record_mac = sealer_with_terms.has_record_mac;
+            let ttl_attribute = sealer_with_terms.ttl_attribute.clone();
+            let (pksk, (terms, truncated), flattened_protected, mut unprotected) =
+                sealer_with_terms.into_parts();
+
+            if has_record_mac {
+                let mac = record_mac(cipher, &pksk, &unprotected);
+                unprotected.insert(RECORD_MAC_ATTRIBUTE, TableAttribute::Bytes(mac));
+            }
 
             pksks.push(pksk);
-            record_terms.push(terms);
+            record_terms.push((terms, truncated, flattened_protected.len(), ttl_attribute));
             unprotecteds.push(unprotected);
             protected.extend(flattened_protected.into_iter());
         }
 
+        Span::current().record("encrypted_attributes", protected.len());
+
         // TODO: Split this out into separate functions and/or implement From for the tuple into Sealed
         if protected.is_empty() {
             unprotecteds
@@ -77,12 +114,16 @@ impl RecordsWithTerms {
                 .zip_eq(record_terms.into_iter())
                 .zip_eq(pksks.into_iter())
                 .map(|record| {
-                    let (attributes, terms, pksk) = flatten_tuple_3(record);
+                    let (attributes, (terms, truncated, encrypted_attributes, ttl_attribute), pksk) =
+                        flatten_tuple_3(record);
                     Ok(Sealed {
                         pk: pksk.pk,
                         sk: pksk.sk,
                         attributes,
                         terms,
+                        truncated,
+                        encrypted_attributes,
+                        ttl_attribute,
                     })
                 })
                 .collect()
@@ -95,12 +136,20 @@ impl RecordsWithTerms {
                 .zip_eq(record_terms.into_iter())
                 .zip_eq(pksks.into_iter())
                 .map(|record| {
-                    let (enc_attrs, unprotecteds, terms, pksk) = flatten_tuple_4(record);
+                    let (
+                        enc_attrs,
+                        unprotecteds,
+                        (terms, truncated, encrypted_attributes, ttl_attribute),
+                        pksk,
+                    ) = flatten_tuple_4(record);
                     enc_attrs.denormalize().map(|protected_attrs| Sealed {
                         pk: pksk.pk,
                         sk: pksk.sk,
                         attributes: unprotecteds.merge(protected_attrs),
                         terms,
+                        truncated,
+                        encrypted_attributes,
+                        ttl_attribute,
                     })
                 })
                 .collect()
@@ -110,8 +159,12 @@ impl RecordsWithTerms {
 
 struct RecordWithTerms {
     pksk: PrimaryKeyParts,
+    is_bound_to_primary_key: bool,
+    has_record_mac: bool,
+    ttl_attribute: Option<Cow<'static, str>>,
     unsealed: Unsealed,
     terms: Vec<Term>,
+    truncated: Vec<TruncatedIndex>,
 }
 
 impl RecordWithTerms {
@@ -119,12 +172,22 @@ impl RecordWithTerms {
         self,
     ) -> (
         PrimaryKeyParts,
-        Vec<Term>,
+        (Vec<Term>, Vec<TruncatedIndex>),
         FlattenedProtectedAttributes,
         TableAttributes,
     ) {
-        let (flattened_protected, unprotected) = self.unsealed.flatten_into_parts();
-        (self.pksk, self.terms, flattened_protected, unprotected)
+        let (mut flattened_protected, unprotected) = self.unsealed.flatten_into_parts();
+
+        if self.is_bound_to_primary_key {
+            flattened_protected.bind_to_primary_key(&self.pksk);
+        }
+
+        (
+            self.pksk,
+            (self.terms, self.truncated),
+            flattened_protected,
+            unprotected,
+        )
     }
 }
 
@@ -153,41 +216,78 @@ impl Sealer {
                 }
 
                 let type_name = &sealer.type_name;
+                let term_shards = sealer.term_shards;
+                let mut truncated = vec![];
+
+                // The terms of each index. Indexes on collections have a plaintext for every
+                // element so their terms are combined before they're capped at `max_terms`.
+                let mut index_terms: Vec<(ProtectedIndex, Vec<Vec<u8>>)> = vec![];
+
+                for (attr, index, protected_index) in sealer.unsealed_indexes {
+                    let info = format!("{}#{}", type_name, protected_index.name);
+
+                    let terms = match cipher.compound_index(index, attr, info)? {
+                        IndexTerm::Binary(x) => vec![x],
+                        IndexTerm::BinaryVec(x) => x,
+                        x => {
+                            return Err(SealError::InvalidCiphertext(format!(
+                                "Invalid index term: `{x:?}"
+                            )))
+                        }
+                    };
+
+                    match index_terms.iter_mut().find(|(index, _)| {
+                        index.name == protected_index.name
+                            && index.index_type == protected_index.index_type
+                    }) {
+                        Some((_, existing)) => existing.extend(terms),
+                        None => index_terms.push((protected_index, terms)),
+                    }
+                }
 
-                // Index name, type and term
-                let terms: Vec<(Cow<'_, str>, IndexType, Vec<u8>)> = sealer
-                    .unsealed_indexes
-                    .into_iter()
-                    .map(|(attr, index, index_name, index_type)| {
-                        let info = format!("{}#{}", type_name, index_name);
-                        let term = cipher.compound_index(index, attr, info)?;
+                // Index name, type, term and the position of the term within its index
+                let mut terms: Vec<(Cow<'_, str>, IndexType, usize, Vec<u8>)> = vec![];
 
-                        Ok::<_, SealError>((index_name, index_type, term))
-                    })
-                    .map(|index_term| match index_term {
-                        Ok((index_name, index_type, IndexTerm::Binary(x))) => {
-                            Ok(vec![(index_name, index_type, x)])
-                        }
-                        Ok((index_name, index_type, IndexTerm::BinaryVec(x))) => Ok(x
+                for (index, index_terms) in index_terms {
+                    // Elements of a collection may share terms which only need to be stored once
+                    let index_terms = index_terms.into_iter().unique().collect::<Vec<_>>();
+
+                    if index_terms.len() > index.max_terms {
+                        truncated.push(TruncatedIndex {
+                            index_name: index.name.to_string(),
+                            index_type: index.index_type,
+                            max_terms: index.max_terms,
+                            total_terms: index_terms.len(),
+                        });
+                    }
+
+                    terms.extend(
+                        index_terms
                             .into_iter()
-                            .take(MAX_TERMS_PER_INDEX)
-                            .map(|x| (index_name.clone(), index_type, x))
-                            .collect()),
-                        x => Err(SealError::InvalidCiphertext(format!(
-                            "Invalid index term: `{x:?}"
-                        ))),
-                    })
-                    .flatten_ok()
-                    .try_collect()?;
+                            .take(index.max_terms)
+                            .enumerate()
+                            .map(|(i, x)| (index.name.clone(), index.index_type, i, x)),
+                    );
+                }
 
                 let terms = terms
                     .into_iter()
-                    .enumerate()
-                    .map(|(i, (index_name, index_type, value))| {
-                        let sk = b64_encode(cipher.mac::<32>(
+                    .map(|(index_name, index_type, i, value)| {
+                        let term_key = cipher.mac::<32>(
                             &format_term_key(sk.as_str(), &index_name, index_type, i),
                             Some(pk.as_str()),
-                        ));
+                        );
+
+                        // The term key is indistinguishable from random so it can be used to
+                        // spread records evenly across the shards for this index
+                        let shards = term_shards(&index_name, index_type);
+                        let value = if shards > 1 {
+                            sharded_term(&value, term_key[0] % shards)
+                        } else {
+                            value
+                        };
+
+                        let sk = b64_encode(term_key);
 
                         Ok::<_, SealError>(Term { sk, value })
                     })
@@ -195,22 +295,38 @@ impl Sealer {
 
                 Ok(RecordWithTerms {
                     pksk: PrimaryKeyParts { pk, sk },
+                    is_bound_to_primary_key: sealer.is_bound_to_primary_key,
+                    has_record_mac: sealer.has_record_mac,
+                    ttl_attribute: sealer.ttl_attribute,
                     unsealed: sealer.unsealed,
                     terms,
+                    truncated,
                 })
             })
             .try_collect()
             .map(|records| RecordsWithTerms::new(records, num_protected_attributes))
     }
 
+    #[instrument(skip_all, fields(type_name, records, terms))]
     pub(crate) async fn seal_all<'a>(
         records: impl IntoIterator<Item = Sealer>,
         protected_attributes: impl AsRef<[Cow<'a, str>]>,
         cipher: &ScopedZeroKmsCipher,
     ) -> Result<Vec<Sealed>, SealError> {
-        Self::index_all_terms(records, protected_attributes, cipher)?
-            .encrypt(cipher)
-            .await
+        let records = records.into_iter().collect::<Vec<_>>();
+        let span = Span::current();
+
+        span.record("records", records.len());
+
+        if let Some(sealer) = records.first() {
+            span.record("type_name", sealer.type_name.as_ref());
+        }
+
+        let records_with_terms = Self::index_all_terms(records, protected_attributes, cipher)?;
+
+        span.record("terms", records_with_terms.num_terms());
+
+        records_with_terms.encrypt(cipher).await
     }
 
     pub(crate) async fn seal<'a>(
@@ -244,6 +360,9 @@ pub struct Sealed {
     sk: String,
     attributes: TableAttributes,
     terms: Vec<Term>,
+    truncated: Vec<TruncatedIndex>,
+    encrypted_attributes: usize,
+    ttl_attribute: Option<Cow<'static, str>>,
 }
 
 impl Sealed {
@@ -259,22 +378,42 @@ impl Sealed {
         }
     }
 
+    /// Returns the indexes which generated more terms than could be stored for this record.
+    pub fn truncated_indexes(&self) -> &[TruncatedIndex] {
+        &self.truncated
+    }
+
+    /// Returns the number of attributes that were encrypted for this record.
+    pub fn num_encrypted_attributes(&self) -> usize {
+        self.encrypted_attributes
+    }
+
     /// Returns the root entry and the term entries for this record.
-    /// `index_predicate` is used to... TODO!!!.
+    ///
+    /// `index_predicate` selects the attributes of the record which are projected into each term
+    /// entry. The TTL attribute (see [`crate::Identifiable::ttl_attribute`]) is always projected
+    /// so that term entries expire with their record, and the `sk` of the root entry is stored
+    /// in each term entry so that bound ciphertexts and record MACs can be verified when a
+    /// record is read from a term entry.
     pub fn into_table_entries(
         self,
         mut index_predicate: impl FnMut(&AttributeName, &TableAttribute) -> bool,
     ) -> (SealedTableEntry, Vec<SealedTableEntry>) {
         let root_attributes = self.attributes;
+        let ttl_attribute = self.ttl_attribute.as_deref();
 
-        let index_attributes: TableAttributes = root_attributes
+        let mut index_attributes: TableAttributes = root_attributes
             .clone()
             .into_iter()
-            .filter(|(name, value)| index_predicate(name, value))
+            .filter(|(name, value)| {
+                Some(name.as_external_name()) == ttl_attribute || index_predicate(name, value)
+            })
             .map(|(name, value)| (name, value.clone()))
             .collect::<HashMap<_, _>>()
             .into();
 
+        index_attributes.insert(ROOT_SK_ATTRIBUTE, TableAttribute::String(self.sk.clone()));
+
         let term_entries = self
             .terms
             .into_iter()
@@ -1,12 +1,13 @@
 use super::{
-    attrs::{FlattenedProtectedAttributes, NormalizedProtectedAttributes},
-    SealError,
+    attrs::{FlattenedProtectedAttributes, NormalizedProtectedAttributes, NormalizedValue},
+    b64_decode, b64_encode, SealError,
 };
 use crate::{
     encrypted_table::{AttributeName, TableAttribute, TableAttributes},
-    Decryptable,
+    Decryptable, Encryptable,
 };
-use cipherstash_client::encryption::Plaintext;
+use cipherstash_client::encryption::{Plaintext, TryFromPlaintext};
+use itertools::Itertools;
 use std::collections::HashMap;
 
 /// Wrapper to which values are added prior to being encrypted.
@@ -16,6 +17,10 @@ pub struct Unsealed {
     /// Protected plaintexts with their descriptors
     protected: NormalizedProtectedAttributes,
     unprotected: TableAttributes,
+
+    /// The first error which occurred while adding attributes. Returned when the record is
+    /// sealed as the methods which add attributes are infallible.
+    error: Option<SealError>,
 }
 
 impl Default for Unsealed {
@@ -29,6 +34,7 @@ impl Unsealed {
         Self {
             protected: NormalizedProtectedAttributes::new(),
             unprotected: Default::default(),
+            error: None,
         }
     }
 
@@ -37,6 +43,7 @@ impl Unsealed {
         Self {
             protected: NormalizedProtectedAttributes::new_with_prefix(descriptor),
             unprotected: Default::default(),
+            error: None,
         }
     }
 
@@ -90,6 +97,103 @@ impl Unsealed {
             .insert_and_update_map(name, subkey, value.into());
     }
 
+    /// Add the attributes of `value`, a nested type, to the protected map, `name`.
+    ///
+    /// The protected attributes of `value` are stored in the map using their names as subkeys.
+    /// Protected maps of `value` are flattened into subkeys of the form `attr.subkey` so that
+    /// types can be nested to any depth.
+    /// The plaintext attributes of `value` are stored as unprotected attributes called
+    /// `name.attr`.
+    pub fn add_protected_nested(&mut self, name: impl Into<String>, value: impl Encryptable) {
+        let name = name.into();
+        let Self {
+            protected,
+            unprotected,
+            error,
+        } = value.into_unsealed();
+
+        if self.error.is_none() {
+            self.error = error;
+        }
+
+        for (key, value) in protected.into_values() {
+            match value {
+                NormalizedValue::Scalar(plaintext) => {
+                    self.protected
+                        .insert_and_update_map(name.as_str(), key, plaintext);
+                }
+                NormalizedValue::Map(map) => {
+                    for (subkey, plaintext) in map {
+                        self.protected.insert_and_update_map(
+                            name.as_str(),
+                            format!("{key}.{subkey}"),
+                            plaintext,
+                        );
+                    }
+                }
+            }
+        }
+
+        for (attr, value) in unprotected {
+            self.unprotected
+                .insert(format!("{name}.{}", attr.as_external_name()), value);
+        }
+    }
+
+    /// Add a new protected list, `name`, with each of the `values` encrypted separately.
+    ///
+    /// The values are stored in a protected map using their positions as subkeys. Sets can be
+    /// stored as lists as their order is not significant.
+    pub fn add_protected_list(
+        &mut self,
+        name: impl Into<String>,
+        values: impl IntoIterator<Item = impl Into<Plaintext>>,
+    ) {
+        let name = name.into();
+
+        for (i, value) in values.into_iter().enumerate() {
+            self.protected
+                .insert_and_update_map(name.as_str(), i.to_string(), value.into());
+        }
+    }
+
+    /// Add a new protected list, `name`, with all of the `values` encrypted together as a single
+    /// plaintext.
+    ///
+    /// This uses less space and fewer encryptions than [Unsealed::add_protected_list] for large
+    /// lists of small values but the whole list has to be decrypted to read any of its values.
+    pub fn add_protected_list_blob(
+        &mut self,
+        name: impl Into<String>,
+        values: impl IntoIterator<Item = impl Into<Plaintext>>,
+    ) {
+        let values = values
+            .into_iter()
+            .map(|value| serde_json::Value::String(b64_encode(value.into().to_vec())))
+            .collect();
+
+        self.protected
+            .insert(name, Plaintext::from(serde_json::Value::Array(values)));
+    }
+
+    /// Serialize `value` as JSON and add it as the protected attribute, `name`.
+    ///
+    /// If `value` can't be serialized the error is returned when the record is sealed.
+    #[cfg(feature = "serde")]
+    pub fn add_protected_serde(&mut self, name: impl Into<String>, value: &impl serde::Serialize) {
+        let name = name.into();
+
+        match serde_json::to_value(value) {
+            Ok(value) => self.protected.insert(name, Plaintext::from(value)),
+            Err(e) => {
+                self.error.get_or_insert(SealError::SerdeError {
+                    name,
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+
     /// Add a new unprotected attribute, `name`, with the given plaintext.
     pub fn add_unprotected(
         &mut self,
@@ -120,6 +224,126 @@ impl Unsealed {
         self.protected.take_map(name)
     }
 
+    /// Removes the protected list, `name`, added with [Unsealed::add_protected_list] and
+    /// converts its values into `C`.
+    ///
+    /// A list which doesn't exist (e.g. because it was empty when it was added) is treated as
+    /// empty.
+    pub fn take_protected_list<C, T>(&mut self, name: &str) -> Result<C, SealError>
+    where
+        C: FromIterator<T>,
+        T: TryFromPlaintext,
+    {
+        let values = self
+            .protected
+            .take_map(name)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(i, plaintext)| {
+                i.parse::<usize>()
+                    .map(|i| (i, plaintext))
+                    .map_err(|_| SealError::InvalidCiphertext(format!("Invalid list index: {i}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        values
+            .into_iter()
+            .sorted_by_key(|(i, _)| *i)
+            .map(|(_, plaintext)| Ok(T::try_from_plaintext(plaintext)?))
+            .collect()
+    }
+
+    /// Removes the protected list, `name`, added with [Unsealed::add_protected_list_blob] and
+    /// converts its values into `C`.
+    pub fn take_protected_list_blob<C, T>(&mut self, name: &str) -> Result<C, SealError>
+    where
+        C: FromIterator<T>,
+        T: TryFromPlaintext,
+    {
+        let invalid = || SealError::InvalidCiphertext(format!("Invalid list: {name}"));
+
+        let values = match self.protected.take(name) {
+            Some(plaintext) => match serde_json::Value::try_from_plaintext(plaintext)? {
+                serde_json::Value::Array(values) => values,
+                _ => return Err(invalid()),
+            },
+            None => vec![],
+        };
+
+        values
+            .into_iter()
+            .map(|value| {
+                let bytes = value.as_str().and_then(b64_decode).ok_or_else(invalid)?;
+                Ok(T::try_from_plaintext(Plaintext::from_slice(&bytes)?)?)
+            })
+            .collect()
+    }
+
+    /// Removes the protected attribute, `name`, added with [Unsealed::add_protected_serde] and
+    /// deserializes it into `T`.
+    ///
+    /// A missing attribute is deserialized from `null` so that it can be read into an `Option`.
+    #[cfg(feature = "serde")]
+    pub fn take_protected_serde<T: serde::de::DeserializeOwned>(
+        &mut self,
+        name: &str,
+    ) -> Result<T, SealError> {
+        let value = match self.protected.take(name) {
+            Some(plaintext) => serde_json::Value::try_from_plaintext(plaintext)?,
+            None => serde_json::Value::Null,
+        };
+
+        serde_json::from_value(value).map_err(|e| SealError::SerdeError {
+            name: name.to_string(),
+            message: e.to_string(),
+        })
+    }
+
+    /// Removes the protected map, `name`, and the unprotected attributes added with
+    /// [Unsealed::add_protected_nested] and converts them into `T`.
+    pub fn take_protected_nested<T: Decryptable>(&mut self, name: &str) -> Result<T, SealError> {
+        let mut nested = Self::new();
+
+        for (subkey, plaintext) in self.protected.take_map(name).unwrap_or_default() {
+            match subkey.split_once('.') {
+                Some((key, subkey)) => {
+                    nested
+                        .protected
+                        .insert_and_update_map(key, subkey, plaintext);
+                }
+                None => nested.protected.insert(subkey, plaintext),
+            }
+        }
+
+        let prefix = format!("{name}.");
+        let unprotected_names = self
+            .unprotected
+            .iter()
+            .map(|(attr, _)| attr.as_external_name())
+            .filter(|attr| attr.starts_with(&prefix))
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+
+        for attr in unprotected_names {
+            if let Some(value) = self.unprotected.remove(attr.as_str()) {
+                nested.unprotected.insert(&attr[prefix.len()..], value);
+            }
+        }
+
+        T::from_unsealed(nested)
+    }
+
+    /// Set the descriptor used for the protected attributes unless one was set when `self` was
+    /// created with [Unsealed::new_with_descriptor].
+    pub(crate) fn set_default_descriptor(&mut self, descriptor: impl Into<String>) {
+        self.protected.set_default_prefix(descriptor);
+    }
+
+    /// Returns the first error which occurred while adding attributes to `self`.
+    pub(crate) fn take_error(&mut self) -> Option<SealError> {
+        self.error.take()
+    }
+
     /// Flatten the protected attributes and returns them along with the unprotected attributes.
     pub(crate) fn flatten_into_parts(self) -> (FlattenedProtectedAttributes, TableAttributes) {
         (self.protected.flatten(), self.unprotected)
@@ -135,7 +359,103 @@ impl Unsealed {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::BTreeMap;
+    use crate::traits::{TryFromPlaintext, TryFromTableAttr};
+    use std::{
+        borrow::Cow,
+        collections::{BTreeMap, HashSet},
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct Geo {
+        lat: String,
+        source: String,
+    }
+
+    impl Encryptable for Geo {
+        fn protected_attributes() -> Cow<'static, [Cow<'static, str>]> {
+            Cow::Borrowed(&[Cow::Borrowed("lat")])
+        }
+
+        fn plaintext_attributes() -> Cow<'static, [Cow<'static, str>]> {
+            Cow::Borrowed(&[Cow::Borrowed("source")])
+        }
+
+        fn into_unsealed(self) -> Unsealed {
+            let mut unsealed = Unsealed::new();
+            unsealed.add_protected("lat", self.lat);
+            unsealed.add_unprotected("source", self.source);
+            unsealed
+        }
+    }
+
+    impl Decryptable for Geo {
+        fn from_unsealed(mut unsealed: Unsealed) -> Result<Self, SealError> {
+            Ok(Self {
+                lat: TryFromPlaintext::try_from_optional_plaintext(unsealed.take_protected("lat"))?,
+                source: TryFromTableAttr::try_from_table_attr(unsealed.take_unprotected("source"))?,
+            })
+        }
+
+        fn protected_attributes() -> Cow<'static, [Cow<'static, str>]> {
+            <Self as Encryptable>::protected_attributes()
+        }
+
+        fn plaintext_attributes() -> Cow<'static, [Cow<'static, str>]> {
+            <Self as Encryptable>::plaintext_attributes()
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Address {
+        postcode: String,
+        geo: Geo,
+    }
+
+    impl Encryptable for Address {
+        fn protected_attributes() -> Cow<'static, [Cow<'static, str>]> {
+            Cow::Borrowed(&[Cow::Borrowed("postcode"), Cow::Borrowed("geo")])
+        }
+
+        fn plaintext_attributes() -> Cow<'static, [Cow<'static, str>]> {
+            Cow::Borrowed(&[])
+        }
+
+        fn into_unsealed(self) -> Unsealed {
+            let mut unsealed = Unsealed::new();
+            unsealed.add_protected("postcode", self.postcode);
+            unsealed.add_protected_nested("geo", self.geo);
+            unsealed
+        }
+    }
+
+    impl Decryptable for Address {
+        fn from_unsealed(mut unsealed: Unsealed) -> Result<Self, SealError> {
+            Ok(Self {
+                postcode: TryFromPlaintext::try_from_optional_plaintext(
+                    unsealed.take_protected("postcode"),
+                )?,
+                geo: unsealed.take_protected_nested("geo")?,
+            })
+        }
+
+        fn protected_attributes() -> Cow<'static, [Cow<'static, str>]> {
+            <Self as Encryptable>::protected_attributes()
+        }
+
+        fn plaintext_attributes() -> Cow<'static, [Cow<'static, str>]> {
+            <Self as Encryptable>::plaintext_attributes()
+        }
+    }
+
+    fn address() -> Address {
+        Address {
+            postcode: "3000".to_string(),
+            geo: Geo {
+                lat: "-37.81".to_string(),
+                source: "gps".to_string(),
+            },
+        }
+    }
 
     #[test]
     fn test_protected_field() {
@@ -226,4 +546,130 @@ mod tests {
         let attribute = unsealed.take_unprotected("test");
         assert!(attribute == "value".into(), "values do not match");
     }
+
+    #[test]
+    fn test_protected_nested_is_flattened() {
+        let mut unsealed = Unsealed::new_with_descriptor("test");
+        unsealed.add_protected_nested("address", address());
+
+        let nested: BTreeMap<String, Plaintext> = unsealed
+            .take_protected_map("address")
+            .unwrap()
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            nested,
+            BTreeMap::from([
+                ("postcode".to_string(), Plaintext::from("3000")),
+                ("geo.lat".to_string(), Plaintext::from("-37.81")),
+            ])
+        );
+
+        let attribute = unsealed.take_unprotected("address.geo.source");
+        assert!(attribute == "gps".into(), "values do not match");
+    }
+
+    #[test]
+    fn test_protected_nested_round_trip() {
+        let mut unsealed = Unsealed::new_with_descriptor("test");
+        unsealed.add_protected("name", "value");
+        unsealed.add_protected_nested("address", address());
+
+        let value: Address = unsealed.take_protected_nested("address").unwrap();
+        assert_eq!(value, address());
+
+        // Only the nested attributes are removed
+        assert!(unsealed.take_protected_map("address").is_none());
+        assert_eq!(
+            unsealed.take_protected("name"),
+            Some(Plaintext::from("value"))
+        );
+    }
+
+    #[test]
+    fn test_protected_lists_are_flattened() {
+        let mut unsealed = Unsealed::new_with_descriptor("test");
+        unsealed.add_protected_list("tags", ["a", "b", "c"]);
+        unsealed.add_protected_list_blob("scores", [1i64, 2, 3]);
+
+        // Each value of a list is encrypted separately but a blob is encrypted once
+        let (protected, _) = unsealed.flatten_into_parts();
+        assert_eq!(protected.len(), 4);
+    }
+
+    #[test]
+    fn test_protected_list_round_trip() {
+        let tags = (0..12).map(|i| format!("tag-{i}")).collect::<Vec<_>>();
+
+        let mut unsealed = Unsealed::new_with_descriptor("test");
+        unsealed.add_protected_list("tags", tags.clone());
+
+        let value: Vec<String> = unsealed.take_protected_list("tags").unwrap();
+        assert_eq!(value, tags);
+
+        let value: Vec<String> = unsealed.take_protected_list("tags").unwrap();
+        assert!(value.is_empty());
+    }
+
+    #[test]
+    fn test_protected_list_blob_round_trip() {
+        let mut unsealed = Unsealed::new_with_descriptor("test");
+        unsealed.add_protected_list_blob("scores", [3i64, 1, 2]);
+
+        let value: HashSet<i64> = unsealed.take_protected_list_blob("scores").unwrap();
+        assert_eq!(value, HashSet::from([1, 2, 3]));
+
+        let value: Vec<i64> = unsealed.take_protected_list_blob("scores").unwrap();
+        assert!(value.is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_protected_serde_round_trip() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Line {
+            street: String,
+            number: u32,
+        }
+
+        let lines = vec![
+            Line {
+                street: "Collins St".to_string(),
+                number: 1,
+            },
+            Line {
+                street: "Bourke St".to_string(),
+                number: 2,
+            },
+        ];
+
+        let mut unsealed = Unsealed::new_with_descriptor("test");
+        unsealed.add_protected_serde("lines", &lines);
+        assert!(unsealed.take_error().is_none());
+
+        let value: Vec<Line> = unsealed.take_protected_serde("lines").unwrap();
+        assert_eq!(value, lines);
+
+        // Missing attributes can be read as None
+        let value: Option<Vec<Line>> = unsealed.take_protected_serde("lines").unwrap();
+        assert_eq!(value, None);
+        assert!(unsealed.take_protected_serde::<Vec<Line>>("lines").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_protected_serde_error_is_deferred() {
+        // JSON objects can only have string keys
+        let value = HashMap::from([((1, 2), "value")]);
+
+        let mut unsealed = Unsealed::new_with_descriptor("test");
+        unsealed.add_protected_serde("value", &value);
+
+        assert!(matches!(
+            unsealed.take_error(),
+            Some(SealError::SerdeError { name, .. }) if name == "value"
+        ));
+        assert!(unsealed.take_protected("value").is_none());
+    }
 }
@@ -0,0 +1,225 @@
+use crate::traits::PrimaryKeyParts;
+use std::{
+    fmt,
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Describes who is decrypting records and why, for auditing.
+///
+/// A context can be passed to [`EncryptedTable::get_with`](super::EncryptedTable::get_with),
+/// [`QueryBuilder::with_context`](super::QueryBuilder::with_context) and
+/// [`EncryptedTable::decrypt_all_with`](super::EncryptedTable::decrypt_all_with).
+/// Each record decrypted with a context is recorded in the local decryption log next to the
+/// entries written by ZeroKMS, and in a `tracing` event with the target
+/// `cipherstash_dynamodb::access`. Records are only recorded once they have been decrypted
+/// successfully.
+///
+/// The context is only recorded locally: the ZeroKMS decrypt API doesn't accept a caller context,
+/// so ZeroKMS's own audit trail doesn't include it.
+///
+/// ```
+/// use cipherstash_dynamodb::AccessContext;
+///
+/// let context = AccessContext::new("support-agent-42", "customer support")
+///     .with_request_id("req-1234");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessContext {
+    /// The user or service decrypting the records.
+    pub actor_id: String,
+
+    /// Why the records are being decrypted.
+    pub purpose: String,
+
+    /// An identifier of the request the records are decrypted for, e.g. to correlate with
+    /// application logs.
+    pub request_id: Option<String>,
+}
+
+impl AccessContext {
+    pub fn new(actor_id: impl Into<String>, purpose: impl Into<String>) -> Self {
+        Self {
+            actor_id: actor_id.into(),
+            purpose: purpose.into(),
+            request_id: None,
+        }
+    }
+
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    /// Record that the records of type `type_name` with the stored primary keys `primary_keys`
+    /// are being decrypted with this context.
+    ///
+    /// Entries are appended to the file at `log_path` if there is one.
+    pub(crate) fn log_decryptions(
+        &self,
+        type_name: &str,
+        primary_keys: &[PrimaryKeyParts],
+        log_path: Option<&Path>,
+    ) {
+        tracing::info!(
+            target: "cipherstash_dynamodb::access",
+            actor_id = %self.actor_id,
+            purpose = %self.purpose,
+            request_id = self.request_id.as_deref(),
+            type_name,
+            records = primary_keys.len(),
+            "decrypting records"
+        );
+
+        if let Some(log_path) = log_path {
+            if let Err(error) = self.append_to_log(type_name, primary_keys, log_path) {
+                tracing::warn!(
+                    target: "cipherstash_dynamodb::access",
+                    %error,
+                    "failed to write to the decryption log"
+                );
+            }
+        }
+    }
+
+    fn append_to_log(
+        &self,
+        type_name: &str,
+        primary_keys: &[PrimaryKeyParts],
+        log_path: &Path,
+    ) -> Result<(), std::io::Error> {
+        let mut log_file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path)?;
+
+        let utc_now = SystemTime::now();
+
+        for primary_key in primary_keys {
+            writeln!(
+                log_file,
+                "{}",
+                self.log_line(utc_now, type_name, primary_key)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Format an entry of the decryption log, following the format used by ZeroKMS.
+    fn log_line(
+        &self,
+        utc_now: SystemTime,
+        type_name: &str,
+        PrimaryKeyParts { pk, sk }: &PrimaryKeyParts,
+    ) -> String {
+        let utc_now = format_utc(utc_now);
+
+        format!("[{utc_now}] - Type: {type_name} - PK: {pk} - SK: {sk} - {self}")
+    }
+}
+
+impl fmt::Display for AccessContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Actor: {} - Purpose: {} - Request: {}",
+            self.actor_id,
+            self.purpose,
+            self.request_id.as_deref().unwrap_or("null")
+        )
+    }
+}
+
+/// Format `time` like chrono's `DateTime<Utc>`, which ZeroKMS uses for its decryption log,
+/// e.g. `2023-11-14 22:13:20.5 UTC` is written as `2023-11-14 22:13:20.500 UTC`.
+fn format_utc(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let nanos = since_epoch.subsec_nanos();
+
+    let (year, month, day) = civil_from_days(secs / 86_400);
+    let secs_of_day = secs % 86_400;
+
+    let fraction = match nanos {
+        0 => String::new(),
+        nanos if nanos % 1_000_000 == 0 => format!(".{:03}", nanos / 1_000_000),
+        nanos if nanos % 1_000 == 0 => format!(".{:06}", nanos / 1_000),
+        nanos => format!(".{nanos:09}"),
+    };
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}{fraction} UTC",
+        secs_of_day / 3_600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+/// Convert days since the unix epoch to a `(year, month, day)` date in the proleptic Gregorian
+/// calendar, see <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_utc_matches_chrono() {
+        for (secs, nanos) in [
+            (0, 0),
+            (951_782_400, 0),
+            (1_700_000_000, 500_000_000),
+            (1_709_164_800, 123_456_000),
+            (4_102_444_799, 999_999_999),
+        ] {
+            let time = UNIX_EPOCH + Duration::new(secs, nanos);
+            let expected = DateTime::from_timestamp(secs as i64, nanos).unwrap();
+
+            assert_eq!(format_utc(time), expected.to_string());
+        }
+    }
+
+    #[test]
+    fn test_log_line() {
+        let utc_now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let primary_key = PrimaryKeyParts {
+            pk: "user#1".to_string(),
+            sk: "user".to_string(),
+        };
+
+        let context = AccessContext::new("agent-42", "customer support");
+
+        assert_eq!(
+            context.log_line(utc_now, "user", &primary_key),
+            "[2023-11-14 22:13:20 UTC] - Type: user - PK: user#1 - SK: user - Actor: agent-42 - Purpose: customer support - Request: null"
+        );
+
+        let context = context.with_request_id("req-1");
+
+        assert_eq!(
+            context.log_line(utc_now, "user", &primary_key),
+            "[2023-11-14 22:13:20 UTC] - Type: user - PK: user#1 - SK: user - Actor: agent-42 - Purpose: customer support - Request: req-1"
+        );
+    }
+}
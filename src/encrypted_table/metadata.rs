@@ -0,0 +1,106 @@
+use aws_sdk_dynamodb::types::ConsumedCapacity;
+use std::time::Duration;
+
+/// Details about the work done to complete an operation on an [`crate::EncryptedTable`].
+///
+/// Returned by the `*_with_metadata` variants of `put`, `get`, `delete` and `query().send()`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OperationMetadata {
+    /// The capacity reported by DynamoDB for each request made during the operation.
+    pub consumed_capacity: Vec<ConsumedCapacity>,
+
+    /// The number of root items written. Each record has exactly one root item.
+    pub root_items_written: usize,
+    /// The number of index term items written.
+    pub term_items_written: usize,
+
+    /// The number of root items deleted.
+    pub root_items_deleted: usize,
+    /// The number of index term items deleted.
+    ///
+    /// When updating or deleting a record every term that *could* exist for the record is deleted
+    /// so this counts delete requests rather than items that actually existed.
+    pub term_items_deleted: usize,
+
+    /// The number of items returned by DynamoDB.
+    pub items_read: usize,
+
+    /// The number of DynamoDB requests which were retried after a transient failure.
+    /// See [`crate::encrypted_table::RetryPolicy`].
+    pub retries: usize,
+
+    /// The number of attributes encrypted with ZeroKMS.
+    pub encrypted_attributes: usize,
+    /// The number of attributes decrypted with ZeroKMS.
+    pub decrypted_attributes: usize,
+
+    /// Time spent encrypting, decrypting and generating index terms, including requests to ZeroKMS.
+    pub crypto_duration: Duration,
+    /// Time spent waiting on DynamoDB.
+    pub io_duration: Duration,
+}
+
+impl OperationMetadata {
+    /// Returns the sum of the capacity units consumed by every DynamoDB request in the operation.
+    pub fn total_capacity_units(&self) -> f64 {
+        self.consumed_capacity
+            .iter()
+            .filter_map(|capacity| capacity.capacity_units())
+            .sum()
+    }
+
+    pub(crate) fn add_consumed_capacity(&mut self, capacity: Option<ConsumedCapacity>) {
+        self.consumed_capacity.extend(capacity);
+    }
+
+    /// Export the metadata for a completed operation as counters and histograms via the
+    /// [`metrics`](https://docs.rs/metrics) crate.
+    ///
+    /// Does nothing unless the `metrics` feature is enabled.
+    pub(crate) fn record(&self, operation: &'static str, type_name: &str) {
+        #[cfg(feature = "metrics")]
+        {
+            let labels = [
+                ("operation", operation.to_string()),
+                ("type_name", type_name.to_string()),
+            ];
+
+            metrics::counter!("cipherstash_dynamodb_operations_total", &labels).increment(1);
+
+            for (kind, written, deleted) in [
+                ("root", self.root_items_written, self.root_items_deleted),
+                ("term", self.term_items_written, self.term_items_deleted),
+            ] {
+                let labels = [
+                    ("operation", operation.to_string()),
+                    ("type_name", type_name.to_string()),
+                    ("kind", kind.to_string()),
+                ];
+
+                metrics::counter!("cipherstash_dynamodb_items_written_total", &labels)
+                    .increment(written as u64);
+                metrics::counter!("cipherstash_dynamodb_items_deleted_total", &labels)
+                    .increment(deleted as u64);
+            }
+
+            metrics::counter!("cipherstash_dynamodb_items_read_total", &labels)
+                .increment(self.items_read as u64);
+            metrics::counter!("cipherstash_dynamodb_retries_total", &labels)
+                .increment(self.retries as u64);
+            metrics::counter!("cipherstash_dynamodb_encrypted_attributes_total", &labels)
+                .increment(self.encrypted_attributes as u64);
+            metrics::counter!("cipherstash_dynamodb_decrypted_attributes_total", &labels)
+                .increment(self.decrypted_attributes as u64);
+
+            metrics::histogram!("cipherstash_dynamodb_consumed_capacity_units", &labels)
+                .record(self.total_capacity_units());
+            metrics::histogram!("cipherstash_dynamodb_crypto_duration_seconds", &labels)
+                .record(self.crypto_duration.as_secs_f64());
+            metrics::histogram!("cipherstash_dynamodb_io_duration_seconds", &labels)
+                .record(self.io_duration.as_secs_f64());
+        }
+
+        #[cfg(not(feature = "metrics"))]
+        let _ = (operation, type_name);
+    }
+}
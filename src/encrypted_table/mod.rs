@@ -1,11 +1,19 @@
+mod access_context;
 mod attribute_name;
+mod metadata;
+mod partition_query;
 pub mod query;
+mod retry;
 mod table_attribute;
 mod table_attributes;
 mod table_entry;
 pub use self::{
+    access_context::AccessContext,
     attribute_name::AttributeName,
-    query::QueryBuilder,
+    metadata::OperationMetadata,
+    partition_query::PartitionQueryBuilder,
+    query::{QueryBuilder, TypedQuery},
+    retry::RetryPolicy,
     table_attribute::{TableAttribute, TryFromTableAttr},
     table_attributes::TableAttributes,
     table_entry::TableEntry,
@@ -13,10 +21,16 @@ pub use self::{
 use crate::{
     crypto::*,
     errors::*,
-    traits::{Decryptable, PrimaryKey, PrimaryKeyError, PrimaryKeyParts, Searchable},
-    Identifiable, IndexType,
+    traits::{
+        Decryptable, KeyComponent, PrimaryKey, PrimaryKeyError, PrimaryKeyParts, ProtectedIndex,
+        Searchable,
+    },
+    Identifiable,
+};
+use aws_sdk_dynamodb::{
+    config::{retry::RetryConfig, AsyncSleep},
+    types::{AttributeValue, Delete, Put, ReturnConsumedCapacity, TransactWriteItem, Update},
 };
-use aws_sdk_dynamodb::types::{AttributeValue, Delete, Put, TransactWriteItem};
 use cipherstash_client::{
     config::{
         console_config::ConsoleConfig, cts_config::CtsConfig, zero_kms_config::ZeroKMSConfig,
@@ -25,17 +39,27 @@ use cipherstash_client::{
     encryption::ScopedCipher,
     zerokms::{ClientKey, ZeroKMS, ZeroKMSWithClientKey},
 };
-use log::info;
+use itertools::Itertools;
 use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
     ops::Deref,
+    path::{Path, PathBuf},
     sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use tracing::{debug, info, info_span, instrument, warn, Instrument, Span};
 use uuid::Uuid;
 
 pub type DatasetId = Uuid;
 
+/// The maximum number of items DynamoDB accepts in a single transaction.
+const MAX_TRANSACT_WRITE_ITEMS: usize = 100;
+
+/// The name of the attribute which marks a record as soft deleted, holding the time it was
+/// deleted in seconds since the Unix epoch. See [`Identifiable::has_soft_delete`].
+pub(crate) const DELETED_AT_ATTRIBUTE: &str = "__deleted_at";
+
 pub struct Headless;
 
 pub struct Dynamo {
@@ -57,12 +81,109 @@ pub type ScopedZeroKmsCipher = ScopedCipher<AutoRefresh<ServiceCredentials>>;
 pub struct EncryptedTable<D = Dynamo> {
     db: D,
     cipher: Arc<ZeroKmsCipher>,
+    strict_index_terms: bool,
+    filter_expired: bool,
+    retry_policy: RetryPolicy,
+    decryption_log_path: Option<PathBuf>,
+    decryption_policy: Option<Arc<dyn DecryptionPolicy>>,
 }
 
 impl<D> EncryptedTable<D> {
     pub fn cipher(&self) -> Arc<ZeroKmsCipher> {
         self.cipher.clone()
     }
+
+    /// The path to the local decryption log if it is enabled in the [`ZeroKMSConfig`].
+    ///
+    /// Decryptions with an [`AccessContext`] are recorded here along with the entries written by
+    /// ZeroKMS.
+    pub fn decryption_log_path(&self) -> Option<&Path> {
+        self.decryption_log_path.as_deref()
+    }
+
+    /// Record the decryption of the records of type `T` with the stored `primary_keys` if there
+    /// is a `context`.
+    ///
+    /// This must only be called once the records have been decrypted successfully.
+    pub(crate) fn log_access<T: Identifiable>(
+        &self,
+        context: Option<&AccessContext>,
+        primary_keys: &[PrimaryKeyParts],
+    ) {
+        if let Some(context) = context {
+            context.log_decryptions(&T::type_name(), primary_keys, self.decryption_log_path());
+        }
+    }
+
+    /// Fail any put where an index generates more terms than can be stored for it with
+    /// [`SealError::TooManyTerms`] instead of dropping the extra terms.
+    ///
+    /// By default extra terms are dropped and reported in the [`PutReport`] returned by
+    /// [`EncryptedTable::put_with_report`].
+    pub fn with_strict_index_terms(mut self, strict: bool) -> Self {
+        self.strict_index_terms = strict;
+        self
+    }
+
+    /// Skip records whose TTL attribute (see [`Identifiable::ttl_attribute`]) is in the past
+    /// when they are returned by `get`, `query` or `query_partition`.
+    ///
+    /// DynamoDB can take some time to delete items after they expire so by default expired
+    /// records are returned until they are deleted.
+    pub fn with_filter_expired(mut self, filter: bool) -> Self {
+        self.filter_expired = filter;
+        self
+    }
+
+    /// Returns true if [`EncryptedTable::with_filter_expired`] is set and the TTL attribute of
+    /// `item` is in the past.
+    pub(crate) fn is_expired<T: Identifiable>(
+        &self,
+        item: &HashMap<String, AttributeValue>,
+    ) -> bool {
+        if !self.filter_expired {
+            return false;
+        }
+
+        let Some(ttl_attribute) = T::ttl_attribute() else {
+            return false;
+        };
+
+        is_expired_at(
+            item,
+            AttributeName::new(ttl_attribute).as_stored_name(),
+            since_epoch().as_secs_f64(),
+        )
+    }
+
+    /// Set the [`DecryptionPolicy`] which decides whether each protected attribute is decrypted,
+    /// masked or omitted when records are read from the table.
+    ///
+    /// The policy is given the [`AccessContext`] passed to `get_with`, `with_context` or
+    /// `decrypt_all_with`, if any.
+    pub fn with_decryption_policy(mut self, policy: impl DecryptionPolicy + 'static) -> Self {
+        self.decryption_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Returns the [`UnsealSpec`] for `T` with the [`DecryptionPolicy`] of the table and `context`.
+    pub(crate) fn unseal_spec_for<T>(&self, context: Option<&AccessContext>) -> UnsealSpec<'static>
+    where
+        T: Decryptable + Identifiable,
+    {
+        UnsealSpec::new_for_decryptable::<T>()
+            .with_decryption_policy(self.decryption_policy.clone())
+            .with_context(context.cloned())
+    }
+
+    /// Set the [`RetryPolicy`] used when writing records to DynamoDB fails because of a
+    /// transient conflict or throttling.
+    ///
+    /// By default [`RetryPolicy::default`] is used.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
 }
 
 impl EncryptedTable<Headless> {
@@ -98,6 +219,11 @@ impl EncryptedTable<Headless> {
         Ok(Self {
             db: Headless,
             cipher: Arc::new(cipher),
+            strict_index_terms: false,
+            filter_expired: false,
+            retry_policy: RetryPolicy::default(),
+            decryption_log_path: zerokms_config.decryption_log_path(),
+            decryption_policy: None,
         })
     }
 }
@@ -112,17 +238,43 @@ impl EncryptedTable<Headless> {
 pub struct DynamoRecordPatch {
     pub put_records: Vec<HashMap<String, AttributeValue>>,
     pub delete_records: Vec<PrimaryKeyParts>,
+
+    /// Indexes which generated more terms than could be stored when creating a put patch.
+    pub truncated_indexes: Vec<TruncatedIndex>,
+
+    /// The number of attributes encrypted when creating a put patch.
+    pub encrypted_attributes: usize,
+}
+
+/// The outcome of inserting a record into the table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PutReport {
+    /// The primary key of the inserted record before any encryption.
+    pub primary_key: PrimaryKeyParts,
+
+    /// Indexes which generated more terms than could be stored for the record.
+    ///
+    /// Queries that would only match the dropped terms will not find the record.
+    /// See [`crate::ProtectedIndex::max_terms`].
+    pub truncated_indexes: Vec<TruncatedIndex>,
+}
+
+impl PutReport {
+    /// Returns true if any index terms were dropped for the record.
+    pub fn is_truncated(&self) -> bool {
+        !self.truncated_indexes.is_empty()
+    }
 }
 
 pub struct PreparedRecord {
-    protected_indexes: Cow<'static, [(Cow<'static, str>, IndexType)]>,
+    protected_indexes: Cow<'static, [ProtectedIndex]>,
     protected_attributes: Cow<'static, [Cow<'static, str>]>,
     sealer: Sealer,
 }
 
 pub struct PreparedDelete {
     primary_key: PreparedPrimaryKey,
-    protected_indexes: Cow<'static, [(Cow<'static, str>, IndexType)]>,
+    protected_indexes: Cow<'static, [ProtectedIndex]>,
 }
 
 impl PreparedDelete {
@@ -147,14 +299,14 @@ impl PreparedDelete {
         self.primary_key.clone()
     }
 
-    pub fn protected_indexes(&self) -> &[(Cow<'static, str>, IndexType)] {
+    pub fn protected_indexes(&self) -> &[ProtectedIndex] {
         &self.protected_indexes
     }
 }
 
 impl PreparedRecord {
     pub(crate) fn new(
-        protected_indexes: Cow<'static, [(Cow<'static, str>, IndexType)]>,
+        protected_indexes: Cow<'static, [ProtectedIndex]>,
         protected_attributes: Cow<'static, [Cow<'static, str>]>,
         sealer: Sealer,
     ) -> Self {
@@ -178,21 +330,40 @@ impl PreparedRecord {
         let protected_indexes = R::protected_indexes();
         let protected_attributes = R::protected_attributes();
 
-        // Get the CompositePlaintext, ComposableIndex, name and type for each index
+        // Get the CompositePlaintext, ComposableIndex, name and type for each index. Indexes on
+        // collections have one for every element.
         let unsealed_indexes = protected_indexes
             .iter()
-            .map(|(index_name, index_type)| {
+            .filter(|protected_index| {
+                record.has_attribute_for_index(&protected_index.name, protected_index.index_type)
+            })
+            .map(|protected_index| {
+                let ProtectedIndex {
+                    name, index_type, ..
+                } = protected_index;
+
+                let missing = || SealError::MissingAttribute(name.to_string());
+
                 record
-                    .attribute_for_index(index_name, *index_type)
-                    .and_then(|attr| {
-                        R::index_by_name(index_name, *index_type)
-                            .map(|index| (attr, index, index_name.clone(), *index_type))
+                    .attributes_for_index(name, *index_type)
+                    .ok_or_else(missing)?
+                    .into_iter()
+                    .map(|attr| {
+                        R::index_by_name(name, *index_type)
+                            .map(|index| (attr, index, protected_index.clone()))
+                            .ok_or_else(missing)
                     })
-                    .ok_or(SealError::MissingAttribute(index_name.to_string()))
+                    .collect::<Result<Vec<_>, _>>()
             })
+            .flatten_ok()
             .collect::<Result<Vec<_>, _>>()?;
 
-        let unsealed = record.into_unsealed();
+        let mut unsealed = record.into_unsealed();
+        unsealed.set_default_descriptor(type_name.as_ref());
+
+        if let Some(error) = unsealed.take_error() {
+            return Err(error);
+        }
 
         let sealer = Sealer {
             pk,
@@ -200,10 +371,14 @@ impl PreparedRecord {
 
             is_sk_encrypted: R::is_sk_encrypted(),
             is_pk_encrypted: R::is_pk_encrypted(),
+            is_bound_to_primary_key: R::is_bound_to_primary_key(),
+            has_record_mac: R::has_record_mac(),
+            ttl_attribute: R::ttl_attribute(),
 
             type_name,
 
             unsealed_indexes,
+            term_shards: R::term_shards,
 
             unsealed,
         };
@@ -226,7 +401,7 @@ impl PreparedRecord {
         &self.sealer.type_name
     }
 
-    pub fn protected_indexes(&self) -> &[(Cow<'static, str>, IndexType)] {
+    pub fn protected_indexes(&self) -> &[ProtectedIndex] {
         &self.protected_indexes
     }
 }
@@ -281,6 +456,37 @@ impl<D> EncryptedTable<D> {
         QueryBuilder::with_backend(self)
     }
 
+    /// Start a query from a [`TypedQuery`] generated by the `Searchable` derive, e.g.
+    /// `table.typed_query(User::query().email_eq("dan@coderdan.co"))`.
+    pub fn typed_query<Q>(&self, query: Q) -> QueryBuilder<Q::Searchable, &Self>
+    where
+        Q: TypedQuery,
+    {
+        QueryBuilder::from_typed(query, self)
+    }
+
+    /// List the records of type `T` stored under the partition key `pk`, in sort key order.
+    ///
+    /// The partition key is encrypted if needed. Records with a sort key of their own can only
+    /// be listed if every field of the sort key is `#[cipherstash(plaintext)]` since encrypted
+    /// sort keys are stored as MACs. Their sort keys can also be matched with
+    /// [`PartitionQueryBuilder::sk_starts_with`] or [`PartitionQueryBuilder::sk_between`].
+    pub fn query_partition<T>(
+        &self,
+        pk: impl Into<<T::PrimaryKey as PrimaryKey>::Pk>,
+    ) -> PartitionQueryBuilder<T, &Self>
+    where
+        T: Decryptable + Identifiable,
+    {
+        PartitionQueryBuilder::new(pk.into().to_key(), self)
+    }
+
+    /// Decrypt a list of raw items, e.g. those returned by a
+    /// [`PreparedQuery`](query::PreparedQuery).
+    ///
+    /// The items may be index term entries returned by a query of the TermIndex, which are
+    /// verified against the primary key of the record they were written for. To read the record
+    /// stored under a primary key use [`EncryptedTable::get`].
     pub async fn decrypt_all<T>(
         &self,
         items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
@@ -288,7 +494,64 @@ impl<D> EncryptedTable<D> {
     where
         T: Decryptable + Identifiable,
     {
-        Ok(decrypt_all(&self.cipher, items).await?)
+        self.decrypt_all_inner(items, None, None).await
+    }
+
+    /// Decrypt a list of items like [`EncryptedTable::decrypt_all`] which were written to a
+    /// specific dataset, e.g. with [`EncryptedTable::put_via`].
+    ///
+    /// Record MACs are computed with the key of the dataset so records from a dataset other than
+    /// the default can only be verified with this method.
+    pub async fn decrypt_all_via<T>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+        dataset_id: DatasetId,
+    ) -> Result<Vec<T>, DecryptError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.decrypt_all_inner(items, Some(dataset_id), None).await
+    }
+
+    /// Decrypt a list of items like [`EncryptedTable::decrypt_all`] and record who is decrypting
+    /// them and why in the decryption log. See [`AccessContext`].
+    pub async fn decrypt_all_with<T>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+        context: &AccessContext,
+    ) -> Result<Vec<T>, DecryptError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.decrypt_all_inner(items, None, Some(context)).await
+    }
+
+    async fn decrypt_all_inner<T>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+        dataset_id: Option<DatasetId>,
+        context: Option<&AccessContext>,
+    ) -> Result<Vec<T>, DecryptError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        let items = items.into_iter().collect::<Vec<_>>();
+        let primary_keys = access_log_keys(context, &items);
+
+        let spec = self.unseal_spec_for::<T>(context).allowing_term_entries();
+        let scoped_cipher = self.init_scoped_cipher_for(&spec, dataset_id).await?;
+
+        let records = decrypt_all(
+            &self.cipher,
+            scoped_cipher.as_ref(),
+            spec,
+            items,
+            &mut OperationMetadata::default(),
+        )
+        .await?;
+        self.log_access::<T>(context, &primary_keys);
+
+        Ok(records)
     }
 
     pub async fn unseal<'a>(
@@ -296,7 +559,12 @@ impl<D> EncryptedTable<D> {
         spec: UnsealSpec<'a>,
         item: HashMap<String, AttributeValue>,
     ) -> Result<Unsealed, DecryptError> {
-        unseal(&self.cipher, spec, item).await
+        let spec = spec
+            .with_decryption_policy(self.decryption_policy.clone())
+            .allowing_term_entries();
+        let scoped_cipher = self.init_scoped_cipher_for(&spec, None).await?;
+
+        unseal(&self.cipher, scoped_cipher.as_ref(), spec, item).await
     }
 
     pub async fn unseal_all<'a>(
@@ -304,9 +572,113 @@ impl<D> EncryptedTable<D> {
         spec: UnsealSpec<'a>,
         items: Vec<HashMap<String, AttributeValue>>,
     ) -> Result<Vec<Unsealed>, DecryptError> {
-        Ok(unseal_all(&self.cipher, spec, items).await?)
+        let spec = spec
+            .with_decryption_policy(self.decryption_policy.clone())
+            .allowing_term_entries();
+        let scoped_cipher = self.init_scoped_cipher_for(&spec, None).await?;
+
+        Ok(unseal_all(
+            &self.cipher,
+            scoped_cipher.as_ref(),
+            spec,
+            items,
+            &mut OperationMetadata::default(),
+        )
+        .await?)
+    }
+
+    /// Unseal a list of items like [`EncryptedTable::unseal_all`] but return a result for each
+    /// item instead of failing if any one of them is invalid.
+    ///
+    /// The results are in the same order as `items`. Items which appear to have been tampered
+    /// with return [`SealError::TamperDetected`] identifying the record, while the remaining items
+    /// are still decrypted in bulk.
+    pub async fn unseal_each<'a>(
+        &self,
+        spec: UnsealSpec<'a>,
+        items: Vec<HashMap<String, AttributeValue>>,
+    ) -> Result<Vec<Result<Unsealed, SealError>>, DecryptError> {
+        let spec = spec
+            .with_decryption_policy(self.decryption_policy.clone())
+            .allowing_term_entries();
+        let scoped_cipher = self.init_scoped_cipher_for(&spec, None).await?;
+
+        Ok(unseal_each(
+            &self.cipher,
+            scoped_cipher.as_ref(),
+            spec,
+            items,
+            &mut OperationMetadata::default(),
+        )
+        .await?)
+    }
+
+    /// Decrypt a list of items like [`EncryptedTable::decrypt_all`] but return a result for each
+    /// item along with its stored primary key instead of failing if any one of them is invalid.
+    ///
+    /// All valid items are still decrypted with a single request to ZeroKMS. An error is only
+    /// returned for the whole list if that request fails.
+    pub async fn decrypt_each<T>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+    ) -> Result<Vec<(PrimaryKeyParts, Result<T, DecryptError>)>, DecryptError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.decrypt_each_inner(items, None).await
+    }
+
+    /// Decrypt a list of items like [`EncryptedTable::decrypt_each`] which were written to a
+    /// specific dataset, e.g. with [`EncryptedTable::put_via`].
+    pub async fn decrypt_each_via<T>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+        dataset_id: DatasetId,
+    ) -> Result<Vec<(PrimaryKeyParts, Result<T, DecryptError>)>, DecryptError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.decrypt_each_inner(items, Some(dataset_id)).await
     }
 
+    async fn decrypt_each_inner<T>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<Vec<(PrimaryKeyParts, Result<T, DecryptError>)>, DecryptError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        let spec = self.unseal_spec_for::<T>(None).allowing_term_entries();
+        let scoped_cipher = self.init_scoped_cipher_for(&spec, dataset_id).await?;
+
+        Ok(decrypt_each(
+            &self.cipher,
+            scoped_cipher.as_ref(),
+            spec,
+            items,
+            &mut OperationMetadata::default(),
+        )
+        .await?)
+    }
+
+    /// Initialise a [ScopedZeroKmsCipher] for `dataset_id` (or the default dataset) if one is
+    /// needed to unseal records with `spec` (e.g. to verify record MACs).
+    async fn init_scoped_cipher_for(
+        &self,
+        spec: &UnsealSpec<'_>,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<Option<ScopedZeroKmsCipher>, SealError> {
+        if spec.requires_scoped_cipher() {
+            Ok(Some(
+                ScopedZeroKmsCipher::init(self.cipher.clone(), dataset_id).await?,
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+
+    #[instrument(skip_all, fields(delete_records))]
     pub async fn create_delete_patch(
         &self,
         delete: PreparedDelete,
@@ -329,9 +701,13 @@ impl<D> EncryptedTable<D> {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
+        Span::current().record("delete_records", delete_records.len());
+
         Ok(DynamoRecordPatch {
             put_records: vec![],
             delete_records,
+            truncated_indexes: vec![],
+            encrypted_attributes: 0,
         })
     }
 
@@ -343,6 +719,13 @@ impl<D> EncryptedTable<D> {
     ///
     /// This patch will also include multiple delete items to remove any index keys that could be
     /// remaining in the database after updating a record.
+    ///
+    /// If the table was configured with [`EncryptedTable::with_strict_index_terms`] this will fail
+    /// when an index generates more terms than can be stored for it.
+    #[instrument(
+        skip_all,
+        fields(type_name = record.type_name(), put_records, delete_records)
+    )]
     pub async fn create_put_patch(
         &self,
         record: PreparedRecord,
@@ -363,6 +746,27 @@ impl<D> EncryptedTable<D> {
         // Do the encryption
         let sealed = sealer.seal(protected_attributes, &indexable_cipher).await?;
 
+        let truncated_indexes = sealed.truncated_indexes().to_vec();
+        let encrypted_attributes = sealed.num_encrypted_attributes();
+
+        if self.strict_index_terms {
+            if let Some(truncated) = truncated_indexes.first().cloned() {
+                let TruncatedIndex {
+                    index_name,
+                    index_type,
+                    max_terms,
+                    total_terms,
+                } = truncated;
+
+                return Err(SealError::TooManyTerms {
+                    index_name,
+                    index_type,
+                    max_terms,
+                    total_terms,
+                })?;
+            }
+        }
+
         let mut put_records = Vec::with_capacity(sealed.len());
 
         // When doing an upsert you need to delete any index keys that are not used for the current
@@ -396,9 +800,15 @@ impl<D> EncryptedTable<D> {
             });
         }
 
+        Span::current()
+            .record("put_records", put_records.len())
+            .record("delete_records", delete_records.len());
+
         Ok(DynamoRecordPatch {
             put_records,
             delete_records,
+            truncated_indexes,
+            encrypted_attributes,
         })
     }
 }
@@ -416,6 +826,11 @@ impl EncryptedTable<Dynamo> {
                 db,
             },
             cipher: table.cipher,
+            strict_index_terms: table.strict_index_terms,
+            filter_expired: table.filter_expired,
+            retry_policy: table.retry_policy,
+            decryption_log_path: table.decryption_log_path,
+            decryption_policy: table.decryption_policy,
         })
     }
 
@@ -432,6 +847,11 @@ impl EncryptedTable<Dynamo> {
                 db,
             },
             cipher: table.cipher,
+            strict_index_terms: table.strict_index_terms,
+            filter_expired: table.filter_expired,
+            retry_policy: table.retry_policy,
+            decryption_log_path: table.decryption_log_path,
+            decryption_policy: table.decryption_policy,
         })
     }
 
@@ -440,7 +860,9 @@ impl EncryptedTable<Dynamo> {
     where
         T: Decryptable + Identifiable,
     {
-        self.get_inner(k, None).await
+        self.get_inner(k, None, false, None)
+            .await
+            .map(|(record, _)| record)
     }
 
     /// Get a record from the table by primary key from a specific dataset.
@@ -452,37 +874,159 @@ impl EncryptedTable<Dynamo> {
     where
         T: Decryptable + Identifiable,
     {
-        self.get_inner(k, Some(dataset_id)).await
+        self.get_inner(k, Some(dataset_id), false, None)
+            .await
+            .map(|(record, _)| record)
+    }
+
+    /// Get a record from the table by primary key from the default dataset like
+    /// [`EncryptedTable::get`] and record who is decrypting it and why in the decryption log.
+    ///
+    /// See [`AccessContext`].
+    pub async fn get_with<T>(
+        &self,
+        k: impl Into<T::PrimaryKey>,
+        context: &AccessContext,
+    ) -> Result<Option<T>, GetError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.get_inner(k, None, false, Some(context))
+            .await
+            .map(|(record, _)| record)
     }
 
+    /// Get a record from the table by primary key from a specific dataset like
+    /// [`EncryptedTable::get_via`] and record who is decrypting it and why in the decryption log.
+    pub async fn get_via_with<T>(
+        &self,
+        k: impl Into<T::PrimaryKey>,
+        dataset_id: DatasetId,
+        context: &AccessContext,
+    ) -> Result<Option<T>, GetError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.get_inner(k, Some(dataset_id), false, Some(context))
+            .await
+            .map(|(record, _)| record)
+    }
+
+    /// Get a record from the table by primary key from the default dataset like
+    /// [`EncryptedTable::get`] but also return it if it has been soft deleted.
+    ///
+    /// See [`Identifiable::has_soft_delete`].
+    pub async fn get_including_deleted<T>(
+        &self,
+        k: impl Into<T::PrimaryKey>,
+    ) -> Result<Option<T>, GetError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.get_inner(k, None, true, None)
+            .await
+            .map(|(record, _)| record)
+    }
+
+    /// Get a record from the table by primary key from a specific dataset like
+    /// [`EncryptedTable::get_via`] but also return it if it has been soft deleted.
+    pub async fn get_including_deleted_via<T>(
+        &self,
+        k: impl Into<T::PrimaryKey>,
+        dataset_id: DatasetId,
+    ) -> Result<Option<T>, GetError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.get_inner(k, Some(dataset_id), true, None)
+            .await
+            .map(|(record, _)| record)
+    }
+
+    /// Get a record from the table by primary key from the default dataset along with
+    /// [`OperationMetadata`] describing the work done to retrieve it.
+    pub async fn get_with_metadata<T>(
+        &self,
+        k: impl Into<T::PrimaryKey>,
+    ) -> Result<(Option<T>, OperationMetadata), GetError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.get_inner(k, None, false, None).await
+    }
+
+    /// Get a record from the table by primary key from a specific dataset along with
+    /// [`OperationMetadata`] describing the work done to retrieve it.
+    pub async fn get_via_with_metadata<T>(
+        &self,
+        k: impl Into<T::PrimaryKey>,
+        dataset_id: DatasetId,
+    ) -> Result<(Option<T>, OperationMetadata), GetError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.get_inner(k, Some(dataset_id), false, None).await
+    }
+
+    #[instrument(name = "get", skip_all, fields(type_name = %T::type_name()))]
     async fn get_inner<T>(
         &self,
         k: impl Into<T::PrimaryKey>,
         dataset_id: Option<DatasetId>,
-    ) -> Result<Option<T>, GetError>
+        include_deleted: bool,
+        context: Option<&AccessContext>,
+    ) -> Result<(Option<T>, OperationMetadata), GetError>
     where
         T: Decryptable + Identifiable,
     {
+        let mut metadata = OperationMetadata::default();
+
+        let start = Instant::now();
         let cipher = ScopedZeroKmsCipher::init(self.cipher.clone(), dataset_id).await?;
 
         let PrimaryKeyParts { pk, sk } =
             encrypt_primary_key_parts(&cipher, PreparedPrimaryKey::new::<T>(k))?;
+        metadata.crypto_duration += start.elapsed();
 
+        let start = Instant::now();
         let result = self
             .db
             .get_item()
             .table_name(&self.db.table_name)
             .key("pk", AttributeValue::S(pk))
             .key("sk", AttributeValue::S(sk))
+            .return_consumed_capacity(ReturnConsumedCapacity::Total)
             .send()
+            .instrument(info_span!("dynamodb.get_item"))
             .await
-            .map_err(|e| GetError::Aws(format!("{e:?}")))?;
+            .map_err(DynamoDbError::from)?;
+        metadata.io_duration += start.elapsed();
+        metadata.add_consumed_capacity(result.consumed_capacity);
 
         if let Some(item) = result.item {
-            Ok(Some(decrypt(&self.cipher, item).await?))
-        } else {
-            Ok(None)
+            metadata.items_read += 1;
+
+            let is_hidden =
+                self.is_expired::<T>(&item) || (!include_deleted && is_soft_deleted(&item));
+
+            if !is_hidden {
+                let primary_keys = access_log_keys(context, std::slice::from_ref(&item));
+
+                let start = Instant::now();
+                let spec = self.unseal_spec_for::<T>(context);
+                let record =
+                    decrypt(&self.cipher, Some(&cipher), spec, item, &mut metadata).await?;
+                metadata.crypto_duration += start.elapsed();
+                self.log_access::<T>(context, &primary_keys);
+                metadata.record("get", &T::type_name());
+
+                return Ok((Some(record), metadata));
+            }
         }
+
+        metadata.record("get", &T::type_name());
+
+        Ok((None, metadata))
     }
 
     /// Delete a record from the table by primary key from the default dataset.
@@ -490,7 +1034,9 @@ impl EncryptedTable<Dynamo> {
         &self,
         k: impl Into<E::PrimaryKey>,
     ) -> Result<(), DeleteError> {
-        self.delete_inner::<E>(k.into(), None).await
+        self.delete_inner::<E>(k.into(), None, E::has_soft_delete())
+            .await
+            .map(|_| ())
     }
 
     /// Delete a record from the table by primary key from a specific dataset.
@@ -499,75 +1045,508 @@ impl EncryptedTable<Dynamo> {
         k: impl Into<E::PrimaryKey>,
         dataset_id: DatasetId,
     ) -> Result<(), DeleteError> {
-        self.delete_inner::<E>(k.into(), Some(dataset_id)).await
+        self.delete_inner::<E>(k.into(), Some(dataset_id), E::has_soft_delete())
+            .await
+            .map(|_| ())
     }
 
+    /// Delete a record from the table by primary key from the default dataset and return
+    /// [`OperationMetadata`] describing the work done to delete it.
+    pub async fn delete_with_metadata<E: Searchable + Identifiable>(
+        &self,
+        k: impl Into<E::PrimaryKey>,
+    ) -> Result<OperationMetadata, DeleteError> {
+        self.delete_inner::<E>(k.into(), None, E::has_soft_delete())
+            .await
+    }
+
+    /// Delete a record from the table by primary key from a specific dataset and return
+    /// [`OperationMetadata`] describing the work done to delete it.
+    pub async fn delete_via_with_metadata<E: Searchable + Identifiable>(
+        &self,
+        k: impl Into<E::PrimaryKey>,
+        dataset_id: DatasetId,
+    ) -> Result<OperationMetadata, DeleteError> {
+        self.delete_inner::<E>(k.into(), Some(dataset_id), E::has_soft_delete())
+            .await
+    }
+
+    /// Remove a record and all of its index terms from the table using the default dataset.
+    ///
+    /// Unlike [`EncryptedTable::delete`] this removes records whose type has soft delete enabled
+    /// (see [`Identifiable::has_soft_delete`]) so they can no longer be restored.
+    pub async fn purge<E: Searchable + Identifiable>(
+        &self,
+        k: impl Into<E::PrimaryKey>,
+    ) -> Result<(), DeleteError> {
+        self.delete_inner::<E>(k.into(), None, false)
+            .await
+            .map(|_| ())
+    }
+
+    /// Remove a record and all of its index terms from the table using a specific dataset.
+    ///
+    /// See [`EncryptedTable::purge`].
+    pub async fn purge_via<E: Searchable + Identifiable>(
+        &self,
+        k: impl Into<E::PrimaryKey>,
+        dataset_id: DatasetId,
+    ) -> Result<(), DeleteError> {
+        self.delete_inner::<E>(k.into(), Some(dataset_id), false)
+            .await
+            .map(|_| ())
+    }
+
+    #[instrument(name = "delete", skip_all, fields(type_name = %E::type_name(), soft_delete))]
     async fn delete_inner<E: Searchable + Identifiable>(
         &self,
         k: E::PrimaryKey,
         dataset_id: Option<DatasetId>,
-    ) -> Result<(), DeleteError> {
-        let transact_items = self
+        soft_delete: bool,
+    ) -> Result<OperationMetadata, DeleteError> {
+        let mut metadata = OperationMetadata::default();
+
+        let start = Instant::now();
+        let patch = self
             .create_delete_patch(PreparedDelete::new::<E>(k), dataset_id)
-            .await?
-            .into_transact_write_items(&self.db.table_name)?;
-
-        // Dynamo has a limit of 100 items per transaction
-        for items in transact_items.chunks(100) {
-            self.db
-                .transact_write_items()
-                .set_transact_items(Some(items.to_vec()))
-                .send()
-                .await
-                .map_err(|e| DeleteError::Aws(format!("{e:?}")))?;
+            .await?;
+        metadata.crypto_duration += start.elapsed();
+
+        if soft_delete {
+            self.send_soft_delete(patch, &mut metadata).await?;
+            metadata.record("delete", &E::type_name());
+
+            return Ok(metadata);
+        }
+
+        // The last delete record is always the root item
+        metadata.root_items_deleted = 1;
+        metadata.term_items_deleted = patch.delete_records.len().saturating_sub(1);
+
+        let transact_items = patch.into_transact_write_items(&self.db.table_name)?;
+
+        self.send_transact_write_items(transact_items, &mut metadata)
+            .await?;
+
+        metadata.record("delete", &E::type_name());
+
+        Ok(metadata)
+    }
+
+    /// Mark the root item of a delete patch as deleted and remove the index terms of the record.
+    ///
+    /// Records which don't exist or were already deleted are left as they are.
+    ///
+    /// The root item is updated in the same transaction as the last index terms. Records with more
+    /// index terms than fit in one transaction have their remaining terms removed first, so a
+    /// failure part way through leaves the record in place with some of its terms missing (which
+    /// putting or restoring the record fixes) rather than deleted with terms left behind.
+    async fn send_soft_delete(
+        &self,
+        mut patch: DynamoRecordPatch,
+        metadata: &mut OperationMetadata,
+    ) -> Result<(), DeleteError> {
+        // The last delete record is always the root item
+        let Some(PrimaryKeyParts { pk, sk }) = patch.delete_records.pop() else {
+            return Ok(());
+        };
+
+        let term_items_deleted = patch.delete_records.len();
+
+        let mark_deleted = TransactWriteItem::builder()
+            .update(
+                Update::builder()
+                    .table_name(&self.db.table_name)
+                    .key("pk", AttributeValue::S(pk))
+                    .key("sk", AttributeValue::S(sk))
+                    .update_expression("SET #deleted_at = :deleted_at")
+                    .condition_expression(
+                        "attribute_exists(pk) AND attribute_not_exists(#deleted_at)",
+                    )
+                    .expression_attribute_names("#deleted_at", DELETED_AT_ATTRIBUTE)
+                    .expression_attribute_values(
+                        ":deleted_at",
+                        AttributeValue::N(since_epoch().as_secs().to_string()),
+                    )
+                    .build()?,
+            )
+            .build();
+
+        let transact_items = patch
+            .into_transact_write_items(&self.db.table_name)?
+            .into_iter()
+            .chain(std::iter::once(mark_deleted))
+            .collect::<Vec<_>>();
+
+        if transact_items.len() > MAX_TRANSACT_WRITE_ITEMS {
+            warn!(
+                items = transact_items.len(),
+                "Soft delete doesn't fit in a single transaction, removing index terms before marking the record as deleted"
+            );
         }
 
+        match self
+            .send_transact_write_items(transact_items, metadata)
+            .await
+        {
+            Ok(()) => {
+                metadata.root_items_written = 1;
+                metadata.term_items_deleted = term_items_deleted;
+
+                Ok(())
+            }
+            // The root item is last so a failed condition means there's nothing to delete
+            Err(DynamoDbError::TransactionCanceled { reasons, .. })
+                if reasons.last().and_then(|reason| reason.code())
+                    == Some("ConditionalCheckFailed") =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Restore a soft deleted record using the default dataset.
+    ///
+    /// The record is decrypted and put back into the table which re-creates its index terms.
+    /// Returns false if there is no soft deleted record with the key.
+    /// See [`Identifiable::has_soft_delete`].
+    pub async fn restore<T>(&self, k: impl Into<T::PrimaryKey>) -> Result<bool, RestoreError>
+    where
+        T: Searchable + Decryptable + Identifiable,
+    {
+        self.restore_inner::<T>(k.into(), None).await
+    }
+
+    /// Restore a soft deleted record using a specific dataset.
+    ///
+    /// See [`EncryptedTable::restore`].
+    pub async fn restore_via<T>(
+        &self,
+        k: impl Into<T::PrimaryKey>,
+        dataset_id: DatasetId,
+    ) -> Result<bool, RestoreError>
+    where
+        T: Searchable + Decryptable + Identifiable,
+    {
+        self.restore_inner::<T>(k.into(), Some(dataset_id)).await
+    }
+
+    #[instrument(name = "restore", skip_all, fields(type_name = %T::type_name()))]
+    async fn restore_inner<T>(
+        &self,
+        k: T::PrimaryKey,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<bool, RestoreError>
+    where
+        T: Searchable + Decryptable + Identifiable,
+    {
+        let cipher = ScopedZeroKmsCipher::init(self.cipher.clone(), dataset_id).await?;
+
+        let PrimaryKeyParts { pk, sk } =
+            encrypt_primary_key_parts(&cipher, PreparedPrimaryKey::new::<T>(k))?;
+
+        let item = self
+            .db
+            .get_item()
+            .table_name(&self.db.table_name)
+            .key("pk", AttributeValue::S(pk))
+            .key("sk", AttributeValue::S(sk))
+            .consistent_read(true)
+            .send()
+            .instrument(info_span!("dynamodb.get_item"))
+            .await
+            .map_err(DynamoDbError::from)?
+            .item;
+
+        let Some(item) = item.filter(is_soft_deleted) else {
+            return Ok(false);
+        };
+
+        // The decryption policy isn't applied as the record is put back as it is
+        let record: T = decrypt(
+            &self.cipher,
+            Some(&cipher),
+            UnsealSpec::new_for_decryptable::<T>(),
+            item,
+            &mut OperationMetadata::default(),
+        )
+        .await?;
+
+        // Putting the record replaces the root item, removing the deleted marker
+        self.put_inner(record, dataset_id).await?;
+
+        Ok(true)
+    }
+
+    /// Disable the dataset `dataset_id` in ZeroKMS so that none of the records stored in it can
+    /// be decrypted or queried until it is enabled again.
+    ///
+    /// Data keys and the index key of a dataset are only ever derived by ZeroKMS so while it is
+    /// disabled the attributes, encrypted primary keys and index terms of its records can't be
+    /// read, including copies in backups and point-in-time restores.
+    ///
+    /// Disabling a dataset is **reversible**: ZeroKMS keeps its keys and the dataset can be
+    /// enabled again with [`ZeroKMS::enable_dataset`](cipherstash_client::ZeroKMS::enable_dataset),
+    /// so this is not crypto-shredding. ZeroKMS doesn't provide a way to destroy the keys of a
+    /// dataset (or of a single record) so records can't be erased irreversibly with this crate.
+    ///
+    /// Disabling a dataset requires credentials which are allowed to manage datasets. Use
+    /// [`EncryptedTable::verify_dataset_disabled`] to check that records can no longer be
+    /// decrypted.
+    #[instrument(skip_all, fields(%dataset_id))]
+    pub async fn disable_dataset(&self, dataset_id: DatasetId) -> Result<(), DisableDatasetError> {
+        self.cipher.disable_dataset(dataset_id).await?;
+
         Ok(())
     }
 
+    /// Check that the dataset `dataset_id` is disabled and that the record with key `k` stored
+    /// in it can't be retrieved.
+    ///
+    /// Returns [`DisableDatasetError::NotDisabled`] if the dataset is still enabled or the record
+    /// can still be retrieved with it, and [`DisableDatasetError::UnknownDataset`] if ZeroKMS
+    /// doesn't know the dataset (e.g. because the id is mistyped), since the keys of an unknown
+    /// dataset are refused just like those of a disabled one. Any other error (e.g. network
+    /// errors, throttling or invalid credentials) is returned as is, since it doesn't show that
+    /// the dataset is disabled.
+    ///
+    /// ZeroKMS doesn't list disabled datasets, so the dataset is looked up by disabling it again,
+    /// which has no effect on a disabled dataset. This requires the same credentials as
+    /// [`EncryptedTable::disable_dataset`].
+    ///
+    /// A successful check only shows that the dataset is disabled *now*: it can be enabled again
+    /// and the records are not destroyed (see [`EncryptedTable::disable_dataset`]).
+    #[instrument(skip_all, fields(type_name = %T::type_name(), %dataset_id))]
+    pub async fn verify_dataset_disabled<T>(
+        &self,
+        k: impl Into<T::PrimaryKey>,
+        dataset_id: DatasetId,
+    ) -> Result<(), DisableDatasetError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        // Disabled datasets aren't listed
+        let datasets = self.cipher.list_datasets().await?;
+
+        if datasets.iter().any(|dataset| dataset.id == dataset_id) {
+            return Err(DisableDatasetError::NotDisabled { dataset_id });
+        }
+
+        // As the dataset isn't enabled, disabling it only checks that ZeroKMS knows it
+        self.cipher
+            .disable_dataset(dataset_id)
+            .await
+            .map_err(|e| DisableDatasetError::from_zerokms(dataset_id, e))?;
+
+        match self.get_via::<T>(k, dataset_id).await {
+            Ok(_) => Err(DisableDatasetError::NotDisabled { dataset_id }),
+            Err(e) if e.is_key_unavailable() => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     /// Put a record into the table using the default dataset.
     pub async fn put<T>(&self, record: T) -> Result<(), PutError>
     where
         T: Searchable + Identifiable,
     {
-        self.put_inner(record, None).await
+        self.put_inner(record, None).await.map(|_| ())
     }
 
     /// Put a record into the table using a specific dataset.
     pub async fn put_via<T>(&self, record: T, dataset_id: DatasetId) -> Result<(), PutError>
+    where
+        T: Searchable + Identifiable,
+    {
+        self.put_inner(record, Some(dataset_id)).await.map(|_| ())
+    }
+
+    /// Put a record into the table using the default dataset like [`EncryptedTable::put`] and
+    /// return a [`PutReport`] listing any indexes where terms were dropped.
+    pub async fn put_with_report<T>(&self, record: T) -> Result<PutReport, PutError>
+    where
+        T: Searchable + Identifiable,
+    {
+        self.put_inner(record, None).await.map(|(report, _)| report)
+    }
+
+    /// Put a record into the table using a specific dataset like [`EncryptedTable::put_via`] and
+    /// return a [`PutReport`] listing any indexes where terms were dropped.
+    pub async fn put_via_with_report<T>(
+        &self,
+        record: T,
+        dataset_id: DatasetId,
+    ) -> Result<PutReport, PutError>
+    where
+        T: Searchable + Identifiable,
+    {
+        self.put_inner(record, Some(dataset_id))
+            .await
+            .map(|(report, _)| report)
+    }
+
+    /// Put a record into the table using the default dataset and return [`OperationMetadata`]
+    /// describing the work done to insert it along with the [`PutReport`].
+    pub async fn put_with_metadata<T>(
+        &self,
+        record: T,
+    ) -> Result<(PutReport, OperationMetadata), PutError>
+    where
+        T: Searchable + Identifiable,
+    {
+        self.put_inner(record, None).await
+    }
+
+    /// Put a record into the table using a specific dataset and return [`OperationMetadata`]
+    /// describing the work done to insert it along with the [`PutReport`].
+    pub async fn put_via_with_metadata<T>(
+        &self,
+        record: T,
+        dataset_id: DatasetId,
+    ) -> Result<(PutReport, OperationMetadata), PutError>
     where
         T: Searchable + Identifiable,
     {
         self.put_inner(record, Some(dataset_id)).await
     }
 
-    async fn put_inner<T>(&self, record: T, dataset_id: Option<DatasetId>) -> Result<(), PutError>
+    #[instrument(name = "put", skip_all, fields(type_name = %T::type_name()))]
+    async fn put_inner<T>(
+        &self,
+        record: T,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<(PutReport, OperationMetadata), PutError>
     where
         T: Searchable + Identifiable,
     {
+        let mut metadata = OperationMetadata::default();
+
+        let start = Instant::now();
         let record = PreparedRecord::prepare_record(record)?;
+        let primary_key = record.primary_key_parts();
 
-        let transact_items = self
+        let patch = self
             .create_put_patch(
                 record,
                 dataset_id,
                 // include all records in the indexes
                 |_, _| true,
             )
-            .await?
-            .into_transact_write_items(&self.db.table_name)?;
-
-        // Dynamo has a limit of 100 items per transaction
-        for items in transact_items.chunks(100) {
-            self.db
-                .transact_write_items()
-                .set_transact_items(Some(items.to_vec()))
-                .send()
-                .await?;
+            .await?;
+        metadata.crypto_duration += start.elapsed();
+
+        // The first put record is always the root item
+        metadata.root_items_written = 1;
+        metadata.term_items_written = patch.put_records.len().saturating_sub(1);
+        metadata.term_items_deleted = patch.delete_records.len();
+        metadata.encrypted_attributes = patch.encrypted_attributes;
+
+        let report = PutReport {
+            primary_key,
+            truncated_indexes: patch.truncated_indexes.clone(),
+        };
+
+        let transact_items = patch.into_transact_write_items(&self.db.table_name)?;
+
+        self.send_transact_write_items(transact_items, &mut metadata)
+            .await?;
+
+        metadata.record("put", &T::type_name());
+
+        Ok((report, metadata))
+    }
+
+    /// Send `transact_items` to DynamoDB recording the consumed capacity and time taken in
+    /// `metadata`.
+    ///
+    /// Failed requests are retried according to the table's [`RetryPolicy`].
+    async fn send_transact_write_items(
+        &self,
+        transact_items: Vec<TransactWriteItem>,
+        metadata: &mut OperationMetadata,
+    ) -> Result<(), DynamoDbError> {
+        let start = Instant::now();
+
+        for items in transact_items.chunks(MAX_TRANSACT_WRITE_ITEMS) {
+            let mut attempt = 1;
+
+            // Every attempt of a transaction uses the same token so that DynamoDB doesn't apply
+            // it again if an attempt which appeared to fail (e.g. timed out) actually succeeded
+            let client_request_token = Uuid::new_v4().to_string();
+
+            let output = loop {
+                let result: Result<_, DynamoDbError> = self
+                    .db
+                    .transact_write_items()
+                    .set_transact_items(Some(items.to_vec()))
+                    .client_request_token(&client_request_token)
+                    .return_consumed_capacity(ReturnConsumedCapacity::Total)
+                    .customize()
+                    // Retries are controlled by the RetryPolicy so SDK retries would multiply them
+                    .config_override(
+                        aws_sdk_dynamodb::config::Builder::default()
+                            .retry_config(RetryConfig::disabled()),
+                    )
+                    .send()
+                    .instrument(info_span!(
+                        "dynamodb.transact_write_items",
+                        items = items.len(),
+                        attempt
+                    ))
+                    .await
+                    .map_err(Into::into);
+
+                match result {
+                    Ok(output) => break output,
+                    Err(e) if self.retry_policy.should_retry(&e, attempt) => {
+                        let backoff = self.retry_policy.backoff(attempt);
+
+                        if !self.sleep(backoff).await {
+                            warn!(error = %e, "No async sleep implementation to back off with, not retrying transact_write_items");
+                            return Err(e);
+                        }
+
+                        debug!(attempt, ?backoff, error = %e, "Retrying transact_write_items");
+
+                        metadata.retries += 1;
+                        attempt += 1;
+                    }
+                    Err(e) => return Err(e),
+                }
+            };
+
+            metadata
+                .consumed_capacity
+                .extend(output.consumed_capacity.unwrap_or_default());
         }
 
+        metadata.io_duration += start.elapsed();
+
         Ok(())
     }
+
+    /// Wait for `duration` using the sleep implementation of the DynamoDB client, falling back to
+    /// tokio if the client doesn't have one.
+    ///
+    /// Returns false if there is no way to wait.
+    async fn sleep(&self, duration: Duration) -> bool {
+        if let Some(sleep) = self.db.config().sleep_impl() {
+            sleep.sleep(duration).await;
+            return true;
+        }
+
+        #[cfg(feature = "tokio")]
+        {
+            tokio::time::sleep(duration).await;
+            true
+        }
+
+        #[cfg(not(feature = "tokio"))]
+        {
+            false
+        }
+    }
 }
 
 /// Take a prepared primary key and encrypt it to get the [`PrimaryKeyParts`] which can be used
@@ -591,48 +1570,195 @@ pub fn encrypt_primary_key_parts(
 
 async fn decrypt<T>(
     cipher: &ZeroKmsCipher,
+    scoped_cipher: Option<&ScopedZeroKmsCipher>,
+    spec: UnsealSpec<'_>,
     item: HashMap<String, AttributeValue>,
+    metadata: &mut OperationMetadata,
 ) -> Result<T, DecryptError>
 where
     T: Decryptable + Identifiable,
 {
-    let spec = UnsealSpec::new_for_decryptable::<T>();
+    let table_entry = SealedTableEntry::try_from(item)?;
+
+    let (mut unsealed, decrypted_attributes) =
+        SealedTableEntry::unseal_all_with_count(vec![table_entry], spec, cipher, scoped_cipher)
+            .await?;
+    metadata.decrypted_attributes += decrypted_attributes;
 
-    Ok(unseal(cipher, spec, item).await?.into_value::<T>()?)
+    let unsealed = unsealed.pop().ok_or_else(|| {
+        SealError::AssertionFailed("Expected unseal_all to return 1 result but got 0".to_string())
+    })?;
+
+    Ok(unsealed.into_value::<T>()?)
 }
 
 async fn unseal<'a>(
     cipher: &ZeroKmsCipher,
+    scoped_cipher: Option<&ScopedZeroKmsCipher>,
     spec: UnsealSpec<'a>,
     item: HashMap<String, AttributeValue>,
 ) -> Result<Unsealed, DecryptError> {
     let table_entry = SealedTableEntry::try_from(item)?;
 
-    Ok(table_entry.unseal(spec, cipher).await?)
+    Ok(table_entry.unseal(spec, cipher, scoped_cipher).await?)
 }
 
 async fn unseal_all<'a>(
     cipher: &ZeroKmsCipher,
+    scoped_cipher: Option<&ScopedZeroKmsCipher>,
     spec: UnsealSpec<'a>,
     items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+    metadata: &mut OperationMetadata,
 ) -> Result<Vec<Unsealed>, SealError> {
     let table_entries = SealedTableEntry::vec_from(items)?;
 
-    SealedTableEntry::unseal_all(table_entries, spec, cipher).await
+    let (unsealed, decrypted_attributes) =
+        SealedTableEntry::unseal_all_with_count(table_entries, spec, cipher, scoped_cipher).await?;
+    metadata.decrypted_attributes += decrypted_attributes;
+
+    Ok(unsealed)
+}
+
+async fn unseal_each<'a>(
+    cipher: &ZeroKmsCipher,
+    scoped_cipher: Option<&ScopedZeroKmsCipher>,
+    spec: UnsealSpec<'a>,
+    items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+    metadata: &mut OperationMetadata,
+) -> Result<Vec<Result<Unsealed, SealError>>, SealError> {
+    let table_entries = items
+        .into_iter()
+        .map(|item| SealedTableEntry::try_from(item).map_err(SealError::from))
+        .collect();
+
+    let (unsealed, decrypted_attributes) =
+        SealedTableEntry::unseal_each_with_count(table_entries, spec, cipher, scoped_cipher)
+            .await?;
+    metadata.decrypted_attributes += decrypted_attributes;
+
+    Ok(unsealed)
 }
 
 async fn decrypt_all<T>(
     cipher: &ZeroKmsCipher,
+    scoped_cipher: Option<&ScopedZeroKmsCipher>,
+    spec: UnsealSpec<'_>,
     items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+    metadata: &mut OperationMetadata,
 ) -> Result<Vec<T>, SealError>
 where
     T: Decryptable + Identifiable,
 {
-    let spec = UnsealSpec::new_for_decryptable::<T>();
-
-    unseal_all(cipher, spec, items)
+    unseal_all(cipher, scoped_cipher, spec, items, metadata)
         .await?
         .into_iter()
         .map(|x| x.into_value::<T>())
         .collect::<Result<_, _>>()
 }
+
+async fn decrypt_each<T>(
+    cipher: &ZeroKmsCipher,
+    scoped_cipher: Option<&ScopedZeroKmsCipher>,
+    spec: UnsealSpec<'_>,
+    items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+    metadata: &mut OperationMetadata,
+) -> Result<Vec<(PrimaryKeyParts, Result<T, DecryptError>)>, SealError>
+where
+    T: Decryptable + Identifiable,
+{
+    let (primary_keys, items): (Vec<_>, Vec<_>) = items
+        .into_iter()
+        .map(|item| (stored_primary_key(&item), item))
+        .unzip();
+
+    let results = unseal_each(cipher, scoped_cipher, spec, items, metadata)
+        .await?
+        .into_iter()
+        .map(|unsealed| Ok(unsealed?.into_value::<T>()?));
+
+    Ok(primary_keys.into_iter().zip(results).collect())
+}
+
+/// Returns the time since the Unix epoch, which is how DynamoDB represents timestamps.
+fn since_epoch() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Returns true if `item` has been soft deleted. See [`Identifiable::has_soft_delete`].
+fn is_soft_deleted(item: &HashMap<String, AttributeValue>) -> bool {
+    item.contains_key(DELETED_AT_ATTRIBUTE)
+}
+
+/// Returns true if the TTL attribute of `item` is before `now`, in seconds since the Unix epoch.
+///
+/// Like DynamoDB, items where the attribute is missing or isn't a number never expire.
+fn is_expired_at(item: &HashMap<String, AttributeValue>, ttl_attribute: &str, now: f64) -> bool {
+    item.get(ttl_attribute)
+        .and_then(|value| value.as_n().ok())
+        .and_then(|value| value.parse::<f64>().ok())
+        .is_some_and(|expires_at| expires_at < now)
+}
+
+/// Returns the stored primary keys of `items` to record in the decryption log once they have been
+/// decrypted, or nothing if there is no `context` to record them with.
+fn access_log_keys(
+    context: Option<&AccessContext>,
+    items: &[HashMap<String, AttributeValue>],
+) -> Vec<PrimaryKeyParts> {
+    match context {
+        Some(_) => items.iter().map(stored_primary_key).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Returns the `pk` and `sk` of an item as stored in the table.
+/// Missing values are returned as empty strings so that invalid items can still be reported.
+fn stored_primary_key(item: &HashMap<String, AttributeValue>) -> PrimaryKeyParts {
+    let get = |name: &str| {
+        item.get(name)
+            .and_then(|value| value.as_s().ok())
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    PrimaryKeyParts {
+        pk: get("pk"),
+        sk: get("sk"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_expired_at() {
+        let item =
+            |expires_at: AttributeValue| HashMap::from([("expires_at".to_string(), expires_at)]);
+
+        assert!(is_expired_at(
+            &item(AttributeValue::N("1700000000".into())),
+            "expires_at",
+            1_700_000_001.0
+        ));
+        assert!(!is_expired_at(
+            &item(AttributeValue::N("1700000000".into())),
+            "expires_at",
+            1_699_999_999.0
+        ));
+
+        // Attributes which aren't numbers are ignored by DynamoDB's TTL
+        assert!(!is_expired_at(
+            &item(AttributeValue::S("1700000000".into())),
+            "expires_at",
+            1_700_000_001.0
+        ));
+        assert!(!is_expired_at(
+            &HashMap::new(),
+            "expires_at",
+            1_700_000_001.0
+        ));
+    }
+}
@@ -0,0 +1,233 @@
+use aws_sdk_dynamodb::types::{AttributeValue, ReturnConsumedCapacity};
+use std::{collections::HashMap, marker::PhantomData, time::Instant};
+use tracing::{info_span, instrument, Instrument, Span};
+use uuid::Uuid;
+
+use crate::{
+    crypto::PreparedPrimaryKey,
+    traits::{Decryptable, PrimaryKey, PrimaryKeyParts},
+    Identifiable, Pk, PkSk,
+};
+
+use super::{
+    encrypt_primary_key_parts, AccessContext, Dynamo, EncryptedTable, OperationMetadata,
+    QueryError, ScopedZeroKmsCipher, DELETED_AT_ATTRIBUTE,
+};
+
+/// A condition on the sort key of the records returned by a [`PartitionQueryBuilder`].
+///
+/// Values don't include the sort key prefix of the type, it is added when the query is sent.
+enum SortKeyCondition {
+    BeginsWith(String),
+    Between(String, String),
+}
+
+/// A builder for a query which lists the records of type `T` stored under a partition key.
+///
+/// Created with [`EncryptedTable::query_partition`].
+pub struct PartitionQueryBuilder<T, B> {
+    pk: String,
+    sort_key: Option<SortKeyCondition>,
+    storage: B,
+    dataset_id: Option<Uuid>,
+    context: Option<AccessContext>,
+    __record: PhantomData<T>,
+}
+
+impl<T, B> PartitionQueryBuilder<T, B> {
+    /// Create a builder for the partition with the encoded key `pk`.
+    pub(crate) fn new(pk: String, storage: B) -> Self {
+        Self {
+            pk,
+            sort_key: None,
+            storage,
+            dataset_id: None,
+            context: None,
+            __record: Default::default(),
+        }
+    }
+
+    /// Specify the dataset to query against.
+    pub fn via(mut self, dataset_id: Uuid) -> Self {
+        self.dataset_id = Some(dataset_id);
+        self
+    }
+
+    /// Record who is decrypting the records in the partition and why in the decryption log.
+    ///
+    /// See [`AccessContext`].
+    pub fn with_context(mut self, context: AccessContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Only return records whose sort key starts with `prefix`.
+    pub fn sk_starts_with(mut self, prefix: impl Into<String>) -> Self {
+        self.sort_key = Some(SortKeyCondition::BeginsWith(prefix.into()));
+        self
+    }
+
+    /// Only return records whose sort key is between `from` and `to` (inclusive).
+    pub fn sk_between(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.sort_key = Some(SortKeyCondition::Between(from.into(), to.into()));
+        self
+    }
+}
+
+impl<T> PartitionQueryBuilder<T, &EncryptedTable<Dynamo>>
+where
+    T: Decryptable + Identifiable,
+{
+    pub async fn send(self) -> Result<Vec<T>, QueryError> {
+        self.send_with_metadata().await.map(|(records, _)| records)
+    }
+
+    /// Run the query like [`PartitionQueryBuilder::send`] and also return [`OperationMetadata`]
+    /// describing the work done to retrieve and decrypt the records.
+    #[instrument(name = "query_partition", skip_all, fields(type_name = %T::type_name(), items))]
+    pub async fn send_with_metadata(self) -> Result<(Vec<T>, OperationMetadata), QueryError> {
+        let mut metadata = OperationMetadata::default();
+        let table = self.storage;
+
+        let start = Instant::now();
+        let scoped_cipher =
+            ScopedZeroKmsCipher::init(table.cipher.clone(), self.dataset_id).await?;
+        let (key_condition, values) = Self::key_condition(self.pk, self.sort_key, &scoped_cipher)?;
+        metadata.crypto_duration += start.elapsed();
+
+        let mut items = vec![];
+        let mut exclusive_start_key = None;
+
+        let start = Instant::now();
+
+        loop {
+            let output = table
+                .db
+                .query()
+                .table_name(&table.db.table_name)
+                .key_condition_expression(&key_condition)
+                // Index terms share the partition of their record so skip them, along with
+                // records which have been soft deleted
+                .filter_expression(
+                    "attribute_not_exists(term) AND attribute_not_exists(#deleted_at)",
+                )
+                .expression_attribute_names("#deleted_at", DELETED_AT_ATTRIBUTE)
+                .set_expression_attribute_values(Some(values.clone()))
+                .set_exclusive_start_key(exclusive_start_key)
+                .return_consumed_capacity(ReturnConsumedCapacity::Total)
+                .send()
+                .instrument(info_span!("dynamodb.query"))
+                .await?;
+            metadata.add_consumed_capacity(output.consumed_capacity);
+
+            items.extend(output.items.unwrap_or_default());
+
+            match output.last_evaluated_key {
+                Some(key) if !key.is_empty() => exclusive_start_key = Some(key),
+                _ => break,
+            }
+        }
+
+        metadata.io_duration += start.elapsed();
+        metadata.items_read = items.len();
+        Span::current().record("items", items.len());
+
+        items.retain(|item| !table.is_expired::<T>(item));
+        let primary_keys = super::access_log_keys(self.context.as_ref(), &items);
+
+        let start = Instant::now();
+        let spec = table.unseal_spec_for::<T>(self.context.as_ref());
+        let records = super::decrypt_all(
+            &table.cipher,
+            Some(&scoped_cipher),
+            spec,
+            items,
+            &mut metadata,
+        )
+        .await?;
+        metadata.crypto_duration += start.elapsed();
+        table.log_access::<T>(self.context.as_ref(), &primary_keys);
+        metadata.record("query_partition", &T::type_name());
+
+        Ok((records, metadata))
+    }
+
+    /// Returns the key condition expression and its values for the query.
+    ///
+    /// Records with a plaintext sort key are selected by their sort key prefix. Records without a
+    /// sort key of their own have exactly one item per partition so it is selected by its full key.
+    fn key_condition(
+        pk: String,
+        sort_key: Option<SortKeyCondition>,
+        scoped_cipher: &ScopedZeroKmsCipher,
+    ) -> Result<(String, HashMap<String, AttributeValue>), QueryError> {
+        let type_name = T::type_name();
+
+        if !<T::PrimaryKey as PrimaryKey>::HAS_SORT_KEY {
+            if sort_key.is_some() {
+                return Err(QueryError::InvalidQuery(format!(
+                    "'{type_name}' doesn't have a sort key so it can't be queried by sort key"
+                )));
+            }
+
+            let PrimaryKeyParts { pk, sk } = encrypt_primary_key_parts(
+                scoped_cipher,
+                PreparedPrimaryKey::new_from_parts::<T>(
+                    Pk(pk).into_parts(&type_name, T::sort_key_prefix().as_deref()),
+                ),
+            )?;
+
+            return Ok((
+                "pk = :pk AND sk = :sk".to_string(),
+                HashMap::from([
+                    (":pk".to_string(), AttributeValue::S(pk)),
+                    (":sk".to_string(), AttributeValue::S(sk)),
+                ]),
+            ));
+        }
+
+        if T::is_sk_encrypted() {
+            return Err(QueryError::InvalidQuery(format!(
+                "the sort key of '{type_name}' is encrypted so it can't be queried by partition. Make every field of the sort key #[cipherstash(plaintext)]"
+            )));
+        }
+
+        // Sort keys are stored as `<prefix>#<sk>` so an empty sort key gives the prefix to match
+        let PrimaryKeyParts { pk, sk: prefix } = encrypt_primary_key_parts(
+            scoped_cipher,
+            PreparedPrimaryKey::new_from_parts::<T>(
+                PkSk(pk, String::new()).into_parts(&type_name, T::sort_key_prefix().as_deref()),
+            ),
+        )?;
+
+        let mut values = HashMap::from([(":pk".to_string(), AttributeValue::S(pk))]);
+
+        let key_condition = match sort_key {
+            Some(SortKeyCondition::BeginsWith(sk)) => {
+                values.insert(
+                    ":sk".to_string(),
+                    AttributeValue::S(format!("{prefix}{sk}")),
+                );
+                "pk = :pk AND begins_with(sk, :sk)"
+            }
+            Some(SortKeyCondition::Between(from, to)) => {
+                values.insert(
+                    ":from".to_string(),
+                    AttributeValue::S(format!("{prefix}{from}")),
+                );
+                values.insert(
+                    ":to".to_string(),
+                    AttributeValue::S(format!("{prefix}{to}")),
+                );
+                "pk = :pk AND sk BETWEEN :from AND :to"
+            }
+            None if prefix.is_empty() => "pk = :pk",
+            None => {
+                values.insert(":sk".to_string(), AttributeValue::S(prefix));
+                "pk = :pk AND begins_with(sk, :sk)"
+            }
+        };
+
+        Ok((key_condition.to_string(), values))
+    }
+}
@@ -1,19 +1,30 @@
-use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
+use aws_sdk_dynamodb::{
+    primitives::Blob,
+    types::{AttributeValue, ReturnConsumedCapacity},
+};
 use cipherstash_client::encryption::{
     compound_indexer::{ComposableIndex, ComposablePlaintext},
     Plaintext,
 };
 use itertools::Itertools;
-use std::{borrow::Cow, collections::HashMap, marker::PhantomData};
+use std::{borrow::Cow, collections::HashMap, marker::PhantomData, time::Instant};
+use tracing::{info_span, instrument, Instrument, Span};
 use uuid::Uuid;
 
 use crate::{
-    traits::{Decryptable, Searchable},
+    crypto::sharded_term,
+    traits::{Decryptable, PrimaryKeyParts, Searchable},
     Identifiable, IndexType, SingleIndex,
 };
 use cipherstash_client::encryption::IndexTerm;
 
-use super::{Dynamo, EncryptedTable, QueryError, ScopedZeroKmsCipher, SealError};
+use super::{
+    AccessContext, DecryptError, Dynamo, EncryptedTable, OperationMetadata, QueryError,
+    ScopedZeroKmsCipher, SealError,
+};
+
+/// A result for each record returned by a query along with its stored primary key.
+type RecordResults<T> = Vec<(PrimaryKeyParts, Result<T, DecryptError>)>;
 
 /// A builder for a query operation which returns records of type `S`.
 /// `B` is the storage backend used to store the data.
@@ -21,26 +32,90 @@ pub struct QueryBuilder<S, B = ()> {
     parts: Vec<(String, SingleIndex, Plaintext)>,
     storage: B,
     dataset_id: Option<Uuid>,
+    context: Option<AccessContext>,
     __searchable: PhantomData<S>,
 }
 
+/// A query built with the methods generated by the `Searchable` derive, e.g.
+/// `User::query().email_eq("dan@coderdan.co")`.
+///
+/// The derive only implements this trait for queries which match one of the declared indexes so
+/// querying a field that isn't indexed, or with an operator its index doesn't support, fails to
+/// compile. Run the query with [`EncryptedTable::typed_query`].
+pub trait TypedQuery: Sized {
+    /// The type of the records returned by the query.
+    type Searchable: Searchable;
+
+    /// Returns the field names, operators and plaintexts of the query.
+    #[doc(hidden)]
+    fn into_parts(self) -> Vec<(String, SingleIndex, Plaintext)>;
+
+    fn build(self) -> Result<PreparedQuery, QueryError> {
+        PreparedQueryBuilder::new::<Self::Searchable>().build(self.into_parts())
+    }
+}
+
 pub struct PreparedQuery {
     index_name: String,
     type_name: String,
     composed_index: Box<dyn ComposableIndex + Send>,
     plaintext: ComposablePlaintext,
+    term_shards: u8,
 }
 
 impl PreparedQuery {
+    /// Encrypt the query into the term used to look up the `TermIndex`.
+    ///
+    /// Sharded indexes store their terms across multiple partitions so there is no single term
+    /// to query. Use [`PreparedQuery::encrypt_all`] for those.
     pub async fn encrypt(
         self,
         scoped_cipher: &ScopedZeroKmsCipher,
+    ) -> Result<AttributeValue, QueryError> {
+        if self.term_shards > 1 {
+            return Err(QueryError::InvalidQuery(format!(
+                "Index '{}' is sharded across {} terms. Use `encrypt_all` instead.",
+                self.index_name, self.term_shards
+            )));
+        }
+
+        self.encrypt_unsharded(scoped_cipher).await
+    }
+
+    /// Encrypt the query into the terms used to look up the `TermIndex`, one for each shard.
+    ///
+    /// Unsharded indexes will return a single term.
+    pub async fn encrypt_all(
+        self,
+        scoped_cipher: &ScopedZeroKmsCipher,
+    ) -> Result<Vec<AttributeValue>, QueryError> {
+        let term_shards = self.term_shards;
+        let term = self.encrypt_unsharded(scoped_cipher).await?;
+
+        if term_shards <= 1 {
+            return Ok(vec![term]);
+        }
+
+        let term = term
+            .as_b()
+            .map_err(|_| QueryError::Other("Expected term to be a binary value".to_string()))?
+            .as_ref();
+
+        Ok((0..term_shards)
+            .map(|shard| AttributeValue::B(Blob::new(sharded_term(term, shard))))
+            .collect())
+    }
+
+    async fn encrypt_unsharded(
+        self,
+        scoped_cipher: &ScopedZeroKmsCipher,
     ) -> Result<AttributeValue, QueryError> {
         let PreparedQuery {
             index_name,
             composed_index,
             plaintext,
             type_name,
+            ..
         } = self;
 
         let info = format!("{}#{}", type_name, index_name);
@@ -65,21 +140,66 @@ impl PreparedQuery {
         table: &EncryptedTable<Dynamo>,
         scoped_cipher: &ScopedZeroKmsCipher,
     ) -> Result<Vec<HashMap<String, AttributeValue>>, QueryError> {
-        let term = self.encrypt(scoped_cipher).await?;
-
-        let query = table
-            .db
-            .query()
-            .table_name(&table.db.table_name)
-            .index_name("TermIndex")
-            .key_condition_expression("term = :term")
-            .expression_attribute_values(":term", term);
-
-        query
-            .send()
-            .await?
-            .items
-            .ok_or_else(|| QueryError::Other("Expected items entry on aws response".into()))
+        self.send_with_metadata(table, scoped_cipher)
+            .await
+            .map(|(items, _)| items)
+    }
+
+    /// Send the query like [`PreparedQuery::send`] and also return [`OperationMetadata`]
+    /// describing the work done to encrypt and run the query.
+    #[instrument(
+        skip_all,
+        fields(
+            type_name = %self.type_name,
+            index_name = %self.index_name,
+            term_shards = self.term_shards,
+            items,
+        )
+    )]
+    pub async fn send_with_metadata(
+        self,
+        table: &EncryptedTable<Dynamo>,
+        scoped_cipher: &ScopedZeroKmsCipher,
+    ) -> Result<(Vec<HashMap<String, AttributeValue>>, OperationMetadata), QueryError> {
+        let mut metadata = OperationMetadata::default();
+
+        let start = Instant::now();
+        let terms = self.encrypt_all(scoped_cipher).await?;
+        metadata.crypto_duration += start.elapsed();
+
+        let mut items = vec![];
+
+        let start = Instant::now();
+
+        // Each shard for a sharded index is stored under a different term so fan out and merge
+        for term in terms {
+            let query = table
+                .db
+                .query()
+                .table_name(&table.db.table_name)
+                .index_name("TermIndex")
+                .key_condition_expression("term = :term")
+                .expression_attribute_values(":term", term)
+                .return_consumed_capacity(ReturnConsumedCapacity::Total);
+
+            let output = query
+                .send()
+                .instrument(info_span!("dynamodb.query"))
+                .await?;
+            metadata.add_consumed_capacity(output.consumed_capacity);
+
+            items.extend(
+                output.items.ok_or_else(|| {
+                    QueryError::Other("Expected items entry on aws response".into())
+                })?,
+            );
+        }
+
+        metadata.io_duration += start.elapsed();
+        metadata.items_read = items.len();
+        Span::current().record("items", items.len());
+
+        Ok((items, metadata))
     }
 }
 
@@ -95,6 +215,7 @@ impl<S> Default for QueryBuilder<S> {
             parts: vec![],
             storage: Default::default(),
             dataset_id: None,
+            context: None,
             __searchable: Default::default(),
         }
     }
@@ -106,16 +227,36 @@ impl<S, B> QueryBuilder<S, B> {
             parts: vec![],
             storage: backend,
             dataset_id: None,
+            context: None,
             __searchable: Default::default(),
         }
     }
 
+    /// Create a builder for the `query` generated by the `Searchable` derive.
+    pub fn from_typed<Q>(query: Q, backend: B) -> Self
+    where
+        Q: TypedQuery<Searchable = S>,
+    {
+        Self {
+            parts: query.into_parts(),
+            ..Self::with_backend(backend)
+        }
+    }
+
     /// Specify the dataset to query against.
     pub fn via(mut self, dataset_id: Uuid) -> Self {
         self.dataset_id = Some(dataset_id);
         self
     }
 
+    /// Record who is decrypting the records returned by the query and why in the decryption log.
+    ///
+    /// See [`AccessContext`].
+    pub fn with_context(mut self, context: AccessContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+
     pub fn eq(mut self, name: impl Into<String>, plaintext: impl Into<Plaintext>) -> Self {
         self.parts
             .push((name.into(), SingleIndex::Exact, plaintext.into()));
@@ -147,20 +288,103 @@ where
     ///
     /// While a client can decrypt records from any dataset it has access to,
     /// queries are always scoped to a single dataset.
-    pub(crate) async fn load<T>(self) -> Result<Vec<T>, QueryError>
+    #[instrument(name = "query", skip_all, fields(type_name = %S::type_name()))]
+    pub(crate) async fn load<T>(mut self) -> Result<(Vec<T>, OperationMetadata), QueryError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        let storage = self.storage;
+        let context = self.context.take();
+        let (items, scoped_cipher, mut metadata) = self.load_items().await?;
+        let primary_keys = super::access_log_keys(context.as_ref(), &items);
+
+        let start = Instant::now();
+        let spec = storage
+            .unseal_spec_for::<T>(context.as_ref())
+            .allowing_term_entries();
+        let results = super::decrypt_all(
+            &storage.cipher,
+            Some(&scoped_cipher),
+            spec,
+            items,
+            &mut metadata,
+        )
+        .await?;
+        metadata.crypto_duration += start.elapsed();
+        storage.log_access::<T>(context.as_ref(), &primary_keys);
+        metadata.record("query", &S::type_name());
+
+        Ok((results, metadata))
+    }
+
+    /// Load all records of type `T` matching the query like [`QueryBuilder::load`] but return a
+    /// result for each record.
+    #[instrument(name = "query", skip_all, fields(type_name = %S::type_name()))]
+    pub(crate) async fn load_each<T>(
+        mut self,
+    ) -> Result<(RecordResults<T>, OperationMetadata), QueryError>
     where
         T: Decryptable + Identifiable,
     {
+        let storage = self.storage;
+        let context = self.context.take();
+        let (items, scoped_cipher, mut metadata) = self.load_items().await?;
+
+        let start = Instant::now();
+        let spec = storage
+            .unseal_spec_for::<T>(context.as_ref())
+            .allowing_term_entries();
+        let results = super::decrypt_each(
+            &storage.cipher,
+            Some(&scoped_cipher),
+            spec,
+            items,
+            &mut metadata,
+        )
+        .await?;
+        metadata.crypto_duration += start.elapsed();
+
+        // Only the records which were decrypted are recorded
+        if context.is_some() {
+            let primary_keys = results
+                .iter()
+                .filter(|(_, result)| result.is_ok())
+                .map(|(primary_key, _)| primary_key.clone())
+                .collect::<Vec<_>>();
+
+            storage.log_access::<T>(context.as_ref(), &primary_keys);
+        }
+        metadata.record("query", &S::type_name());
+
+        Ok((results, metadata))
+    }
+
+    /// Encrypt the query and return the raw items matching it along with the cipher scoped to the
+    /// dataset that was queried.
+    async fn load_items(
+        self,
+    ) -> Result<
+        (
+            Vec<HashMap<String, AttributeValue>>,
+            ScopedZeroKmsCipher,
+            OperationMetadata,
+        ),
+        QueryError,
+    > {
+        let start = Instant::now();
         let scoped_cipher =
             ScopedZeroKmsCipher::init(self.storage.cipher.clone(), self.dataset_id).await?;
+        let init_duration = start.elapsed();
 
         let storage = self.storage;
         let query = self.build()?;
 
-        let items = query.send(storage, &scoped_cipher).await?;
-        let results = super::decrypt_all(&storage.cipher, items).await?;
+        let (mut items, mut metadata) = query.send_with_metadata(storage, &scoped_cipher).await?;
+        metadata.crypto_duration += init_duration;
+
+        items.retain(|item| !storage.is_expired::<S>(item));
 
-        Ok(results)
+        Ok((items, scoped_cipher, metadata))
     }
 }
 
@@ -169,13 +393,30 @@ where
     S: Searchable + Decryptable + Identifiable,
 {
     pub async fn send(self) -> Result<Vec<S>, QueryError> {
+        self.load::<S>().await.map(|(records, _)| records)
+    }
+
+    /// Run the query like [`QueryBuilder::send`] and also return [`OperationMetadata`]
+    /// describing the work done to retrieve and decrypt the records.
+    pub async fn send_with_metadata(self) -> Result<(Vec<S>, OperationMetadata), QueryError> {
         self.load::<S>().await
     }
+
+    /// Run the query like [`QueryBuilder::send`] but return a result for each record along with
+    /// its stored primary key, so that a single invalid record doesn't fail the whole query.
+    ///
+    /// See [`EncryptedTable::decrypt_each`].
+    pub async fn send_each(
+        self,
+    ) -> Result<Vec<(PrimaryKeyParts, Result<S, DecryptError>)>, QueryError> {
+        self.load_each::<S>().await.map(|(records, _)| records)
+    }
 }
 
 pub struct PreparedQueryBuilder {
     pub type_name: Cow<'static, str>,
     pub index_by_name: fn(&str, IndexType) -> Option<Box<dyn ComposableIndex + Send>>,
+    pub term_shards: fn(&str, IndexType) -> u8,
 }
 
 impl PreparedQueryBuilder {
@@ -183,6 +424,7 @@ impl PreparedQueryBuilder {
         Self {
             type_name: S::type_name(),
             index_by_name: S::index_by_name,
+            term_shards: S::term_shards,
         }
     }
 
@@ -238,11 +480,14 @@ impl PreparedQueryBuilder {
                         .expect("Failed to compose");
                 }
 
+                let term_shards = (self.term_shards)(index_name.as_str(), index_type);
+
                 return Ok(PreparedQuery {
                     index_name,
                     type_name: self.type_name.to_string(),
                     plaintext,
                     composed_index,
+                    term_shards,
                 });
             }
         }
@@ -0,0 +1,173 @@
+use crate::errors::{DynamoDbError, RETRYABLE_CANCELLATION_REASONS};
+use std::{
+    borrow::Cow,
+    collections::hash_map::RandomState,
+    hash::BuildHasher,
+    time::{Duration, SystemTime},
+};
+
+/// Controls how `transact_write_items` requests made by [`crate::EncryptedTable`] are retried
+/// when they fail because of a transient conflict or throttling.
+///
+/// Records are only sealed once so retrying a put or delete never re-encrypts the record.
+/// Every attempt of a transaction is sent with the same client request token so a transaction
+/// which was applied but appeared to fail (e.g. because it timed out) isn't applied twice.
+///
+/// The retry configuration of the DynamoDB client is disabled for these requests so that each
+/// attempt is only sent once. Backoffs use the sleep implementation of the client, or tokio if
+/// the client doesn't have one and the `tokio` feature is enabled. Otherwise requests aren't
+/// retried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of times a request is sent, including the first attempt.
+    pub max_attempts: u32,
+
+    /// The backoff before the first retry. This doubles for every subsequent retry.
+    pub initial_backoff: Duration,
+
+    /// The upper limit on the backoff between retries.
+    pub max_backoff: Duration,
+
+    /// The `TransactionCanceledException` cancellation reason codes which can be retried.
+    ///
+    /// A cancelled transaction is only retried when every item was either not the cause of the
+    /// cancellation (code `None`) or was cancelled for one of these reasons.
+    pub retryable_reasons: Vec<Cow<'static, str>>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+            retryable_reasons: RETRYABLE_CANCELLATION_REASONS
+                .iter()
+                .map(|reason| Cow::Borrowed(*reason))
+                .collect(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy which never retries.
+    pub fn none() -> Self {
+        Self::default().with_max_attempts(1)
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn with_retryable_reasons(
+        mut self,
+        reasons: impl IntoIterator<Item = impl Into<Cow<'static, str>>>,
+    ) -> Self {
+        self.retryable_reasons = reasons.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Returns true if a request which failed with `error` on `attempt` (starting at 1) should be
+    /// sent again.
+    pub(crate) fn should_retry(&self, error: &DynamoDbError, attempt: u32) -> bool {
+        attempt < self.max_attempts && error.is_retryable_with_reasons(&self.retryable_reasons)
+    }
+
+    /// Returns the time to wait before sending `attempt` + 1.
+    ///
+    /// Uses "full jitter" so that concurrent writers which conflicted with each other don't
+    /// retry in lockstep.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+
+        backoff.mul_f64(jitter())
+    }
+}
+
+/// Returns a random number in the range `[0, 1]`.
+fn jitter() -> f64 {
+    // RandomState is randomly seeded so this avoids pulling in a dependency just for jitter
+    let random = RandomState::new().hash_one(SystemTime::now());
+
+    random as f64 / u64::MAX as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_dynamodb::{
+        config::http::HttpResponse,
+        error::SdkError,
+        operation::transact_write_items::TransactWriteItemsError,
+        types::{error::TransactionCanceledException, CancellationReason},
+    };
+
+    fn cancelled(codes: &[&str]) -> DynamoDbError {
+        let error = TransactionCanceledException::builder()
+            .set_cancellation_reasons(Some(
+                codes
+                    .iter()
+                    .map(|code| CancellationReason::builder().code(*code).build())
+                    .collect(),
+            ))
+            .build();
+
+        SdkError::service_error(
+            TransactWriteItemsError::TransactionCanceledException(error),
+            HttpResponse::new(400u16.try_into().unwrap(), "".into()),
+        )
+        .into()
+    }
+
+    #[test]
+    fn test_retries_conflicts() {
+        let policy = RetryPolicy::default();
+
+        assert!(policy.should_retry(&cancelled(&["None", "TransactionConflict"]), 1));
+        assert!(policy.should_retry(&cancelled(&["ThrottlingError"]), 2));
+        assert!(!policy.should_retry(&cancelled(&["TransactionConflict"]), 3));
+    }
+
+    #[test]
+    fn test_does_not_retry_other_reasons() {
+        let policy = RetryPolicy::default();
+
+        assert!(!policy.should_retry(&cancelled(&["None"]), 1));
+        assert!(!policy.should_retry(&cancelled(&["ValidationError"]), 1));
+        assert!(!policy.should_retry(
+            &cancelled(&["TransactionConflict", "ConditionalCheckFailed"]),
+            1
+        ));
+        assert!(!RetryPolicy::none().should_retry(&cancelled(&["TransactionConflict"]), 1));
+    }
+
+    #[test]
+    fn test_custom_retryable_reasons() {
+        let policy = RetryPolicy::default().with_retryable_reasons(["ValidationError"]);
+
+        assert!(policy.should_retry(&cancelled(&["ValidationError"]), 1));
+        assert!(!policy.should_retry(&cancelled(&["TransactionConflict"]), 1));
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy::default()
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(300));
+
+        for attempt in 1..10 {
+            assert!(policy.backoff(attempt) <= Duration::from_millis(300));
+        }
+
+        assert!(policy.backoff(1) <= Duration::from_millis(100));
+    }
+}
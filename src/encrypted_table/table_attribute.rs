@@ -1,10 +1,14 @@
 use super::{ReadConversionError, SealError};
+use crate::traits::{KeyComponent, PrimaryKeyParts};
 use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, NaiveDate, Utc};
 use cipherstash_client::zerokms::EncryptedRecord;
 use std::{
     collections::{BTreeMap, HashMap},
     str::FromStr,
 };
+use uuid::Uuid;
 
 /// Trait for converting `TableAttribute` to `Self`
 pub trait TryFromTableAttr: Sized {
@@ -29,29 +33,33 @@ pub enum TableAttribute {
 }
 
 impl TableAttribute {
-    // TODO: Unit test this
     /// Try to convert the `TableAttribute` to an `EncryptedRecord` if it is a `Bytes` variant.
     /// The descriptor of the record is checked against the `descriptor` argument
     /// (which will be verified to be the correct descriptor for the record via AAD).
     ///
-    /// If the descriptor does not match, an error is returned and this may indicate that the record
-    /// has been tampered with (e.g. via a confused deputy attack).
+    /// If the descriptor does not match, [SealError::TamperDetected] is returned for the record
+    /// identified by `primary_key` as this indicates that the record has been tampered with
+    /// (e.g. via a confused deputy attack).
     pub(crate) fn as_encrypted_record(
         &self,
         descriptor: &str,
+        primary_key: &PrimaryKeyParts,
     ) -> Result<EncryptedRecord, SealError> {
         if let TableAttribute::Bytes(s) = self {
             EncryptedRecord::from_mp_bytes(&s[..])
-                .map_err(|_| SealError::AssertionFailed("Could not parse EncryptedRecord".to_string()))
+                .map_err(|_| {
+                    SealError::AssertionFailed("Could not parse EncryptedRecord".to_string())
+                })
                 .and_then(|record| {
                     if record.descriptor == descriptor {
                         Ok(record)
                     } else {
-                        Err(SealError::AssertionFailed(format!(
-                            "Expected descriptor {}, got {} - WARNING: record may have been tampered with",
-                            descriptor,
-                            record.descriptor
-                        )))
+                        Err(SealError::TamperDetected {
+                            pk: primary_key.pk.clone(),
+                            sk: primary_key.sk.clone(),
+                            expected: descriptor.to_string(),
+                            actual: record.descriptor,
+                        })
                     }
                 })
         } else {
@@ -188,6 +196,36 @@ impl From<&str> for TableAttribute {
     }
 }
 
+/// Non-string key types are stored as strings using their [`KeyComponent`] encoding so that
+/// plaintext key fields match the primary key.
+macro_rules! impl_try_from_table_attr_for_key_component {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for TableAttribute {
+                fn from(value: $ty) -> Self {
+                    TableAttribute::String(value.to_key())
+                }
+            }
+
+            impl TryFromTableAttr for $ty {
+                fn try_from_table_attr(value: TableAttribute) -> Result<Self, ReadConversionError> {
+                    let TableAttribute::String(value) = value else {
+                        return Err(ReadConversionError::ConversionFailed(stringify!($ty).to_string()));
+                    };
+
+                    <$ty>::from_key(&value)
+                        .map_err(|_| ReadConversionError::ConversionFailed(stringify!($ty).to_string()))
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_table_attr_for_key_component!(Uuid);
+
+#[cfg(feature = "chrono")]
+impl_try_from_table_attr_for_key_component!(DateTime<Utc>, NaiveDate);
+
 impl<T> TryFromTableAttr for Option<T>
 where
     T: TryFromTableAttr,
@@ -658,4 +696,40 @@ mod test {
 
         assert_eq!(original, map);
     }
+
+    fn encrypted_record_attr(descriptor: &str) -> TableAttribute {
+        let record = EncryptedRecord {
+            iv: Default::default(),
+            ciphertext: vec![1, 2, 3],
+            tag: vec![4, 5, 6],
+            descriptor: descriptor.to_string(),
+            dataset_id: None,
+        };
+
+        TableAttribute::Bytes(record.to_mp_bytes().unwrap())
+    }
+
+    #[test]
+    fn test_as_encrypted_record() {
+        let primary_key = PrimaryKeyParts {
+            pk: "pk".to_string(),
+            sk: "sk".to_string(),
+        };
+
+        let record = encrypted_record_attr("user/email")
+            .as_encrypted_record("user/email", &primary_key)
+            .unwrap();
+
+        assert_eq!(record.descriptor, "user/email");
+
+        let err = encrypted_record_attr("user/name")
+            .as_encrypted_record("user/email", &primary_key)
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            SealError::TamperDetected { pk, sk, expected, actual }
+            if pk == "pk" && sk == "sk" && expected == "user/email" && actual == "user/name"
+        ));
+    }
 }
@@ -64,6 +64,11 @@ impl TableAttributes {
         (protected.into(), unprotected.into())
     }
 
+    /// Iterates over references to each pair of [AttributeName] and [TableAttribute].
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&AttributeName, &TableAttribute)> {
+        self.0.iter()
+    }
+
     // TODO: Doc, test
     pub(crate) fn get(&self, name: impl Into<AttributeName>) -> Option<&TableAttribute> {
         let name: AttributeName = name.into();
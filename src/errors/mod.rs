@@ -1,13 +1,18 @@
-use aws_sdk_dynamodb::{error::SdkError, operation};
+use aws_sdk_dynamodb::{
+    error::{ProvideErrorMetadata, SdkError},
+    operation::{self, transact_write_items::TransactWriteItemsError},
+    types::CancellationReason,
+};
 use cipherstash_client::zerokms;
 use miette::Diagnostic;
 use thiserror::Error;
+use zerokms_protocol::{ViturRequestError, ViturRequestErrorKind};
 
-use crate::traits::PrimaryKeyError;
 pub use crate::{
     crypto::{CryptoError, SealError},
     traits::{ReadConversionError, WriteConversionError},
 };
+use crate::{encrypted_table::DatasetId, traits::PrimaryKeyError};
 
 pub use cipherstash_client::{config::errors::ConfigError, encryption::EncryptionError};
 
@@ -31,12 +36,18 @@ pub enum PutError {
     Encryption(#[from] EncryptionError),
 
     #[error(transparent)]
-    DynamoError(#[from] SdkError<operation::transact_write_items::TransactWriteItemsError>),
+    DynamoError(#[from] DynamoDbError),
 
     #[error("ZeroKMS Error: {0}")]
     ZeroKMS(#[from] zerokms::Error),
 }
 
+impl From<SdkError<TransactWriteItemsError>> for PutError {
+    fn from(error: SdkError<TransactWriteItemsError>) -> Self {
+        Self::DynamoError(error.into())
+    }
+}
+
 /// Error returned by `EncryptedTable::get` when retrieving and decrypting records from DynamoDB
 #[derive(Error, Debug, Diagnostic)]
 pub enum GetError {
@@ -46,13 +57,36 @@ pub enum GetError {
     DecryptError(#[from] DecryptError),
     #[error(transparent)]
     Encryption(#[from] EncryptionError),
-    #[error("AwsError: {0}")]
-    Aws(String),
+    #[error(transparent)]
+    DynamoError(#[from] DynamoDbError),
 
     #[error("ZeroKMS Error: {0}")]
     ZeroKMS(#[from] zerokms::Error),
 }
 
+impl GetError {
+    /// Returns true if the record couldn't be retrieved because ZeroKMS refused to provide the
+    /// keys of its dataset, e.g. because the dataset is disabled.
+    ///
+    /// Transient errors (such as network errors or throttling) and invalid credentials are not
+    /// treated as the keys being unavailable.
+    pub(crate) fn is_key_unavailable(&self) -> bool {
+        is_key_unavailable(self)
+    }
+}
+
+fn is_key_unavailable(error: &(dyn std::error::Error + 'static)) -> bool {
+    std::iter::successors(Some(error), |error| error.source()).any(|error| {
+        matches!(
+            error.downcast_ref::<ViturRequestError>(),
+            Some(ViturRequestError {
+                kind: ViturRequestErrorKind::NotFound,
+                ..
+            })
+        )
+    })
+}
+
 /// Error returned by `EncryptedTable::delete` when indexing and deleting records in DynamoDB
 #[derive(Error, Debug, Diagnostic)]
 pub enum DeleteError {
@@ -62,13 +96,55 @@ pub enum DeleteError {
     Encryption(#[from] EncryptionError),
     #[error("AwsBuildError: {0}")]
     AwsBuildError(#[from] BuildError),
-    #[error("AwsError: {0}")]
-    Aws(String),
+    #[error(transparent)]
+    DynamoError(#[from] DynamoDbError),
 
     #[error("ZeroKMS Error: {0}")]
     ZeroKMS(#[from] zerokms::Error),
 }
 
+/// Error returned by `EncryptedTable::restore` when retrieving, decrypting and re-indexing soft deleted records
+#[derive(Error, Debug, Diagnostic)]
+pub enum RestoreError {
+    #[error(transparent)]
+    PrimaryKeyError(#[from] PrimaryKeyError),
+    #[error(transparent)]
+    DecryptError(#[from] DecryptError),
+    #[error(transparent)]
+    PutError(#[from] PutError),
+    #[error(transparent)]
+    DynamoError(#[from] DynamoDbError),
+
+    #[error("ZeroKMS Error: {0}")]
+    ZeroKMS(#[from] zerokms::Error),
+}
+
+/// Error returned by `EncryptedTable::disable_dataset` and `EncryptedTable::verify_dataset_disabled`
+#[derive(Error, Debug, Diagnostic)]
+pub enum DisableDatasetError {
+    #[error("Dataset {dataset_id} has not been disabled: its records can still be decrypted")]
+    NotDisabled { dataset_id: DatasetId },
+    #[error("Dataset {dataset_id} does not exist in ZeroKMS")]
+    UnknownDataset { dataset_id: DatasetId },
+    #[error(transparent)]
+    GetError(#[from] GetError),
+
+    #[error("ZeroKMS Error: {0}")]
+    ZeroKMS(#[from] zerokms::Error),
+}
+
+impl DisableDatasetError {
+    /// Convert an error returned by ZeroKMS for a request about the dataset `dataset_id`,
+    /// returning [`DisableDatasetError::UnknownDataset`] if ZeroKMS doesn't know the dataset.
+    pub(crate) fn from_zerokms(dataset_id: DatasetId, error: zerokms::Error) -> Self {
+        if is_key_unavailable(&error) {
+            Self::UnknownDataset { dataset_id }
+        } else {
+            Self::ZeroKMS(error)
+        }
+    }
+}
+
 /// Error returned by `EncryptedTable::query` when indexing, retrieving and decrypting records from DynamoDB
 #[derive(Error, Debug, Diagnostic)]
 pub enum DecryptError {
@@ -101,14 +177,147 @@ pub enum QueryError {
     DecryptError(#[from] SealError),
 
     #[error(transparent)]
-    DynamoError(#[from] SdkError<operation::query::QueryError>),
+    DynamoError(#[from] DynamoDbError),
 
     #[error("ZeroKMS Error: {0}")]
     ZeroKMS(#[from] zerokms::Error),
 }
 
+impl From<SdkError<operation::query::QueryError>> for QueryError {
+    fn from(error: SdkError<operation::query::QueryError>) -> Self {
+        Self::DynamoError(error.into())
+    }
+}
+
+/// Transaction cancellation reasons which are usually transient and can be retried.
+pub(crate) const RETRYABLE_CANCELLATION_REASONS: &[&str] = &[
+    "TransactionConflict",
+    "ThrottlingError",
+    "ProvisionedThroughputExceeded",
+];
+
 pub trait DynamoError: std::error::Error + Sized {}
 
+/// An error returned by DynamoDB classified by its error code, or by how the request failed if
+/// no response was received.
+#[derive(Error, Debug, Diagnostic)]
+pub enum DynamoDbError {
+    #[error("Request was throttled: {0}")]
+    Throttled(String),
+    #[error("Request timed out: {0}")]
+    Timeout(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("Request failed to send or its response couldn't be read: {0}")]
+    Dispatch(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("DynamoDB failed to process the request: {0}")]
+    InternalServerError(String),
+    #[error("Conditional check failed: {0}")]
+    ConditionalCheckFailed(String),
+    #[error("Transaction was cancelled: {message}")]
+    TransactionCanceled {
+        message: String,
+        /// The reason for each item in the transaction, in the order the items were sent.
+        /// Items which didn't cause the cancellation have the code `None`.
+        reasons: Vec<CancellationReason>,
+    },
+    #[error("Resource not found: {0}")]
+    ResourceNotFound(String),
+    #[error("Validation error: {0}")]
+    Validation(String),
+    #[error("DynamoDB error: {0}")]
+    Other(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl DynamoError for DynamoDbError {}
+
+impl DynamoDbError {
+    /// Returns true if the error is transient and the request may succeed if sent again.
+    pub fn is_retryable(&self) -> bool {
+        self.is_retryable_with_reasons(RETRYABLE_CANCELLATION_REASONS)
+    }
+
+    /// Like [`DynamoDbError::is_retryable`] but treats a cancelled transaction as retryable when
+    /// every item was either not the cause of the cancellation or was cancelled for one of
+    /// `retryable_reasons`.
+    pub(crate) fn is_retryable_with_reasons(&self, retryable_reasons: &[impl AsRef<str>]) -> bool {
+        match self {
+            Self::Throttled(_)
+            | Self::Timeout(_)
+            | Self::Dispatch(_)
+            | Self::InternalServerError(_) => true,
+            Self::TransactionCanceled { reasons, .. } => {
+                let is_retryable = |code: &str| {
+                    retryable_reasons
+                        .iter()
+                        .any(|reason| reason.as_ref() == code)
+                };
+
+                reasons
+                    .iter()
+                    .any(|reason| reason.code().is_some_and(is_retryable))
+                    && reasons.iter().all(|reason| match reason.code() {
+                        None | Some("None") => true,
+                        Some(code) => is_retryable(code),
+                    })
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<E, R> From<SdkError<E, R>> for DynamoDbError
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+    R: std::fmt::Debug + Send + Sync + 'static,
+{
+    fn from(error: SdkError<E, R>) -> Self {
+        let context = match &error {
+            SdkError::ServiceError(context) => context,
+            SdkError::TimeoutError(_) => return Self::Timeout(Box::new(error)),
+            SdkError::DispatchFailure(failure) if failure.is_timeout() => {
+                return Self::Timeout(Box::new(error))
+            }
+            // User errors are caused by the request itself so sending it again won't help
+            SdkError::DispatchFailure(failure) if !failure.is_user() => {
+                return Self::Dispatch(Box::new(error))
+            }
+            SdkError::ResponseError(_) => return Self::Dispatch(Box::new(error)),
+            _ => return Self::Other(Box::new(error)),
+        };
+
+        let service_error = context.err();
+        let message = service_error.message().unwrap_or_default().to_string();
+
+        // Only transact_write_items includes the reason for each item in the transaction
+        if let Some(TransactWriteItemsError::TransactionCanceledException(cancelled)) =
+            (service_error as &dyn std::error::Error).downcast_ref::<TransactWriteItemsError>()
+        {
+            return Self::TransactionCanceled {
+                message,
+                reasons: cancelled.cancellation_reasons().to_vec(),
+            };
+        }
+
+        match service_error.code() {
+            Some(
+                "ThrottlingException"
+                | "ProvisionedThroughputExceededException"
+                | "RequestLimitExceeded",
+            ) => Self::Throttled(message),
+            Some("ConditionalCheckFailedException") => Self::ConditionalCheckFailed(message),
+            Some("TransactionCanceledException") => Self::TransactionCanceled {
+                message,
+                reasons: vec![],
+            },
+            Some("ResourceNotFoundException") => Self::ResourceNotFound(message),
+            Some("ValidationException") => Self::Validation(message),
+            Some("InternalServerError" | "ServiceUnavailable") => {
+                Self::InternalServerError(message)
+            }
+            _ => Self::Other(Box::new(error)),
+        }
+    }
+}
+
 /// Error returned by `EncryptedTable::init` when connecting to CipherStash services
 #[derive(Error, Debug, Diagnostic)]
 pub enum InitError {
@@ -130,6 +339,131 @@ pub enum Error {
     GetError(#[from] GetError),
     #[error("DeleteError: {0}")]
     DeleteError(#[from] DeleteError),
+    #[error("RestoreError: {0}")]
+    RestoreError(#[from] RestoreError),
+    #[error("DisableDatasetError: {0}")]
+    DisableDatasetError(#[from] DisableDatasetError),
     #[error(transparent)]
     QueryError(#[from] QueryError),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_dynamodb::{
+        config::http::HttpResponse,
+        error::{ConnectorError, ErrorMetadata},
+        operation::get_item::GetItemError,
+    };
+
+    fn service_error(code: &str) -> SdkError<GetItemError, HttpResponse> {
+        SdkError::service_error(
+            GetItemError::generic(ErrorMetadata::builder().code(code).message("oops").build()),
+            HttpResponse::new(400u16.try_into().unwrap(), "".into()),
+        )
+    }
+
+    #[test]
+    fn test_classify_dynamo_errors() {
+        assert!(matches!(
+            service_error("ThrottlingException").into(),
+            DynamoDbError::Throttled(message) if message == "oops"
+        ));
+        assert!(matches!(
+            service_error("ConditionalCheckFailedException").into(),
+            DynamoDbError::ConditionalCheckFailed(_)
+        ));
+        assert!(matches!(
+            service_error("ResourceNotFoundException").into(),
+            DynamoDbError::ResourceNotFound(_)
+        ));
+        assert!(matches!(
+            service_error("ValidationException").into(),
+            DynamoDbError::Validation(_)
+        ));
+        assert!(matches!(
+            service_error("InternalServerError").into(),
+            DynamoDbError::InternalServerError(_)
+        ));
+        assert!(matches!(
+            service_error("SomethingElse").into(),
+            DynamoDbError::Other(_)
+        ));
+    }
+
+    #[derive(Error, Debug)]
+    #[error("Failed to retrieve key: {0}")]
+    struct RetrieveKeyError(#[source] ViturRequestError);
+
+    fn is_key_error_unavailable(error: ViturRequestError) -> bool {
+        is_key_unavailable(&RetrieveKeyError(error))
+    }
+
+    #[test]
+    fn test_is_key_unavailable() {
+        let not_found = || std::io::Error::new(std::io::ErrorKind::NotFound, "dataset disabled");
+        let reset = || std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+
+        assert!(is_key_error_unavailable(ViturRequestError::not_found(
+            "Not found",
+            not_found()
+        )));
+
+        // Transient and authentication errors don't show that the keys are unavailable
+        assert!(!is_key_error_unavailable(
+            ViturRequestError::send("Failed to send", reset()).retryable()
+        ));
+        assert!(!is_key_error_unavailable(ViturRequestError::response(
+            "Unauthorized",
+            reset()
+        )));
+        assert!(
+            !GetError::ZeroKMS(zerokms::Error::Unexpected("oops".to_string())).is_key_unavailable()
+        );
+        assert!(
+            !GetError::DynamoError(DynamoDbError::Throttled("slow down".to_string()))
+                .is_key_unavailable()
+        );
+    }
+
+    #[test]
+    fn test_classify_request_failures() {
+        let timeout: SdkError<GetItemError, HttpResponse> = SdkError::timeout_error("slow");
+        assert!(matches!(timeout.into(), DynamoDbError::Timeout(_)));
+
+        let timeout: SdkError<GetItemError, HttpResponse> =
+            SdkError::dispatch_failure(ConnectorError::timeout("slow".into()));
+        assert!(matches!(timeout.into(), DynamoDbError::Timeout(_)));
+
+        let io: SdkError<GetItemError, HttpResponse> =
+            SdkError::dispatch_failure(ConnectorError::io("connection reset".into()));
+        assert!(matches!(io.into(), DynamoDbError::Dispatch(_)));
+
+        let user: SdkError<GetItemError, HttpResponse> =
+            SdkError::dispatch_failure(ConnectorError::user("bad request".into()));
+        assert!(matches!(user.into(), DynamoDbError::Other(_)));
+
+        let construction: SdkError<GetItemError, HttpResponse> =
+            SdkError::construction_failure("missing table name");
+        assert!(matches!(construction.into(), DynamoDbError::Other(_)));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(
+            DynamoDbError::from(service_error("ProvisionedThroughputExceededException"))
+                .is_retryable()
+        );
+        assert!(!DynamoDbError::from(service_error("ValidationException")).is_retryable());
+        assert!(!DynamoDbError::from(service_error("SomethingElse")).is_retryable());
+        assert!(DynamoDbError::from(service_error("InternalServerError")).is_retryable());
+        assert!(DynamoDbError::from(service_error("ServiceUnavailable")).is_retryable());
+
+        let timeout: SdkError<GetItemError, HttpResponse> = SdkError::timeout_error("slow");
+        assert!(DynamoDbError::from(timeout).is_retryable());
+
+        let io: SdkError<GetItemError, HttpResponse> =
+            SdkError::dispatch_failure(ConnectorError::io("connection reset".into()));
+        assert!(DynamoDbError::from(io).is_retryable());
+    }
+}
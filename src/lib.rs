@@ -6,10 +6,14 @@
 pub mod crypto;
 pub mod encrypted_table;
 pub mod traits;
-pub use encrypted_table::{EncryptedTable, QueryBuilder};
+pub use crypto::{AttributeAccess, DecryptionPolicy};
+pub use encrypted_table::{
+    AccessContext, EncryptedTable, OperationMetadata, PartitionQueryBuilder, PutReport,
+    QueryBuilder, RetryPolicy, TypedQuery,
+};
 pub use traits::{
-    Decryptable, Encryptable, Identifiable, IndexType, Pk, PkSk, PrimaryKey, Searchable,
-    SingleIndex,
+    Decryptable, Encryptable, Identifiable, IndexType, Pk, PkSk, PrimaryKey, ProtectedIndex,
+    Searchable, SingleIndex,
 };
 
 pub mod errors;
@@ -0,0 +1,204 @@
+use super::PrimaryKeyError;
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use uuid::Uuid;
+
+/// A value which can be used as the partition key or sort key of a record.
+///
+/// Key components are stored as strings. Encodings must be stable since records can only be
+/// retrieved if their key is encoded the same way it was when they were stored, and should sort
+/// in the same order as the values they encode so that partitions are listed in order.
+///
+/// DynamoDB number (`N`) and binary (`B`) key attributes aren't supported. The `pk` and `sk` of
+/// the table are always strings so that encrypted and plaintext keys can be stored in the same
+/// table.
+pub trait KeyComponent: Sized {
+    /// Encode the value for use in a primary key.
+    fn to_key(&self) -> String;
+
+    /// Parse a value encoded with [`KeyComponent::to_key`].
+    fn from_key(key: &str) -> Result<Self, PrimaryKeyError>;
+}
+
+impl KeyComponent for String {
+    fn to_key(&self) -> String {
+        self.clone()
+    }
+
+    fn from_key(key: &str) -> Result<Self, PrimaryKeyError> {
+        Ok(key.to_string())
+    }
+}
+
+/// Uuids are encoded in their lowercase hyphenated form.
+impl KeyComponent for Uuid {
+    fn to_key(&self) -> String {
+        self.hyphenated().to_string()
+    }
+
+    fn from_key(key: &str) -> Result<Self, PrimaryKeyError> {
+        Uuid::parse_str(key).map_err(|e| invalid_key::<Self>(key, e))
+    }
+}
+
+/// Timestamps are encoded as RFC 3339 with nanoseconds so that they sort in time order.
+#[cfg(feature = "chrono")]
+impl KeyComponent for DateTime<Utc> {
+    fn to_key(&self) -> String {
+        self.to_rfc3339_opts(SecondsFormat::Nanos, true)
+    }
+
+    fn from_key(key: &str) -> Result<Self, PrimaryKeyError> {
+        DateTime::parse_from_rfc3339(key)
+            .map(|value| value.with_timezone(&Utc))
+            .map_err(|e| invalid_key::<Self>(key, e))
+    }
+}
+
+/// Dates are encoded as `YYYY-MM-DD`.
+#[cfg(feature = "chrono")]
+impl KeyComponent for NaiveDate {
+    fn to_key(&self) -> String {
+        self.format("%Y-%m-%d").to_string()
+    }
+
+    fn from_key(key: &str) -> Result<Self, PrimaryKeyError> {
+        NaiveDate::parse_from_str(key, "%Y-%m-%d").map_err(|e| invalid_key::<Self>(key, e))
+    }
+}
+
+/// Integers are encoded in decimal, zero padded to the number of digits of the largest value of
+/// the type so that they sort in numeric order (e.g. `42u32` is encoded as `0000000042`).
+///
+/// Signed integers are offset by the magnitude of their smallest value before they are encoded,
+/// so `i8::MIN` is encoded as `000`, `0i8` as `128` and `i8::MAX` as `255`.
+macro_rules! impl_key_component_for_integer {
+    ($($ty:ty => $unsigned:ty),*) => {
+        $(
+            impl KeyComponent for $ty {
+                fn to_key(&self) -> String {
+                    const WIDTH: usize = decimal_digits(<$unsigned>::MAX as u128);
+
+                    #[allow(clippy::unnecessary_cast)]
+                    let offset = (*self as $unsigned) ^ (<$ty>::MIN as $unsigned);
+
+                    format!("{offset:0WIDTH$}")
+                }
+
+                fn from_key(key: &str) -> Result<Self, PrimaryKeyError> {
+                    const WIDTH: usize = decimal_digits(<$unsigned>::MAX as u128);
+
+                    if key.len() != WIDTH || !key.bytes().all(|b| b.is_ascii_digit()) {
+                        return Err(invalid_key::<Self>(
+                            key,
+                            format!("expected {WIDTH} decimal digits"),
+                        ));
+                    }
+
+                    let offset: $unsigned = key.parse().map_err(|e| invalid_key::<Self>(key, e))?;
+
+                    #[allow(clippy::unnecessary_cast)]
+                    Ok((offset ^ (<$ty>::MIN as $unsigned)) as $ty)
+                }
+            }
+        )*
+    };
+}
+
+impl_key_component_for_integer!(
+    u8 => u8,
+    u16 => u16,
+    u32 => u32,
+    u64 => u64,
+    u128 => u128,
+    usize => usize,
+    i8 => u8,
+    i16 => u16,
+    i32 => u32,
+    i64 => u64,
+    i128 => u128,
+    isize => usize
+);
+
+const fn decimal_digits(mut value: u128) -> usize {
+    let mut digits = 1;
+
+    while value >= 10 {
+        value /= 10;
+        digits += 1;
+    }
+
+    digits
+}
+
+fn invalid_key<T>(key: &str, error: impl std::fmt::Display) -> PrimaryKeyError {
+    PrimaryKeyError::InvalidKeyComponent {
+        key: key.to_string(),
+        type_name: std::any::type_name::<T>(),
+        message: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: KeyComponent + PartialEq + std::fmt::Debug>(value: T, key: &str) {
+        assert_eq!(value.to_key(), key);
+        assert_eq!(T::from_key(key).unwrap(), value);
+    }
+
+    #[test]
+    fn test_key_component_round_trip() {
+        round_trip("dan@coderdan.co".to_string(), "dan@coderdan.co");
+        round_trip(
+            Uuid::from_u128(0x67e55044_10b1_426f_9247_bb680e5fe0c8),
+            "67e55044-10b1-426f-9247-bb680e5fe0c8",
+        );
+        round_trip(42u64, "00000000000000000042");
+        round_trip(42u32, "0000000042");
+        round_trip(-7i32, "2147483641");
+        round_trip(i8::MIN, "000");
+        round_trip(0i8, "128");
+        round_trip(i8::MAX, "255");
+        round_trip(u128::MAX, &u128::MAX.to_string());
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn test_chrono_key_component_round_trip() {
+        round_trip(
+            DateTime::from_timestamp(1_700_000_000, 5).unwrap(),
+            "2023-11-14T22:13:20.000000005Z",
+        );
+        round_trip(NaiveDate::from_ymd_opt(2024, 2, 29).unwrap(), "2024-02-29");
+    }
+
+    #[test]
+    fn test_integer_keys_sort_in_numeric_order() {
+        let values = [i64::MIN, -1_000, -7, -1, 0, 1, 2, 10, 99, 100, i64::MAX];
+        let keys = values
+            .iter()
+            .map(|value| value.to_key())
+            .collect::<Vec<_>>();
+
+        let mut sorted = keys.clone();
+        sorted.sort();
+
+        assert_eq!(sorted, keys);
+        assert!(keys.iter().all(|key| key.len() == keys[0].len()));
+    }
+
+    #[test]
+    fn test_invalid_key_component() {
+        assert!(matches!(
+            u32::from_key("forty two"),
+            Err(PrimaryKeyError::InvalidKeyComponent { .. })
+        ));
+        assert!(Uuid::from_key("not-a-uuid").is_err());
+        // Keys must be padded to the full width of the type
+        assert!(u32::from_key("42").is_err());
+        assert!(u8::from_key("256").is_err());
+        assert!(i8::from_key("-01").is_err());
+    }
+}
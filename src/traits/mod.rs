@@ -11,7 +11,9 @@ pub use cipherstash_client::{
     },
 };
 
+mod key_component;
 mod primary_key;
+pub use key_component::*;
 use miette::Diagnostic;
 pub use primary_key::*;
 
@@ -56,6 +58,38 @@ impl Display for IndexType {
     }
 }
 
+/// An index defined on a [`Searchable`] type along with the settings needed to manage its terms.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtectedIndex {
+    pub name: Cow<'static, str>,
+    pub index_type: IndexType,
+
+    /// The maximum number of terms stored for this index per record.
+    ///
+    /// Terms beyond this limit are not stored which means that, for example, prefix queries on
+    /// long strings may not match. This is also used to clean up all the terms for a record when
+    /// it is updated or deleted so changing it for an existing index may leave stale terms behind.
+    pub max_terms: usize,
+}
+
+impl ProtectedIndex {
+    /// The number of terms stored per record for an index unless otherwise specified.
+    pub const DEFAULT_MAX_TERMS: usize = 25;
+
+    pub fn new(name: impl Into<Cow<'static, str>>, index_type: IndexType) -> Self {
+        Self {
+            name: name.into(),
+            index_type,
+            max_terms: Self::DEFAULT_MAX_TERMS,
+        }
+    }
+
+    pub fn with_max_terms(mut self, max_terms: usize) -> Self {
+        self.max_terms = max_terms;
+        self
+    }
+}
+
 #[derive(Debug, Error, Diagnostic)]
 pub enum ReadConversionError {
     #[error("Missing attribute: {0}")]
@@ -76,6 +110,12 @@ pub enum WriteConversionError {
 pub enum PrimaryKeyError {
     #[error("EncryptionError: {0}")]
     EncryptionError(#[from] EncryptionError),
+    #[error("Invalid key '{key}' for {type_name}: {message}")]
+    InvalidKeyComponent {
+        key: String,
+        type_name: &'static str,
+        message: String,
+    },
     #[error("PrimaryKeyError: {0}")]
     Unknown(String),
 }
@@ -93,11 +133,55 @@ pub trait Identifiable {
         false
     }
 
+    /// Returns true if encrypted attributes are bound to the primary key of the record.
+    ///
+    /// When set, the `pk` and `sk` of the record are included in the descriptor which is
+    /// authenticated with every encrypted attribute so that ciphertexts copied to another record
+    /// are rejected when decrypted.
+    /// Changing this for a type means existing records can no longer be decrypted.
+    fn is_bound_to_primary_key() -> bool {
+        false
+    }
+
+    /// Returns true if a MAC covering the `pk`, `sk` and all unprotected attributes is stored
+    /// with each record.
+    ///
+    /// The MAC is verified when the record is decrypted so that changes to plaintext attributes
+    /// made directly in the table are detected.
+    ///
+    /// The `__deleted_at` marker of soft deleted records (see [`Identifiable::has_soft_delete`])
+    /// isn't covered by the MAC, so adding or removing it directly in the table isn't detected.
+    fn has_record_mac() -> bool {
+        false
+    }
+
+    /// Returns true if deleting a record only marks it as deleted so that it can be restored.
+    ///
+    /// Soft deleted records keep their root item with a `__deleted_at` attribute but their index
+    /// terms are removed so they no longer match queries. They are hidden from `get` until they
+    /// are restored with [`crate::EncryptedTable::restore`] or removed with
+    /// [`crate::EncryptedTable::purge`].
+    ///
+    /// The marker isn't covered by the record MAC (see [`Identifiable::has_record_mac`]).
+    fn has_soft_delete() -> bool {
+        false
+    }
+
+    /// Returns the name of the plaintext attribute which holds the time the record expires, in
+    /// seconds since the Unix epoch.
+    ///
+    /// The attribute is copied into every index term of the record so that DynamoDB's
+    /// time-to-live deletes the terms along with the record when the table's TTL attribute is set
+    /// to the same name.
+    fn ttl_attribute() -> Option<Cow<'static, str>> {
+        None
+    }
+
     fn type_name() -> Cow<'static, str>;
     fn sort_key_prefix() -> Option<Cow<'static, str>>;
 }
 
-pub trait Encryptable: Debug + Sized + Identifiable {
+pub trait Encryptable: Debug + Sized {
     /// Defines what attributes are protected and should be encrypted for this type.
     ///
     /// Must be equal to or a superset of protected_attributes on the [`Decryptable`] type.
@@ -111,7 +195,7 @@ pub trait Encryptable: Debug + Sized + Identifiable {
     fn into_unsealed(self) -> Unsealed;
 }
 
-pub trait Searchable: Encryptable {
+pub trait Searchable: Encryptable + Identifiable {
     fn attribute_for_index(
         &self,
         _index_name: &str,
@@ -120,9 +204,28 @@ pub trait Searchable: Encryptable {
         None
     }
 
-    // TODO: Make a type to represent the result of this function
-    /// Returns of indexes with their name and type.
-    fn protected_indexes() -> Cow<'static, [(Cow<'static, str>, IndexType)]> {
+    /// Returns the plaintexts which are indexed by the index, `index_name`.
+    ///
+    /// Indexes on collections return a plaintext for every element so that a query matches a
+    /// record when any of its elements match. An empty collection has no terms.
+    /// Defaults to the result of [`Searchable::attribute_for_index`].
+    fn attributes_for_index(
+        &self,
+        index_name: &str,
+        index_type: IndexType,
+    ) -> Option<Vec<ComposablePlaintext>> {
+        self.attribute_for_index(index_name, index_type)
+            .map(|attr| vec![attr])
+    }
+
+    /// Returns false if `self` has no attribute for the index (e.g. a variant of an enum which
+    /// doesn't have the indexed fields) in which case no terms are stored for it.
+    fn has_attribute_for_index(&self, _index_name: &str, _index_type: IndexType) -> bool {
+        true
+    }
+
+    /// Returns the indexes defined for this type.
+    fn protected_indexes() -> Cow<'static, [ProtectedIndex]> {
         Cow::Borrowed(&[])
     }
 
@@ -132,6 +235,15 @@ pub trait Searchable: Encryptable {
     ) -> Option<Box<dyn ComposableIndex + Send>> {
         None
     }
+
+    /// Returns the number of shards the terms for an index are spread across.
+    ///
+    /// When greater than 1 each term is suffixed with a shard number so that records sharing a
+    /// common term (e.g. a popular prefix) don't all land on the same `TermIndex` partition.
+    /// Queries against a sharded index fan out to every shard and merge the results.
+    fn term_shards(_index_name: &str, _index_type: IndexType) -> u8 {
+        1
+    }
 }
 
 pub trait Decryptable: Sized {
@@ -1,48 +1,127 @@
-#[derive(Clone)]
+use super::{KeyComponent, PrimaryKeyError};
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, NaiveDate, Utc};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct PrimaryKeyParts {
     pub pk: String,
     pub sk: String,
 }
 
-pub trait PrimaryKey: private::Sealed {
-    type Pk;
+/// The primary key of a record, as returned by [`crate::Identifiable::get_primary_key`].
+///
+/// [`Pk`] and [`PkSk`] cover keys made of a partition key and an optional sort key. Other key
+/// schemes (e.g. hierarchical keys like `TENANT#x/USER#y` or sort keys with embedded versions)
+/// can be supported by implementing this trait for your own type.
+///
+/// Implementations only decide how the key is laid out in the `pk` and `sk` attributes. The
+/// parts are still encrypted according to [`crate::Identifiable::is_pk_encrypted`] and
+/// [`crate::Identifiable::is_sk_encrypted`] (see [`crate::crypto::PreparedPrimaryKey`]), so the
+/// same key must always produce the same parts.
+pub trait PrimaryKey: Sized {
+    /// The type of the partition key, used to list a partition with
+    /// [`crate::EncryptedTable::query_partition`].
+    type Pk: KeyComponent;
+
+    /// The type of the sort key, or `()` if records don't have one.
     type Sk;
 
+    /// Whether records with this key have a sort key of their own. Records without one use their
+    /// type name as the sort key.
+    const HAS_SORT_KEY: bool = true;
+
+    /// Lay out the key as the values of the `pk` and `sk` attributes before any encryption.
+    ///
+    /// `sort_key_prefix` is the value returned by [`crate::Identifiable::sort_key_prefix`] and
+    /// should be included at the start of the sort key so that records of different types can be
+    /// told apart when they share a partition.
     fn into_parts(self, type_name: &str, sort_key_prefix: Option<&str>) -> PrimaryKeyParts;
+
+    /// Parse a key from the parts returned by [`PrimaryKey::into_parts`].
+    ///
+    /// Only keys which are stored in plaintext can be parsed since encrypted keys are stored as
+    /// MACs. Returns an error by default.
+    fn from_parts(
+        parts: PrimaryKeyParts,
+        sort_key_prefix: Option<&str>,
+    ) -> Result<Self, PrimaryKeyError> {
+        let _ = sort_key_prefix;
+
+        Err(PrimaryKeyError::Unknown(format!(
+            "primary key with pk '{}' and sk '{}' can't be parsed",
+            parts.pk, parts.sk
+        )))
+    }
 }
 
-impl PrimaryKey for Pk {
-    type Pk = String;
+impl<P: KeyComponent> PrimaryKey for Pk<P> {
+    type Pk = P;
     type Sk = ();
 
+    const HAS_SORT_KEY: bool = false;
+
     fn into_parts(self, type_name: &str, _sort_key_prefix: Option<&str>) -> PrimaryKeyParts {
         PrimaryKeyParts {
-            pk: self.0,
+            pk: self.0.to_key(),
             sk: type_name.into(),
         }
     }
+
+    fn from_parts(
+        parts: PrimaryKeyParts,
+        _sort_key_prefix: Option<&str>,
+    ) -> Result<Self, PrimaryKeyError> {
+        Ok(Self(P::from_key(&parts.pk)?))
+    }
 }
 
-impl PrimaryKey for PkSk {
-    type Pk = String;
-    type Sk = String;
+impl<P: KeyComponent, S: KeyComponent> PrimaryKey for PkSk<P, S> {
+    type Pk = P;
+    type Sk = S;
+
+    const HAS_SORT_KEY: bool = true;
 
     fn into_parts(self, _type_name: &str, sort_key_prefix: Option<&str>) -> PrimaryKeyParts {
         PrimaryKeyParts {
-            pk: self.0,
+            pk: self.0.to_key(),
             sk: if let Some(prefix) = sort_key_prefix {
-                format!("{prefix}#{}", self.1)
+                format!("{prefix}#{}", self.1.to_key())
             } else {
-                self.1
+                self.1.to_key()
             },
         }
     }
+
+    fn from_parts(
+        parts: PrimaryKeyParts,
+        sort_key_prefix: Option<&str>,
+    ) -> Result<Self, PrimaryKeyError> {
+        let sk = match sort_key_prefix {
+            Some(prefix) => parts
+                .sk
+                .strip_prefix(prefix)
+                .and_then(|sk| sk.strip_prefix('#'))
+                .ok_or_else(|| {
+                    PrimaryKeyError::Unknown(format!(
+                        "sort key '{}' doesn't start with the prefix '{prefix}'",
+                        parts.sk
+                    ))
+                })?,
+            None => parts.sk.as_str(),
+        };
+
+        Ok(Self(P::from_key(&parts.pk)?, S::from_key(sk)?))
+    }
 }
 
-pub struct Pk(pub String);
+/// A primary key made of only a partition key.
+///
+/// The partition key can be any [`KeyComponent`] (e.g. a `String` or `Uuid`).
+pub struct Pk<P = String>(pub P);
 
-impl Pk {
-    pub fn new(pk: impl Into<String>) -> Self {
+impl<P> Pk<P> {
+    pub fn new(pk: impl Into<P>) -> Self {
         Self(pk.into())
     }
 }
@@ -53,25 +132,68 @@ impl<P: Into<String>> From<P> for Pk {
     }
 }
 
-pub struct PkSk(pub String, pub String);
+/// A primary key made of a partition key and a sort key.
+///
+/// Both parts can be any [`KeyComponent`] (e.g. a `String` or `Uuid`).
+pub struct PkSk<P = String, S = String>(pub P, pub S);
 
-impl<Pk: Into<String>, Sk: Into<String>> From<(Pk, Sk)> for PkSk {
-    fn from(value: (Pk, Sk)) -> Self {
+impl<P, S, Pi: Into<P>, Si: Into<S>> From<(Pi, Si)> for PkSk<P, S> {
+    fn from(value: (Pi, Si)) -> Self {
         Self::new(value.0, value.1)
     }
 }
 
-impl PkSk {
-    pub fn new(pk: impl Into<String>, sk: impl Into<String>) -> Self {
+impl<P, S> PkSk<P, S> {
+    pub fn new(pk: impl Into<P>, sk: impl Into<S>) -> Self {
         Self(pk.into(), sk.into())
     }
 }
 
-mod private {
+/// Allow non-string keys to be passed directly, e.g. `table.get::<User>(id)` where `id` is a `Uuid`.
+macro_rules! impl_from_key_component_for_pk {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for Pk<$ty> {
+                fn from(value: $ty) -> Self {
+                    Self(value)
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "chrono")]
+impl_from_key_component_for_pk!(DateTime<Utc>, NaiveDate);
+
+impl_from_key_component_for_pk!(
+    Uuid, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
+);
+
+#[cfg(test)]
+mod tests {
     use super::*;
 
-    pub trait Sealed {}
+    #[test]
+    fn test_pk_sk_round_trip() {
+        let id = Uuid::from_u128(1);
+        let parts = PkSk::<Uuid, u32>(id, 7).into_parts("user", Some("user"));
 
-    impl Sealed for Pk {}
-    impl Sealed for PkSk {}
+        assert_eq!(
+            parts,
+            PrimaryKeyParts {
+                pk: "00000000-0000-0000-0000-000000000001".to_string(),
+                sk: "user#0000000007".to_string(),
+            }
+        );
+
+        let PkSk(pk, sk) = PkSk::<Uuid, u32>::from_parts(parts, Some("user")).unwrap();
+        assert_eq!((pk, sk), (id, 7));
+    }
+
+    #[test]
+    fn test_pk_sk_from_parts_with_wrong_prefix() {
+        let parts = PkSk::<String, String>::new("dan", "x").into_parts("user", Some("user"));
+
+        assert!(PkSk::<String, String>::from_parts(parts, Some("account")).is_err());
+    }
 }
@@ -0,0 +1,91 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+use cipherstash_dynamodb::{AccessContext, Decryptable, Encryptable, Identifiable, Searchable};
+use miette::IntoDiagnostic;
+use serial_test::serial;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+mod common;
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct Customer {
+    #[partition_key]
+    pub id: String,
+
+    #[cipherstash(query = "exact")]
+    pub email: String,
+}
+
+#[tokio::test]
+#[serial]
+async fn test_access_context_is_logged() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("access-context", |table| async move {
+        let request_id = Uuid::new_v4().to_string();
+        let context =
+            AccessContext::new("agent-42", "customer support").with_request_id(&request_id);
+
+        let customer = Customer {
+            id: "customer-1".to_string(),
+            email: "dan@coderdan.co".to_string(),
+        };
+
+        table.put(customer.clone()).await?;
+
+        let found: Option<Customer> = table.get_with("customer-1", &context).await?;
+        common::check_eq(found, Some(customer.clone()))?;
+
+        let found: Vec<Customer> = table
+            .query()
+            .eq("email", "dan@coderdan.co")
+            .with_context(context.clone())
+            .send()
+            .await?;
+        common::check_eq(found, vec![customer.clone()])?;
+
+        let found: Vec<Customer> = table
+            .query_partition("customer-1")
+            .with_context(context.clone())
+            .send()
+            .await?;
+        common::check_eq(found, vec![customer])?;
+
+        // Records which can't be decrypted aren't recorded
+        let invalid = HashMap::from([
+            (
+                "pk".to_string(),
+                AttributeValue::S("customer-2".to_string()),
+            ),
+            ("sk".to_string(), AttributeValue::S("customer".to_string())),
+        ]);
+        common::check_err(
+            table
+                .decrypt_all_with::<Customer>(vec![invalid], &context)
+                .await,
+        )?;
+
+        // Reads without a context aren't recorded
+        table.get::<Customer>("customer-1").await?;
+
+        let log_path = table
+            .decryption_log_path()
+            .expect("decryption log should be enabled");
+        let log = std::fs::read_to_string(log_path).into_diagnostic()?;
+
+        let entries = log
+            .lines()
+            .filter(|line| line.ends_with(&format!("Request: {request_id}")))
+            .collect::<Vec<_>>();
+        common::check_eq(entries.len(), 3)?;
+
+        for entry in entries {
+            common::check_eq(entry.contains("Type: customer - PK: "), true)?;
+            common::check_eq(
+                entry.contains("Actor: agent-42 - Purpose: customer support"),
+                true,
+            )?;
+        }
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,65 @@
+use cipherstash_dynamodb::{Decryptable, Encryptable, Identifiable, Searchable};
+use serial_test::serial;
+use std::collections::BTreeSet;
+
+mod common;
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct Customer {
+    #[partition_key]
+    pub email: String,
+
+    #[cipherstash(collection, query = "exact")]
+    pub tags: Vec<String>,
+
+    #[cipherstash(collection = "blob", query = "prefix")]
+    pub aliases: BTreeSet<String>,
+
+    #[cipherstash(collection)]
+    pub scores: Vec<i64>,
+}
+
+fn dan() -> Customer {
+    Customer {
+        email: "dan@coderdan.co".to_string(),
+        tags: vec!["vip".to_string(), "beta".to_string()],
+        aliases: BTreeSet::from(["Daniel".to_string(), "Danno".to_string()]),
+        scores: vec![3, 1, 2],
+    }
+}
+
+fn ada() -> Customer {
+    Customer {
+        email: "ada@example.com".to_string(),
+        tags: vec!["beta".to_string()],
+        aliases: BTreeSet::new(),
+        scores: vec![],
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_collection_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("collection-customers", |table| async move {
+        table.put(dan()).await?;
+        table.put(ada()).await?;
+
+        common::check_eq(table.get::<Customer>("dan@coderdan.co").await?, Some(dan()))?;
+        common::check_eq(table.get::<Customer>("ada@example.com").await?, Some(ada()))?;
+
+        // Any element of a collection matches
+        let customers: Vec<Customer> = table.query().eq("tags", "vip").send().await?;
+        common::check_eq(customers, vec![dan()])?;
+
+        let mut customers: Vec<Customer> = table.query().eq("tags", "beta").send().await?;
+        customers.sort_by(|a, b| a.email.cmp(&b.email));
+        common::check_eq(customers, vec![ada(), dan()])?;
+
+        // Collections stored as a blob are still indexed by element
+        let customers: Vec<Customer> = table.query().starts_with("aliases", "Dann").send().await?;
+        common::check_eq(customers, vec![dan()])?;
+
+        Ok(())
+    })
+    .await
+}
@@ -20,24 +20,32 @@ macro_rules! run_tests {
 
 run_tests! {
     fail => {
+        "./ui/collection-compound-index.rs",
         "./ui/compound-index-missing-config.rs",
         "./ui/compound-index-missing-field.rs",
         "./ui/compound-index-too-many-fields.rs",
         "./ui/compound-index-unsupported.rs",
+        "./ui/enum-field-attributes-differ.rs",
+        "./ui/enum-variant-missing-key.rs",
         "./ui/index-unsupported.rs",
         "./ui/invalid-field-name.rs",
+        "./ui/nested-path-without-nested.rs",
         "./ui/no-multi-same-index-per-field.rs",
         "./ui/pk-field-no-partition.rs",
         "./ui/pk-field-wrong-partition.rs",
         "./ui/sk-field-no-sort.rs",
         "./ui/sk-field-wrong-sort.rs",
+        "./ui/sort-key-unknown-field.rs",
+        "./ui/ttl-not-plaintext.rs",
         "./ui/using-pk-instead-of-pk-sk.rs"
     },
 
     pass => {
         "./ui/pass.rs",
+        "./ui/enum-and-tuple-struct.rs",
         "./ui/public_api.rs",
         "./ui/pk-field-on-struct.rs",
+        "./ui/typed-query.rs",
         "./ui/various-fields.rs"
     }
 }
@@ -0,0 +1,126 @@
+use cipherstash_dynamodb::{
+    traits::{PrimaryKeyError, PrimaryKeyParts},
+    Decryptable, Encryptable, Identifiable, PrimaryKey, Searchable,
+};
+use serial_test::serial;
+use std::borrow::Cow;
+
+mod common;
+
+/// A hierarchical key stored as `TENANT#<tenant>` / `USER#<user>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TenantUserKey {
+    tenant: String,
+    user: String,
+}
+
+impl PrimaryKey for TenantUserKey {
+    type Pk = String;
+    type Sk = String;
+
+    fn into_parts(self, _type_name: &str, _sort_key_prefix: Option<&str>) -> PrimaryKeyParts {
+        PrimaryKeyParts {
+            pk: format!("TENANT#{}", self.tenant),
+            sk: format!("USER#{}", self.user),
+        }
+    }
+
+    fn from_parts(
+        parts: PrimaryKeyParts,
+        _sort_key_prefix: Option<&str>,
+    ) -> Result<Self, PrimaryKeyError> {
+        match (
+            parts.pk.strip_prefix("TENANT#"),
+            parts.sk.strip_prefix("USER#"),
+        ) {
+            (Some(tenant), Some(user)) => Ok(Self {
+                tenant: tenant.to_string(),
+                user: user.to_string(),
+            }),
+            _ => Err(PrimaryKeyError::Unknown(
+                "invalid tenant user key".to_string(),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Encryptable, Decryptable, Searchable)]
+pub struct Member {
+    #[cipherstash(plaintext)]
+    tenant: String,
+
+    #[cipherstash(plaintext)]
+    user: String,
+
+    role: String,
+}
+
+impl Identifiable for Member {
+    type PrimaryKey = TenantUserKey;
+
+    fn get_primary_key(&self) -> Self::PrimaryKey {
+        TenantUserKey {
+            tenant: self.tenant.clone(),
+            user: self.user.clone(),
+        }
+    }
+
+    fn type_name() -> Cow<'static, str> {
+        Cow::Borrowed("member")
+    }
+
+    fn sort_key_prefix() -> Option<Cow<'static, str>> {
+        None
+    }
+}
+
+fn member() -> Member {
+    Member {
+        tenant: "acme".to_string(),
+        user: "dan".to_string(),
+        role: "admin".to_string(),
+    }
+}
+
+#[test]
+fn test_custom_primary_key_parts() {
+    let parts = member()
+        .get_primary_key()
+        .into_parts(&Member::type_name(), None);
+
+    assert_eq!(
+        parts,
+        PrimaryKeyParts {
+            pk: "TENANT#acme".to_string(),
+            sk: "USER#dan".to_string(),
+        }
+    );
+
+    assert_eq!(
+        TenantUserKey::from_parts(parts, None).unwrap(),
+        member().get_primary_key()
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_custom_primary_key_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("custom-primary-key-members", |table| async move {
+        table.put(member()).await?;
+
+        common::check_eq(
+            table.get::<Member>(member().get_primary_key()).await?,
+            Some(member()),
+        )?;
+
+        // Custom keys are stored as laid out by the key so their partition can be listed
+        let members: Vec<Member> = table.query_partition("TENANT#acme").send().await?;
+        common::check_eq(members, vec![member()])?;
+
+        table.delete::<Member>(member().get_primary_key()).await?;
+        common::check_none(table.get::<Member>(member().get_primary_key()).await?)?;
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,286 @@
+use cipherstash_dynamodb::{
+    crypto::SealError,
+    encryption::Plaintext,
+    errors::{DecryptError, GetError},
+    AccessContext, AttributeAccess, Decryptable, DecryptionPolicy, Encryptable, Identifiable,
+    Searchable,
+};
+use serial_test::serial;
+
+mod common;
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct Customer {
+    #[partition_key]
+    pub id: String,
+
+    #[cipherstash(query = "exact")]
+    pub email: String,
+
+    pub name: String,
+
+    pub notes: Option<String>,
+}
+
+/// Support staff see masked emails and no notes unless they're investigating fraud.
+struct SupportPolicy;
+
+impl DecryptionPolicy for SupportPolicy {
+    fn access(
+        &self,
+        _type_name: &str,
+        attribute: &str,
+        context: Option<&AccessContext>,
+    ) -> AttributeAccess {
+        match (attribute, context) {
+            (_, Some(context)) if context.purpose == "fraud investigation" => {
+                AttributeAccess::Decrypt
+            }
+            ("email", _) => AttributeAccess::Mask,
+            ("notes", _) => AttributeAccess::Omit,
+            _ => AttributeAccess::Decrypt,
+        }
+    }
+}
+
+/// Masks the name and notes with a fixed value.
+struct RedactPolicy;
+
+impl DecryptionPolicy for RedactPolicy {
+    fn access(&self, _: &str, attribute: &str, _: Option<&AccessContext>) -> AttributeAccess {
+        match attribute {
+            "name" | "notes" => AttributeAccess::Mask,
+            _ => AttributeAccess::Decrypt,
+        }
+    }
+
+    fn mask(&self, _: &str, _: &str, _: Plaintext) -> Result<Plaintext, SealError> {
+        Ok(Plaintext::from("[redacted]"))
+    }
+}
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct Member {
+    #[partition_key]
+    pub id: String,
+
+    pub age: i64,
+
+    pub verified: bool,
+}
+
+/// Masks the age and verified status of members with the default mask.
+struct MemberPolicy;
+
+impl DecryptionPolicy for MemberPolicy {
+    fn access(&self, _: &str, attribute: &str, _: Option<&AccessContext>) -> AttributeAccess {
+        match attribute {
+            "age" | "verified" => AttributeAccess::Mask,
+            _ => AttributeAccess::Decrypt,
+        }
+    }
+}
+
+/// Masks the age and verified status of members with values of the same type.
+struct TypedMemberPolicy;
+
+impl DecryptionPolicy for TypedMemberPolicy {
+    fn access(
+        &self,
+        type_name: &str,
+        attribute: &str,
+        context: Option<&AccessContext>,
+    ) -> AttributeAccess {
+        MemberPolicy.access(type_name, attribute, context)
+    }
+
+    fn mask(&self, _: &str, _: &str, plaintext: Plaintext) -> Result<Plaintext, SealError> {
+        match plaintext {
+            Plaintext::BigInt(_) => Ok(Plaintext::from(0_i64)),
+            Plaintext::Boolean(_) => Ok(Plaintext::from(false)),
+            plaintext => Ok(plaintext),
+        }
+    }
+}
+
+#[derive(Encryptable, Decryptable, Debug, Clone, PartialEq)]
+pub struct Address {
+    pub street: String,
+
+    pub contact: String,
+}
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct Shipment {
+    #[partition_key]
+    pub id: String,
+
+    #[cipherstash(nested)]
+    pub address: Address,
+}
+
+/// Masks the whole address of shipments with the default mask.
+struct ShipmentPolicy;
+
+impl DecryptionPolicy for ShipmentPolicy {
+    fn access(&self, _: &str, attribute: &str, _: Option<&AccessContext>) -> AttributeAccess {
+        match attribute {
+            "address" => AttributeAccess::Mask,
+            _ => AttributeAccess::Decrypt,
+        }
+    }
+}
+
+fn customer() -> Customer {
+    Customer {
+        id: "customer-1".to_string(),
+        email: "dan@coderdan.co".to_string(),
+        name: "Dan Draper".to_string(),
+        notes: Some("Requested a refund".to_string()),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_decryption_policy() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("decryption-policy", |table| async move {
+        let table = table.with_decryption_policy(SupportPolicy);
+
+        table.put(customer()).await?;
+
+        let masked = Customer {
+            email: "****@coderdan.co".to_string(),
+            notes: None,
+            ..customer()
+        };
+
+        let found: Option<Customer> = table.get("customer-1").await?;
+        common::check_eq(found, Some(masked.clone()))?;
+
+        let context = AccessContext::new("agent-42", "customer support");
+
+        let found: Option<Customer> = table.get_with("customer-1", &context).await?;
+        common::check_eq(found, Some(masked.clone()))?;
+
+        let found: Vec<Customer> = table
+            .query()
+            .eq("email", "dan@coderdan.co")
+            .with_context(context)
+            .send()
+            .await?;
+        common::check_eq(found, vec![masked])?;
+
+        let context = AccessContext::new("analyst-7", "fraud investigation");
+
+        let found: Option<Customer> = table.get_with("customer-1", &context).await?;
+        common::check_eq(found, Some(customer()))?;
+
+        let found: Vec<Customer> = table
+            .query_partition("customer-1")
+            .with_context(context)
+            .send()
+            .await?;
+        common::check_eq(found, vec![customer()])?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_custom_mask() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("decryption-policy-mask", |table| async move {
+        let table = table.with_decryption_policy(RedactPolicy);
+
+        table.put(customer()).await?;
+
+        let found: Option<Customer> = table.get("customer-1").await?;
+        common::check_eq(
+            found,
+            Some(Customer {
+                name: "[redacted]".to_string(),
+                notes: Some("[redacted]".to_string()),
+                ..customer()
+            }),
+        )?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_mask_non_string_fields() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("decryption-policy-typed-mask", |table| async move {
+        let member = Member {
+            id: "member-1".to_string(),
+            age: 42,
+            verified: true,
+        };
+
+        table.put(member.clone()).await?;
+
+        // Only strings can be masked by default
+        let table = table.with_decryption_policy(MemberPolicy);
+        let result = table.get::<Member>("member-1").await;
+        common::check_eq(
+            matches!(
+                result,
+                Err(GetError::DecryptError(DecryptError::SealError(
+                    SealError::CannotMask { .. }
+                )))
+            ),
+            true,
+        )?;
+
+        let table = table.with_decryption_policy(TypedMemberPolicy);
+        let found: Option<Member> = table.get("member-1").await?;
+        common::check_eq(
+            found,
+            Some(Member {
+                age: 0,
+                verified: false,
+                ..member
+            }),
+        )?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_mask_nested_field() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("decryption-policy-nested", |table| async move {
+        let table = table.with_decryption_policy(ShipmentPolicy);
+
+        table
+            .put(Shipment {
+                id: "shipment-1".to_string(),
+                address: Address {
+                    street: "1 Main St".to_string(),
+                    contact: "dan@coderdan.co".to_string(),
+                },
+            })
+            .await?;
+
+        // Every protected field of the nested value is masked
+        let found: Option<Shipment> = table.get("shipment-1").await?;
+        common::check_eq(
+            found,
+            Some(Shipment {
+                id: "shipment-1".to_string(),
+                address: Address {
+                    street: "****".to_string(),
+                    contact: "****@coderdan.co".to_string(),
+                },
+            }),
+        )?;
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,112 @@
+use cipherstash_dynamodb::{
+    errors::DisableDatasetError, Decryptable, Encryptable, Identifiable, Searchable,
+};
+use miette::IntoDiagnostic;
+use serial_test::serial;
+use uuid::Uuid;
+
+mod common;
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct Patient {
+    #[partition_key]
+    pub id: String,
+
+    #[cipherstash(query = "exact")]
+    pub email: String,
+
+    pub diagnosis: String,
+}
+
+#[tokio::test]
+#[serial]
+async fn test_disable_dataset() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("disabled-patients", |table| async move {
+        let client_id: Uuid = std::env::var("CS_CLIENT_ID")
+            .expect("CS_CLIENT_ID must be set")
+            .parse()
+            .expect("CS_CLIENT_ID must be a uuid");
+
+        // Each subject's records are stored in a dataset of their own
+        let cipher = table.cipher();
+        let dataset = cipher
+            .create_dataset(
+                &format!("disable-test-{}", Uuid::new_v4()),
+                "Disable test subject",
+            )
+            .await
+            .into_diagnostic()?;
+        cipher
+            .grant_dataset(client_id, dataset.id)
+            .await
+            .into_diagnostic()?;
+
+        let patient = Patient {
+            id: "patient-1".to_string(),
+            email: "dan@coderdan.co".to_string(),
+            diagnosis: "healthy".to_string(),
+        };
+
+        table.put_via(patient.clone(), dataset.id).await?;
+
+        let found: Option<Patient> = table.get_via("patient-1", dataset.id).await?;
+        common::check_eq(found, Some(patient.clone()))?;
+
+        common::check_err(
+            table
+                .verify_dataset_disabled::<Patient>("patient-1", dataset.id)
+                .await,
+        )?;
+
+        table.disable_dataset(dataset.id).await?;
+
+        // The record is still in the table but can't be found or decrypted while the dataset is
+        // disabled
+        table
+            .verify_dataset_disabled::<Patient>("patient-1", dataset.id)
+            .await?;
+
+        // An unknown dataset is not mistaken for a disabled one
+        let unknown_dataset_id = Uuid::new_v4();
+        assert!(matches!(
+            table
+                .verify_dataset_disabled::<Patient>("patient-1", unknown_dataset_id)
+                .await,
+            Err(DisableDatasetError::UnknownDataset { dataset_id }) if dataset_id == unknown_dataset_id
+        ));
+
+        common::check_err(table.get_via::<Patient>("patient-1", dataset.id).await)?;
+        common::check_err(
+            table
+                .query::<Patient>()
+                .eq("email", "dan@coderdan.co")
+                .via(dataset.id)
+                .send()
+                .await,
+        )?;
+
+        // Disabling a dataset is reversible
+        cipher.enable_dataset(dataset.id).await.into_diagnostic()?;
+
+        let found: Option<Patient> = table.get_via("patient-1", dataset.id).await?;
+        common::check_eq(found, Some(patient))?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[test]
+fn test_not_disabled_error() {
+    let dataset_id = Uuid::nil();
+
+    assert_eq!(
+        DisableDatasetError::NotDisabled { dataset_id }.to_string(),
+        "Dataset 00000000-0000-0000-0000-000000000000 has not been disabled: its records can still be decrypted"
+    );
+
+    assert_eq!(
+        DisableDatasetError::UnknownDataset { dataset_id }.to_string(),
+        "Dataset 00000000-0000-0000-0000-000000000000 does not exist in ZeroKMS"
+    );
+}
@@ -0,0 +1,103 @@
+use cipherstash_dynamodb::{Decryptable, Encryptable, Identifiable, Searchable};
+use serial_test::serial;
+
+mod common;
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+#[cipherstash(tag = "kind")]
+pub enum PaymentMethod {
+    Card {
+        #[partition_key]
+        id: String,
+        #[cipherstash(query = "exact")]
+        owner: String,
+        #[cipherstash(query = "exact")]
+        number: String,
+        #[cipherstash(plaintext)]
+        expiry: String,
+    },
+    BankAccount {
+        #[partition_key]
+        id: String,
+        #[cipherstash(query = "exact")]
+        owner: String,
+        account_number: String,
+    },
+}
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct Note(
+    #[partition_key] String,
+    #[cipherstash(query = "prefix")] String,
+    #[cipherstash(plaintext)] u32,
+);
+
+fn card() -> PaymentMethod {
+    PaymentMethod::Card {
+        id: "pm-1".to_string(),
+        owner: "dan@coderdan.co".to_string(),
+        number: "4242424242424242".to_string(),
+        expiry: "12/30".to_string(),
+    }
+}
+
+fn bank_account() -> PaymentMethod {
+    PaymentMethod::BankAccount {
+        id: "pm-2".to_string(),
+        owner: "dan@coderdan.co".to_string(),
+        account_number: "000123456".to_string(),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_enum_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("enum-payment-methods", |table| async move {
+        table.put(card()).await?;
+        table.put(bank_account()).await?;
+
+        common::check_eq(table.get::<PaymentMethod>("pm-1").await?, Some(card()))?;
+        common::check_eq(
+            table.get::<PaymentMethod>("pm-2").await?,
+            Some(bank_account()),
+        )?;
+
+        let mut methods: Vec<PaymentMethod> =
+            table.query().eq("owner", "dan@coderdan.co").send().await?;
+
+        methods.sort_by_key(|method| method.get_primary_key().0);
+
+        common::check_eq(methods, vec![card(), bank_account()])?;
+
+        // Only cards are indexed by number
+        let methods: Vec<PaymentMethod> = table
+            .query()
+            .eq("number", "4242424242424242")
+            .send()
+            .await?;
+
+        common::check_eq(methods, vec![card()])?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_tuple_struct_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("tuple-struct-notes", |table| async move {
+        let note = Note("note-1".to_string(), "Groceries".to_string(), 3);
+
+        table.put(note.clone()).await?;
+
+        common::check_eq(table.get::<Note>("note-1").await?, Some(note.clone()))?;
+
+        let notes: Vec<Note> = table.query().starts_with("1", "Groc").send().await?;
+
+        common::check_eq(notes, vec![note])?;
+
+        Ok(())
+    })
+    .await
+}
@@ -1,10 +1,12 @@
-use aws_sdk_dynamodb::Client;
+use aws_sdk_dynamodb::{types::AttributeValue, Client};
 use cipherstash_dynamodb::{
-    encrypted_table::PreparedRecord, Decryptable, Encryptable, EncryptedTable, Identifiable,
-    Searchable,
+    crypto::SealError,
+    encrypted_table::{Headless, PreparedRecord},
+    errors::DecryptError,
+    Decryptable, Encryptable, EncryptedTable, Identifiable, Searchable,
 };
 use serial_test::serial;
-use std::future::Future;
+use std::{collections::HashMap, future::Future};
 
 mod common;
 
@@ -36,6 +38,36 @@ impl User {
     }
 }
 
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, PartialEq, Clone)]
+#[cipherstash(bind_to_primary_key)]
+pub struct BoundUser {
+    #[partition_key]
+    pub id: String,
+
+    #[cipherstash(query = "exact")]
+    pub email: String,
+}
+
+impl BoundUser {
+    pub fn new(id: impl Into<String>, email: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            email: email.into(),
+        }
+    }
+}
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, PartialEq, Clone)]
+#[cipherstash(record_mac)]
+pub struct Account {
+    #[cipherstash(query = "exact")]
+    #[partition_key]
+    pub email: String,
+
+    #[cipherstash(plaintext)]
+    pub role: String,
+}
+
 async fn run_test<F: Future<Output = ()>>(f: impl FnOnce(Client, String) -> F) {
     let config = aws_config::from_env()
         .endpoint_url("http://localhost:8000")
@@ -98,3 +130,271 @@ async fn test_headless_roundtrip() {
     })
     .await;
 }
+
+#[tokio::test]
+async fn test_headless_decrypt_each() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let mut items = vec![];
+
+    for user in [
+        User::new("john@john.co", "john", "tag"),
+        User::new("jane@jane.co", "jane", "tag"),
+    ] {
+        let user_record = PreparedRecord::prepare_record(user).expect("failed to prepare record");
+
+        let patch = table
+            .create_put_patch(user_record, None, |_, _| true)
+            .await
+            .expect("failed to encrypt");
+
+        items.extend(
+            patch
+                .put_records
+                .into_iter()
+                .filter(|x| !x.contains_key("term")),
+        );
+    }
+
+    // Move the encrypted name of jane into the email attribute
+    let name = items[1].remove("name").expect("expected name attribute");
+    items[1].insert("email".to_string(), name);
+    let tampered_pk = items[1]["pk"].as_s().unwrap().to_string();
+
+    let decrypted = table
+        .decrypt_each::<User>(items)
+        .await
+        .expect("failed to decrypt");
+
+    assert_eq!(decrypted.len(), 2);
+    assert_eq!(
+        decrypted[0].1.as_ref().expect("expected john to decrypt"),
+        &User::new("john@john.co", "john", "tag")
+    );
+
+    let (primary_key, result) = &decrypted[1];
+    assert_eq!(primary_key.pk, tampered_pk);
+    assert!(matches!(
+        result,
+        Err(DecryptError::SealError(SealError::TamperDetected { pk, .. })) if pk == &tampered_pk
+    ));
+}
+
+async fn seal_items<T: Searchable + Identifiable>(
+    table: &EncryptedTable<Headless>,
+    records: impl IntoIterator<Item = T>,
+    is_term: bool,
+) -> Vec<HashMap<String, AttributeValue>> {
+    let mut items = vec![];
+
+    for record in records {
+        let record = PreparedRecord::prepare_record(record).expect("failed to prepare record");
+
+        let patch = table
+            .create_put_patch(record, None, |_, _| true)
+            .await
+            .expect("failed to encrypt");
+
+        items.extend(
+            patch
+                .put_records
+                .into_iter()
+                .filter(|x| x.contains_key("term") == is_term),
+        );
+    }
+
+    items
+}
+
+async fn seal_root_items<T: Searchable + Identifiable>(
+    table: &EncryptedTable<Headless>,
+    records: impl IntoIterator<Item = T>,
+) -> Vec<HashMap<String, AttributeValue>> {
+    seal_items(table, records, false).await
+}
+
+async fn seal_term_items<T: Searchable + Identifiable>(
+    table: &EncryptedTable<Headless>,
+    records: impl IntoIterator<Item = T>,
+) -> Vec<HashMap<String, AttributeValue>> {
+    seal_items(table, records, true).await
+}
+
+fn swap_attribute(items: &mut [HashMap<String, AttributeValue>], name: &str) {
+    let a = items[0].remove(name).expect("expected attribute");
+    let b = items[1]
+        .insert(name.to_string(), a)
+        .expect("expected attribute");
+    items[0].insert(name.to_string(), b);
+}
+
+#[tokio::test]
+async fn test_headless_bind_to_primary_key() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let users = [
+        BoundUser::new("1", "john@john.co"),
+        BoundUser::new("2", "jane@jane.co"),
+    ];
+
+    let items = seal_root_items(&table, users.clone()).await;
+
+    let decrypted: Vec<BoundUser> = table
+        .decrypt_all(items.clone())
+        .await
+        .expect("failed to decrypt");
+
+    assert_eq!(decrypted, users);
+
+    // Move the encrypted emails between the records
+    let mut swapped = items;
+    swap_attribute(&mut swapped, "email");
+
+    let decrypted = table
+        .decrypt_each::<BoundUser>(swapped)
+        .await
+        .expect("failed to decrypt");
+
+    for (_, result) in decrypted {
+        assert!(matches!(
+            result,
+            Err(DecryptError::SealError(SealError::TamperDetected { .. }))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_headless_unbound_ciphertexts_can_be_swapped() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let users = [
+        User::new("john@john.co", "john", "tag"),
+        User::new("jane@jane.co", "jane", "tag"),
+    ];
+
+    let mut items = seal_root_items(&table, users).await;
+    swap_attribute(&mut items, "name");
+
+    // Without `bind_to_primary_key` the names are decrypted for the wrong records
+    let decrypted: Vec<User> = table.decrypt_all(items).await.expect("failed to decrypt");
+
+    assert_eq!(
+        decrypted,
+        [
+            User::new("john@john.co", "jane", "tag"),
+            User::new("jane@jane.co", "john", "tag"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_headless_record_mac() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let account = Account {
+        email: "jane@jane.co".to_string(),
+        role: "user".to_string(),
+    };
+
+    let items = seal_root_items(&table, [account.clone()]).await;
+
+    assert!(items[0].contains_key("__mac"));
+
+    let decrypted: Vec<Account> = table
+        .decrypt_all(items.clone())
+        .await
+        .expect("failed to decrypt");
+
+    assert_eq!(decrypted, [account]);
+
+    let mut escalated = items.clone();
+    escalated[0].insert("role".to_string(), AttributeValue::S("admin".to_string()));
+
+    let mut unsigned = items;
+    unsigned[0].remove("__mac");
+
+    let decrypted = table
+        .decrypt_each::<Account>(escalated.into_iter().chain(unsigned))
+        .await
+        .expect("failed to decrypt");
+
+    for (_, result) in decrypted {
+        assert!(matches!(
+            result,
+            Err(DecryptError::SealError(
+                SealError::IntegrityCheckFailed { .. }
+            ))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_headless_record_mac_term_items() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let account = Account {
+        email: "jane@jane.co".to_string(),
+        role: "user".to_string(),
+    };
+
+    let items = seal_term_items(&table, [account.clone()]).await;
+
+    assert_eq!(items.len(), 1);
+    assert!(items[0].contains_key("__root_sk"));
+
+    // Queries return term entries which are verified against the primary key of their record
+    let decrypted: Vec<Account> = table
+        .decrypt_all(items.clone())
+        .await
+        .expect("failed to decrypt");
+
+    assert_eq!(decrypted, [account]);
+
+    let mut escalated = items.clone();
+    escalated[0].insert("role".to_string(), AttributeValue::S("admin".to_string()));
+
+    let mut moved = items;
+    moved[0].insert(
+        "__root_sk".to_string(),
+        AttributeValue::S("other".to_string()),
+    );
+
+    let decrypted = table
+        .decrypt_each::<Account>(escalated.into_iter().chain(moved))
+        .await
+        .expect("failed to decrypt");
+
+    for (_, result) in decrypted {
+        assert!(matches!(
+            result,
+            Err(DecryptError::SealError(
+                SealError::IntegrityCheckFailed { .. }
+            ))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_headless_bind_to_primary_key_term_items() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let users = [BoundUser::new("1", "john@john.co")];
+
+    let items = seal_term_items(&table, users.clone()).await;
+
+    let decrypted: Vec<BoundUser> = table.decrypt_all(items).await.expect("failed to decrypt");
+
+    assert_eq!(decrypted, users);
+}
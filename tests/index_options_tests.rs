@@ -0,0 +1,164 @@
+use cipherstash_dynamodb::{
+    crypto::TruncatedIndex,
+    errors::{PutError, SealError},
+    Decryptable, Encryptable, Identifiable, IndexType, ProtectedIndex, Searchable, SingleIndex,
+};
+use serial_test::serial;
+
+mod common;
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, PartialEq)]
+pub struct Product {
+    #[partition_key]
+    pub id: String,
+
+    #[cipherstash(query = "prefix", min_length = 1, max_length = 64, max_terms = 64)]
+    #[cipherstash(query = "exact")]
+    #[cipherstash(query = "prefix", compound = "category#title")]
+    pub title: String,
+
+    #[cipherstash(query = "exact", compound = "category#title", max_terms = 10)]
+    pub category: String,
+}
+
+impl Product {
+    fn new(id: impl Into<String>, title: impl Into<String>, category: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            title: title.into(),
+            category: category.into(),
+        }
+    }
+}
+
+#[test]
+fn test_protected_indexes_max_terms() {
+    let indexes = Product::protected_indexes();
+
+    assert!(indexes.contains(
+        &ProtectedIndex::new("title", IndexType::Single(SingleIndex::Prefix)).with_max_terms(64)
+    ));
+    assert!(indexes.contains(&ProtectedIndex::new(
+        "title",
+        IndexType::Single(SingleIndex::Exact)
+    )));
+    assert!(indexes.contains(
+        &ProtectedIndex::new(
+            "category#title",
+            IndexType::Compound2((SingleIndex::Exact, SingleIndex::Prefix))
+        )
+        .with_max_terms(10)
+    ));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_long_prefix_query() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("index-options", |table| async move {
+        let title = "The Hitchhiker's Guide to the Galaxy";
+
+        table.put(Product::new("1", title, "books")).await?;
+        table.put(Product::new("2", "The Hobbit", "books")).await?;
+
+        // Longer than the default maximum prefix length of 10
+        let res: Vec<Product> = table
+            .query()
+            .starts_with("title", "The Hitchhiker's Guide")
+            .send()
+            .await?;
+
+        common::check_eq(res, vec![Product::new("1", title, "books")])?;
+
+        // Shorter than the default minimum prefix length of 3
+        let res: Vec<Product> = table.query().starts_with("title", "T").send().await?;
+
+        common::check_eq(res.len(), 2)?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, PartialEq)]
+pub struct Note {
+    #[partition_key]
+    pub id: String,
+
+    #[cipherstash(query = "prefix", max_terms = 2)]
+    pub text: String,
+}
+
+#[tokio::test]
+#[serial]
+async fn test_put_report_truncated() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("index-options-report", |table| async move {
+        let report = table
+            .put_with_report(Note {
+                id: "1".into(),
+                text: "abc".into(),
+            })
+            .await?;
+
+        common::check_eq(report.is_truncated(), false)?;
+
+        let report = table
+            .put_with_report(Note {
+                id: "2".into(),
+                text: "abcdef".into(),
+            })
+            .await?;
+
+        common::check_eq(report.primary_key.pk.as_str(), "2")?;
+        common::check_eq(
+            report.truncated_indexes,
+            vec![TruncatedIndex {
+                index_name: "text".into(),
+                index_type: IndexType::Single(SingleIndex::Prefix),
+                max_terms: 2,
+                total_terms: 4,
+            }],
+        )?;
+
+        // The record is still stored but only matches the stored terms
+        let res: Vec<Note> = table.query().starts_with("text", "abcd").send().await?;
+        common::check_eq(res.len(), 1)?;
+
+        let res: Vec<Note> = table.query().starts_with("text", "abcdef").send().await?;
+        common::check_eq(res.len(), 0)?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_strict_index_terms() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("index-options-strict", |table| async move {
+        let table = table.with_strict_index_terms(true);
+
+        let result = table
+            .put(Note {
+                id: "1".into(),
+                text: "abcdef".into(),
+            })
+            .await;
+
+        common::check_eq(
+            matches!(
+                result,
+                Err(PutError::Seal(SealError::TooManyTerms {
+                    max_terms: 2,
+                    total_terms: 4,
+                    ..
+                }))
+            ),
+            true,
+        )?;
+
+        common::check_none(table.get::<Note>("1").await?)?;
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,103 @@
+#![cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+use cipherstash_dynamodb::{
+    traits::PrimaryKeyParts, Decryptable, Encryptable, Identifiable, PkSk, PrimaryKey, Searchable,
+};
+use serial_test::serial;
+use uuid::Uuid;
+
+mod common;
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct Account {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    pub id: Uuid,
+
+    pub name: String,
+}
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct Event {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    pub account_id: Uuid,
+
+    #[sort_key]
+    #[cipherstash(plaintext)]
+    pub created_at: DateTime<Utc>,
+
+    pub description: String,
+}
+
+fn account_id() -> Uuid {
+    Uuid::from_u128(0x67e55044_10b1_426f_9247_bb680e5fe0c8)
+}
+
+fn event(seconds: i64, description: &str) -> Event {
+    Event {
+        account_id: account_id(),
+        created_at: DateTime::from_timestamp(seconds, 0).unwrap(),
+        description: description.to_string(),
+    }
+}
+
+#[test]
+fn test_typed_primary_key_round_trip() {
+    let parts = event(1_700_000_000, "Created")
+        .get_primary_key()
+        .into_parts(&Event::type_name(), Event::sort_key_prefix().as_deref());
+
+    assert_eq!(
+        parts,
+        PrimaryKeyParts {
+            pk: "67e55044-10b1-426f-9247-bb680e5fe0c8".to_string(),
+            sk: "event#2023-11-14T22:13:20.000000000Z".to_string(),
+        }
+    );
+
+    let PkSk(pk, sk) =
+        PkSk::<Uuid, DateTime<Utc>>::from_parts(parts, Event::sort_key_prefix().as_deref())
+            .unwrap();
+
+    assert_eq!(pk, account_id());
+    assert_eq!(sk, DateTime::from_timestamp(1_700_000_000, 0).unwrap());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_non_string_keys() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("key-component-accounts", |table| async move {
+        let account = Account {
+            id: account_id(),
+            name: "CipherStash".to_string(),
+        };
+
+        let events = [
+            event(1_700_000_000, "Created"),
+            event(1_700_000_060, "Renamed"),
+        ];
+
+        table.put(account.clone()).await?;
+
+        for event in events.iter().cloned() {
+            table.put(event).await?;
+        }
+
+        // Keys can be passed without formatting them as strings
+        common::check_eq(table.get::<Account>(account_id()).await?, Some(account))?;
+
+        common::check_eq(
+            table
+                .get::<Event>((account_id(), events[1].created_at))
+                .await?,
+            Some(events[1].clone()),
+        )?;
+
+        let listed: Vec<Event> = table.query_partition(account_id()).send().await?;
+        common::check_eq(listed, events.to_vec())?;
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,79 @@
+use cipherstash_dynamodb::{Decryptable, Encryptable, Identifiable, Searchable};
+use serial_test::serial;
+
+mod common;
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, PartialEq)]
+pub struct User {
+    #[partition_key]
+    #[cipherstash(query = "exact")]
+    pub email: String,
+
+    #[cipherstash(query = "prefix")]
+    pub name: String,
+
+    #[cipherstash(plaintext)]
+    pub tag: String,
+}
+
+impl User {
+    fn new(email: impl Into<String>, name: impl Into<String>, tag: impl Into<String>) -> Self {
+        Self {
+            email: email.into(),
+            name: name.into(),
+            tag: tag.into(),
+        }
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_operation_metadata() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("operation-metadata", |table| async move {
+        let (_, metadata) = table
+            .put_with_metadata(User::new("dan@coderdan.co", "Dan Draper", "blue"))
+            .await?;
+
+        common::check_eq(metadata.root_items_written, 1)?;
+        // At least the exact term for email plus the prefix terms for name
+        common::check_eq(metadata.term_items_written > 1, true)?;
+        common::check_eq(metadata.encrypted_attributes, 2)?;
+        common::check_eq(metadata.decrypted_attributes, 0)?;
+
+        let (user, metadata) = table.get_with_metadata::<User>("dan@coderdan.co").await?;
+
+        common::check_eq(
+            user,
+            Some(User::new("dan@coderdan.co", "Dan Draper", "blue")),
+        )?;
+        common::check_eq(metadata.items_read, 1)?;
+        common::check_eq(metadata.decrypted_attributes, 2)?;
+        common::check_eq(metadata.root_items_written, 0)?;
+
+        let (users, metadata) = table
+            .query::<User>()
+            .starts_with("name", "Dan")
+            .send_with_metadata()
+            .await?;
+
+        common::check_eq(users.len(), 1)?;
+        common::check_eq(metadata.items_read, 1)?;
+        common::check_eq(metadata.decrypted_attributes, 2)?;
+
+        let metadata = table
+            .delete_with_metadata::<User>("dan@coderdan.co")
+            .await?;
+
+        common::check_eq(metadata.root_items_deleted, 1)?;
+        // Every term that could exist for the 2 indexes is deleted
+        common::check_eq(metadata.term_items_deleted, 2 * 25)?;
+
+        let (user, metadata) = table.get_with_metadata::<User>("dan@coderdan.co").await?;
+
+        common::check_none(user)?;
+        common::check_eq(metadata.items_read, 0)?;
+
+        Ok(())
+    })
+    .await
+}
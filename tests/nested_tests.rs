@@ -87,3 +87,90 @@ async fn test_round_trip() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[derive(Debug, Clone, PartialEq, Encryptable, Decryptable)]
+struct Geo {
+    lat: f64,
+    lng: f64,
+    #[cipherstash(plaintext)]
+    source: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Encryptable, Decryptable)]
+struct Address {
+    street: String,
+    postcode: String,
+    #[cipherstash(nested)]
+    geo: Geo,
+}
+
+#[derive(Debug, Clone, PartialEq, Searchable, Encryptable, Decryptable, Identifiable)]
+struct Customer {
+    #[partition_key]
+    pub email: String,
+    pub name: String,
+    #[cipherstash(nested)]
+    #[cipherstash(query = "exact", path = "postcode")]
+    #[cipherstash(query = "exact", path = "geo.source")]
+    pub address: Address,
+}
+
+#[tokio::test]
+async fn test_nested_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let config = aws_config::from_env()
+        .endpoint_url("http://localhost:8000")
+        .load()
+        .await;
+
+    let client = aws_sdk_dynamodb::Client::new(&config);
+    let table_name = "nested-customers";
+
+    common::create_table(&client, table_name).await;
+
+    let table = EncryptedTable::init(client, table_name)
+        .await
+        .into_diagnostic()?;
+
+    let customer = Customer {
+        email: "dan@coderdan.co".to_string(),
+        name: "Dan Draper".to_string(),
+        address: Address {
+            street: "1 Collins St".to_string(),
+            postcode: "3000".to_string(),
+            geo: Geo {
+                lat: -37.81,
+                lng: 144.96,
+                source: "gps".to_string(),
+            },
+        },
+    };
+
+    table.put(customer.clone()).await.into_diagnostic()?;
+
+    let check = table
+        .get::<Customer>("dan@coderdan.co")
+        .await
+        .into_diagnostic()?;
+
+    assert_eq!(check, Some(customer.clone()));
+
+    let results: Vec<Customer> = table
+        .query()
+        .eq("address.postcode", "3000")
+        .send()
+        .await
+        .into_diagnostic()?;
+
+    assert_eq!(results, vec![customer.clone()]);
+
+    let results: Vec<Customer> = table
+        .query()
+        .eq("address.geo.source", "gps")
+        .send()
+        .await
+        .into_diagnostic()?;
+
+    assert_eq!(results, vec![customer]);
+
+    Ok(())
+}
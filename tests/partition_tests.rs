@@ -0,0 +1,91 @@
+use cipherstash_dynamodb::{Decryptable, Encryptable, Identifiable, Searchable};
+use serial_test::serial;
+
+mod common;
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct User {
+    #[partition_key]
+    pub email: String,
+
+    pub name: String,
+}
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct License {
+    #[partition_key]
+    pub email: String,
+
+    #[sort_key]
+    #[cipherstash(plaintext)]
+    pub product: String,
+
+    pub key: String,
+}
+
+impl License {
+    fn new(email: &str, product: &str, key: &str) -> Self {
+        Self {
+            email: email.to_string(),
+            product: product.to_string(),
+            key: key.to_string(),
+        }
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_query_partition() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("partition-licenses", |table| async move {
+        let dan = User {
+            email: "dan@coderdan.co".to_string(),
+            name: "Dan Draper".to_string(),
+        };
+
+        let licenses = [
+            License::new("dan@coderdan.co", "database", "abc"),
+            License::new("dan@coderdan.co", "dynamodb", "def"),
+            License::new("dan@coderdan.co", "proxy", "ghi"),
+            License::new("ada@example.com", "database", "jkl"),
+        ];
+
+        table.put(dan.clone()).await?;
+
+        for license in licenses.iter().cloned() {
+            table.put(license).await?;
+        }
+
+        // Only the licenses of the partition are returned and not the user stored alongside them
+        let dans_licenses: Vec<License> = table.query_partition("dan@coderdan.co").send().await?;
+        common::check_eq(dans_licenses, licenses[..3].to_vec())?;
+
+        let users: Vec<User> = table.query_partition("dan@coderdan.co").send().await?;
+        common::check_eq(users, vec![dan])?;
+
+        let database_licenses: Vec<License> = table
+            .query_partition("dan@coderdan.co")
+            .sk_starts_with("d")
+            .send()
+            .await?;
+        common::check_eq(database_licenses, licenses[..2].to_vec())?;
+
+        let licenses_between: Vec<License> = table
+            .query_partition("dan@coderdan.co")
+            .sk_between("dynamodb", "proxy")
+            .send()
+            .await?;
+        common::check_eq(licenses_between, licenses[1..3].to_vec())?;
+
+        // Records without a sort key can't be queried by one
+        common::check_err(
+            table
+                .query_partition::<User>("dan@coderdan.co")
+                .sk_starts_with("d")
+                .send()
+                .await,
+        )?;
+
+        Ok(())
+    })
+    .await
+}
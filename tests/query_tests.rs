@@ -226,6 +226,37 @@ async fn test_query_compound_via_secondary() -> Result<(), Box<dyn std::error::E
     .await
 }
 
+#[tokio::test]
+async fn test_typed_query() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("query-tests", |table| async move {
+        setup(&table).await?;
+
+        let res: Vec<User> = table
+            .typed_query(User::query().email_eq("dan@coderdan.co"))
+            .send()
+            .await?;
+
+        check_eq(
+            res,
+            vec![User::new("dan@coderdan.co", "Dan Draper", "blue")],
+        )?;
+
+        // The parts of a compound index can be added in either order
+        let res: Vec<User> = table
+            .typed_query(
+                User::query()
+                    .name_starts_with("Dan")
+                    .email_eq("dan@coderdan.co"),
+            )
+            .via(secondary_dataset_id())
+            .send()
+            .await?;
+
+        check_eq(res, vec![User::new("dan@coderdan.co", "Dan Draper", "red")])
+    })
+    .await
+}
+
 #[tokio::test]
 async fn test_get_by_partition_key() -> Result<(), Box<dyn std::error::Error>> {
     with_encrypted_table("query-tests", |table| async move {
@@ -0,0 +1,68 @@
+#![cfg(feature = "serde")]
+
+use chrono::{DateTime, NaiveDate, Utc};
+use cipherstash_dynamodb::{Decryptable, Encryptable, Identifiable, Searchable};
+use serde::{Deserialize, Serialize};
+use serial_test::serial;
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Address {
+    pub street: String,
+    pub postcode: String,
+}
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct Customer {
+    #[partition_key]
+    #[cipherstash(query = "exact")]
+    pub email: String,
+
+    #[cipherstash(serde)]
+    pub addresses: Vec<Address>,
+
+    #[cipherstash(serde)]
+    pub joined_at: DateTime<Utc>,
+
+    #[cipherstash(serde)]
+    pub birthday: Option<NaiveDate>,
+}
+
+fn customer() -> Customer {
+    Customer {
+        email: "dan@coderdan.co".to_string(),
+        addresses: vec![
+            Address {
+                street: "1 Collins St".to_string(),
+                postcode: "3000".to_string(),
+            },
+            Address {
+                street: "2 George St".to_string(),
+                postcode: "2000".to_string(),
+            },
+        ],
+        joined_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        birthday: None,
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_serde_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("serde-customers", |table| async move {
+        table.put(customer()).await?;
+
+        common::check_eq(
+            table.get::<Customer>("dan@coderdan.co").await?,
+            Some(customer()),
+        )?;
+
+        let customers: Vec<Customer> = table.query().eq("email", "dan@coderdan.co").send().await?;
+
+        common::check_eq(customers, vec![customer()])?;
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,117 @@
+use cipherstash_dynamodb::{Decryptable, Encryptable, Identifiable, Searchable};
+use serial_test::serial;
+
+mod common;
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+#[cipherstash(soft_delete, record_mac)]
+pub struct Note {
+    #[partition_key]
+    pub id: String,
+
+    #[cipherstash(query = "exact")]
+    pub author: String,
+
+    #[cipherstash(plaintext)]
+    pub status: String,
+}
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct Draft {
+    #[partition_key]
+    pub id: String,
+
+    pub body: String,
+}
+
+#[test]
+fn test_has_soft_delete() {
+    assert!(Note::has_soft_delete());
+    assert!(!Draft::has_soft_delete());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_soft_delete_and_restore() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("soft-delete-notes", |table| async move {
+        let note = Note {
+            id: "note-1".to_string(),
+            author: "dan@coderdan.co".to_string(),
+            status: "published".to_string(),
+        };
+
+        table.put(note.clone()).await?;
+        table.delete::<Note>("note-1").await?;
+
+        // The record is hidden and its index terms are removed
+        let found: Option<Note> = table.get("note-1").await?;
+        common::check_none(found)?;
+
+        let found: Vec<Note> = table.query().eq("author", "dan@coderdan.co").send().await?;
+        common::check_eq(found, vec![])?;
+
+        let found: Vec<Note> = table.query_partition("note-1").send().await?;
+        common::check_eq(found, vec![])?;
+
+        // The record MAC still verifies after the record is marked as deleted
+        let found: Option<Note> = table.get_including_deleted("note-1").await?;
+        common::check_eq(found, Some(note.clone()))?;
+
+        // Deleting it again leaves it as it is
+        table.delete::<Note>("note-1").await?;
+
+        common::check_eq(table.restore::<Note>("note-1").await?, true)?;
+
+        let found: Option<Note> = table.get("note-1").await?;
+        common::check_eq(found, Some(note.clone()))?;
+
+        let found: Vec<Note> = table.query().eq("author", "dan@coderdan.co").send().await?;
+        common::check_eq(found, vec![note])?;
+
+        // Only soft deleted records can be restored
+        common::check_eq(table.restore::<Note>("note-1").await?, false)?;
+        common::check_eq(table.restore::<Note>("note-2").await?, false)?;
+
+        // Deleting a record which doesn't exist isn't an error
+        table.delete::<Note>("note-2").await?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_purge() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("soft-delete-purge", |table| async move {
+        let note = Note {
+            id: "note-1".to_string(),
+            author: "dan@coderdan.co".to_string(),
+            status: "draft".to_string(),
+        };
+
+        table.put(note).await?;
+        table.delete::<Note>("note-1").await?;
+        table.purge::<Note>("note-1").await?;
+
+        let found: Option<Note> = table.get_including_deleted("note-1").await?;
+        common::check_none(found)?;
+
+        common::check_eq(table.restore::<Note>("note-1").await?, false)?;
+
+        // Types without soft delete are removed by delete
+        table
+            .put(Draft {
+                id: "draft-1".to_string(),
+                body: "Hello".to_string(),
+            })
+            .await?;
+        table.delete::<Draft>("draft-1").await?;
+
+        let found: Option<Draft> = table.get_including_deleted("draft-1").await?;
+        common::check_none(found)?;
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,138 @@
+use cipherstash_dynamodb::{
+    traits::PrimaryKeyParts, Decryptable, Encryptable, Identifiable, PrimaryKey, Searchable,
+};
+use serial_test::serial;
+
+mod common;
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+#[sort_key(fields = ["created_at", "id"])]
+pub struct Post {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    pub user: String,
+
+    #[cipherstash(plaintext)]
+    pub created_at: String,
+
+    #[cipherstash(plaintext)]
+    pub id: u32,
+
+    pub body: String,
+}
+
+impl Post {
+    fn new(created_at: &str, id: u32, body: &str) -> Self {
+        Self {
+            user: "dan@coderdan.co".to_string(),
+            created_at: created_at.to_string(),
+            id,
+            body: body.to_string(),
+        }
+    }
+}
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+#[sort_key(fields = ["day", "title"], separator = "/")]
+pub struct Entry {
+    #[partition_key]
+    pub user: String,
+
+    #[cipherstash(plaintext)]
+    pub day: String,
+
+    pub title: String,
+}
+
+fn parts<T: Identifiable>(record: T) -> PrimaryKeyParts {
+    record
+        .get_primary_key()
+        .into_parts(&T::type_name(), T::sort_key_prefix().as_deref())
+}
+
+#[test]
+fn test_composite_sort_key() {
+    assert_eq!(
+        parts(Post::new("2024-01-01", 7, "Hello")),
+        PrimaryKeyParts {
+            pk: "dan@coderdan.co".to_string(),
+            sk: "post#2024-01-01#0000000007".to_string(),
+        }
+    );
+    assert!(!Post::is_sk_encrypted());
+
+    let entry = Entry {
+        user: "dan@coderdan.co".to_string(),
+        day: "monday".to_string(),
+        title: "Notes".to_string(),
+    };
+
+    assert_eq!(
+        parts(entry),
+        PrimaryKeyParts {
+            pk: "dan@coderdan.co".to_string(),
+            sk: "entry#monday/Notes".to_string(),
+        }
+    );
+
+    // The sort key is encrypted if any of its fields are
+    assert!(Entry::is_sk_encrypted());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_query_by_sort_key_prefix() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("sort-key-posts", |table| async move {
+        let posts = [
+            Post::new("2024-01-02", 1, "Second"),
+            Post::new("2024-01-01", 2, "First"),
+            Post::new("2024-02-01", 3, "Third"),
+            Post::new("2024-01-01", 10, "Fourth"),
+        ];
+
+        for post in posts.iter().cloned() {
+            table.put(post).await?;
+        }
+
+        common::check_eq(
+            table
+                .get::<Post>(("dan@coderdan.co", "2024-01-01#0000000002"))
+                .await?,
+            Some(posts[1].clone()),
+        )?;
+
+        // Records are listed in sort key order
+        let january: Vec<Post> = table
+            .query_partition("dan@coderdan.co")
+            .sk_starts_with("2024-01")
+            .send()
+            .await?;
+        common::check_eq(
+            january,
+            vec![posts[1].clone(), posts[3].clone(), posts[0].clone()],
+        )?;
+
+        let all: Vec<Post> = table.query_partition("dan@coderdan.co").send().await?;
+        common::check_eq(
+            all,
+            vec![
+                posts[1].clone(),
+                posts[3].clone(),
+                posts[0].clone(),
+                posts[2].clone(),
+            ],
+        )?;
+
+        // Encrypted sort keys can't be queried by prefix
+        common::check_err(
+            table
+                .query_partition::<Entry>("dan@coderdan.co")
+                .sk_starts_with("monday")
+                .send()
+                .await,
+        )?;
+
+        Ok(())
+    })
+    .await
+}
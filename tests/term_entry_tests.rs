@@ -0,0 +1,173 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+use cipherstash_dynamodb::{
+    crypto::SealError,
+    errors::{DecryptError, GetError},
+    Decryptable, Encryptable, EncryptedTable, Identifiable, Searchable,
+};
+use itertools::Itertools;
+use serial_test::serial;
+use std::collections::HashMap;
+
+mod common;
+
+#[derive(
+    Identifiable, Encryptable, Decryptable, Searchable, Debug, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[cipherstash(bind_to_primary_key)]
+pub struct BoundUser {
+    #[partition_key]
+    pub id: String,
+
+    #[cipherstash(query = "exact")]
+    #[cipherstash(query = "prefix")]
+    pub email: String,
+}
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, PartialEq, Clone)]
+#[cipherstash(record_mac)]
+pub struct Account {
+    #[cipherstash(query = "exact")]
+    #[partition_key]
+    pub email: String,
+
+    #[cipherstash(plaintext)]
+    pub role: String,
+}
+
+impl Account {
+    fn new(email: impl Into<String>, role: impl Into<String>) -> Self {
+        Self {
+            email: email.into(),
+            role: role.into(),
+        }
+    }
+}
+
+impl BoundUser {
+    fn new(id: impl Into<String>, email: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            email: email.into(),
+        }
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_query_bound_to_primary_key() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("term-entries", |table| async move {
+        table
+            .put(BoundUser::new("user-1", "dan@coderdan.co"))
+            .await?;
+        table
+            .put(BoundUser::new("user-2", "daniel@example.com"))
+            .await?;
+
+        let users: Vec<BoundUser> = table.query().eq("email", "dan@coderdan.co").send().await?;
+
+        common::check_eq(users, vec![BoundUser::new("user-1", "dan@coderdan.co")])?;
+
+        let users: Vec<BoundUser> = table
+            .query()
+            .starts_with("email", "dan")
+            .send()
+            .await?
+            .into_iter()
+            .sorted()
+            .collect();
+
+        common::check_eq(
+            users,
+            vec![
+                BoundUser::new("user-1", "dan@coderdan.co"),
+                BoundUser::new("user-2", "daniel@example.com"),
+            ],
+        )?;
+
+        Ok(())
+    })
+    .await
+}
+
+fn key_of(item: &HashMap<String, AttributeValue>, name: &str) -> String {
+    item[name].as_s().expect("expected string key").to_string()
+}
+
+#[tokio::test]
+#[serial]
+async fn test_get_rejects_term_entry() {
+    let config = aws_config::from_env()
+        .endpoint_url("http://localhost:8000")
+        .load()
+        .await;
+
+    let client = aws_sdk_dynamodb::Client::new(&config);
+    let table_name = "test-term-entries-get";
+
+    common::create_table(&client, table_name).await;
+
+    let table = EncryptedTable::init(client.clone(), table_name)
+        .await
+        .expect("failed to init table");
+
+    table
+        .put(Account::new("alice@example.com", "user"))
+        .await
+        .expect("failed to put alice");
+    table
+        .put(Account::new("bob@example.com", "admin"))
+        .await
+        .expect("failed to put bob");
+
+    let (roots, terms): (Vec<_>, Vec<_>) = client
+        .scan()
+        .table_name(table_name)
+        .send()
+        .await
+        .expect("failed to scan table")
+        .items
+        .unwrap_or_default()
+        .into_iter()
+        .partition(|item| !item.contains_key("term"));
+
+    let accounts: Vec<Account> = table
+        .decrypt_all(roots.clone())
+        .await
+        .expect("failed to decrypt");
+
+    let alice = &roots[accounts
+        .iter()
+        .position(|account| account.email == "alice@example.com")
+        .expect("expected alice")];
+    let bob = &roots[accounts
+        .iter()
+        .position(|account| account.email == "bob@example.com")
+        .expect("expected bob")];
+
+    // Overwrite the record of alice with a term entry of bob which carries the sk of bob
+    let mut forged = terms
+        .into_iter()
+        .find(|item| key_of(item, "pk") == key_of(bob, "pk"))
+        .expect("expected a term entry for bob");
+    forged.insert("pk".to_string(), alice["pk"].clone());
+    forged.insert("sk".to_string(), alice["sk"].clone());
+
+    client
+        .put_item()
+        .table_name(table_name)
+        .set_item(Some(forged))
+        .send()
+        .await
+        .expect("failed to put forged item");
+
+    let result = table.get::<Account>("alice@example.com").await;
+
+    assert!(matches!(
+        result,
+        Err(GetError::DecryptError(DecryptError::SealError(
+            SealError::TamperDetected { .. }
+        )))
+    ));
+
+    common::delete_table(&client, table_name).await;
+}
@@ -0,0 +1,124 @@
+use cipherstash_dynamodb::{
+    Decryptable, Encryptable, Identifiable, IndexType, Searchable, SingleIndex,
+};
+use itertools::Itertools;
+use serial_test::serial;
+
+mod common;
+
+#[derive(
+    Identifiable, Encryptable, Decryptable, Searchable, Debug, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[cipherstash(term_shards = 4)]
+pub struct User {
+    #[partition_key]
+    #[cipherstash(query = "exact")]
+    pub email: String,
+
+    #[cipherstash(query = "prefix", term_shards = 16)]
+    #[cipherstash(query = "prefix", compound = "tag#name")]
+    pub name: String,
+
+    #[cipherstash(query = "exact", compound = "tag#name")]
+    pub tag: String,
+}
+
+impl User {
+    fn new(email: impl Into<String>, name: impl Into<String>, tag: impl Into<String>) -> Self {
+        Self {
+            email: email.into(),
+            name: name.into(),
+            tag: tag.into(),
+        }
+    }
+}
+
+#[test]
+fn test_term_shards_per_index() {
+    assert_eq!(
+        User::term_shards("email", IndexType::Single(SingleIndex::Exact)),
+        4
+    );
+    assert_eq!(
+        User::term_shards("name", IndexType::Single(SingleIndex::Prefix)),
+        16
+    );
+    assert_eq!(
+        User::term_shards(
+            "tag#name",
+            IndexType::Compound2((SingleIndex::Exact, SingleIndex::Prefix))
+        ),
+        4
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_sharded_queries() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("term-shards", |table| async move {
+        table
+            .put(User::new("dan@coderdan.co", "Dan Draper", "blue"))
+            .await?;
+        table
+            .put(User::new("daniel@example.com", "Daniel Johnson", "blue"))
+            .await?;
+        table
+            .put(User::new("jane@smith.org", "Jane Smith", "red"))
+            .await?;
+
+        let res: Vec<User> = table.query().eq("email", "jane@smith.org").send().await?;
+
+        common::check_eq(res, vec![User::new("jane@smith.org", "Jane Smith", "red")])?;
+
+        let res: Vec<User> = table
+            .query()
+            .starts_with("name", "Dan")
+            .send()
+            .await?
+            .into_iter()
+            .sorted()
+            .collect();
+
+        common::check_eq(
+            res,
+            vec![
+                User::new("dan@coderdan.co", "Dan Draper", "blue"),
+                User::new("daniel@example.com", "Daniel Johnson", "blue"),
+            ],
+        )?;
+
+        let res: Vec<User> = table
+            .query()
+            .eq("tag", "blue")
+            .starts_with("name", "Daniel")
+            .send()
+            .await?;
+
+        common::check_eq(
+            res,
+            vec![User::new("daniel@example.com", "Daniel Johnson", "blue")],
+        )?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_sharded_delete() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("term-shards-delete", |table| async move {
+        table
+            .put(User::new("dan@coderdan.co", "Dan Draper", "blue"))
+            .await?;
+
+        table.delete::<User>("dan@coderdan.co").await?;
+
+        let res: Vec<User> = table.query().starts_with("name", "Dan").send().await?;
+
+        common::check_eq(res, vec![])?;
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,99 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+use cipherstash_dynamodb::{
+    encrypted_table::PreparedRecord, Decryptable, Encryptable, EncryptedTable, Identifiable,
+    Searchable,
+};
+use serial_test::serial;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod common;
+
+#[derive(Identifiable, Encryptable, Decryptable, Searchable, Debug, Clone, PartialEq)]
+pub struct Session {
+    #[partition_key]
+    pub id: String,
+
+    #[cipherstash(query = "exact")]
+    pub email: String,
+
+    #[cipherstash(plaintext, ttl)]
+    pub expires_at: i64,
+}
+
+impl Session {
+    fn new(id: &str, email: &str, expires_in: i64) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_secs() as i64;
+
+        Self {
+            id: id.to_string(),
+            email: email.to_string(),
+            expires_at: now + expires_in,
+        }
+    }
+}
+
+#[test]
+fn test_ttl_attribute() {
+    assert_eq!(Session::ttl_attribute().as_deref(), Some("expires_at"));
+}
+
+#[tokio::test]
+async fn test_ttl_copied_to_index_terms() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let session = Session::new("session-1", "dan@coderdan.co", 3600);
+    let record = PreparedRecord::prepare_record(session.clone()).expect("failed to prepare record");
+
+    // Don't project any attributes into the index terms
+    let patch = table
+        .create_put_patch(record, None, |_, _| false)
+        .await
+        .expect("failed to encrypt");
+
+    assert_eq!(patch.put_records.len(), 2);
+
+    for item in patch.put_records {
+        assert_eq!(
+            item.get("expires_at"),
+            Some(&AttributeValue::N(session.expires_at.to_string()))
+        );
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_filter_expired() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("ttl-sessions", |table| async move {
+        let live = Session::new("session-1", "dan@coderdan.co", 3600);
+        let expired = Session::new("session-2", "dan@coderdan.co", -3600);
+
+        table.put(live.clone()).await?;
+        table.put(expired.clone()).await?;
+
+        // Expired records are returned until DynamoDB deletes them unless they're filtered
+        let session: Option<Session> = table.get("session-2").await?;
+        common::check_eq(session, Some(expired.clone()))?;
+
+        let table = table.with_filter_expired(true);
+
+        let session: Option<Session> = table.get("session-2").await?;
+        common::check_none(session)?;
+
+        let session: Option<Session> = table.get("session-1").await?;
+        common::check_eq(session, Some(live.clone()))?;
+
+        let sessions: Vec<Session> = table.query().eq("email", "dan@coderdan.co").send().await?;
+        common::check_eq(sessions, vec![live])?;
+
+        let sessions: Vec<Session> = table.query_partition("session-2").send().await?;
+        common::check_eq(sessions, vec![])?;
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,13 @@
+use cipherstash_dynamodb::Encryptable;
+
+#[derive(Debug, Encryptable)]
+struct Customer {
+    #[partition_key]
+    email: String,
+    #[cipherstash(collection, query = "exact", compound = "tags#name")]
+    tags: Vec<String>,
+    #[cipherstash(query = "exact", compound = "tags#name")]
+    name: String,
+}
+
+fn main() {}
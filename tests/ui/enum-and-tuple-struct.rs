@@ -0,0 +1,38 @@
+use cipherstash_dynamodb::{Decryptable, Encryptable, Identifiable, Searchable};
+
+#[derive(Debug, Identifiable, Encryptable, Decryptable, Searchable)]
+struct Email(#[partition_key] String);
+
+#[derive(Debug, Identifiable, Encryptable, Decryptable, Searchable)]
+struct Contact(
+    #[partition_key] String,
+    #[cipherstash(query = "prefix")] String,
+    #[cipherstash(plaintext)] u32,
+    #[cipherstash(skip)] Option<String>,
+);
+
+#[derive(Debug, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(tag = "kind")]
+enum PaymentMethod {
+    Card {
+        #[partition_key]
+        id: String,
+        #[cipherstash(query = "exact")]
+        owner: String,
+        #[cipherstash(query = "exact")]
+        number: String,
+        #[cipherstash(plaintext)]
+        expiry: String,
+    },
+    BankAccount {
+        #[partition_key]
+        id: String,
+        #[cipherstash(query = "exact")]
+        owner: String,
+        account_number: String,
+        #[cipherstash(skip)]
+        nickname: Option<String>,
+    },
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use cipherstash_dynamodb::{Encryptable, Identifiable};
+
+#[derive(Debug, Identifiable, Encryptable)]
+enum PaymentMethod {
+    Card {
+        #[partition_key]
+        id: String,
+        #[cipherstash(query = "exact")]
+        owner: String,
+    },
+    BankAccount {
+        #[partition_key]
+        id: String,
+        #[cipherstash(plaintext)]
+        owner: String,
+    },
+}
+
+fn main() {}
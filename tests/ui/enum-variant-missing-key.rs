@@ -0,0 +1,15 @@
+use cipherstash_dynamodb::{Encryptable, Identifiable};
+
+#[derive(Debug, Identifiable, Encryptable)]
+enum PaymentMethod {
+    Card {
+        #[partition_key]
+        id: String,
+        number: String,
+    },
+    BankAccount {
+        account_number: String,
+    },
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use cipherstash_dynamodb::Encryptable;
+
+#[derive(Debug, Encryptable)]
+struct Address {
+    postcode: String,
+}
+
+#[derive(Debug, Encryptable)]
+struct Customer {
+    #[partition_key]
+    email: String,
+    #[cipherstash(query = "exact", path = "postcode")]
+    address: Address,
+}
+
+fn main() {}
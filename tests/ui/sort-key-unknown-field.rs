@@ -0,0 +1,11 @@
+use cipherstash_dynamodb::Identifiable;
+
+#[derive(Debug, Identifiable)]
+#[sort_key(fields = ["created_at", "id"])]
+struct Post {
+    #[partition_key]
+    user: String,
+    created_at: String,
+}
+
+fn main() {}
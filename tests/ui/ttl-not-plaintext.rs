@@ -0,0 +1,12 @@
+use cipherstash_dynamodb::{Decryptable, Encryptable, Identifiable};
+
+#[derive(Debug, Identifiable, Encryptable, Decryptable)]
+struct Session {
+    #[partition_key]
+    id: String,
+
+    #[cipherstash(ttl)]
+    expires_at: i64,
+}
+
+fn main() {}
@@ -0,0 +1,48 @@
+use cipherstash_dynamodb::{
+    Decryptable, Encryptable, Identifiable, Searchable, SingleIndex, TypedQuery,
+};
+
+#[derive(Debug, Identifiable, Encryptable, Decryptable, Searchable)]
+pub struct User {
+    #[partition_key]
+    #[cipherstash(query = "exact")]
+    #[cipherstash(query = "exact", compound = "email#name")]
+    email: String,
+
+    #[cipherstash(query = "prefix", compound = "email#name")]
+    name: String,
+
+    #[cipherstash(query = "exact")]
+    age: i32,
+}
+
+fn fields(query: impl TypedQuery<Searchable = User>) -> Vec<(String, SingleIndex)> {
+    query
+        .into_parts()
+        .into_iter()
+        .map(|(name, index, _)| (name, index))
+        .collect()
+}
+
+fn main() {
+    assert_eq!(
+        fields(User::query().email_eq("dan@coderdan.co")),
+        vec![("email".to_string(), SingleIndex::Exact)]
+    );
+
+    assert_eq!(
+        fields(User::query().age_eq(42)),
+        vec![("age".to_string(), SingleIndex::Exact)]
+    );
+
+    assert_eq!(
+        fields(User::query().name_starts_with("Dan").email_eq("dan@coderdan.co")),
+        vec![
+            ("name".to_string(), SingleIndex::Prefix),
+            ("email".to_string(), SingleIndex::Exact),
+        ]
+    );
+
+    // Only the compound index covers name so it can't be queried on its own
+    let _ = User::query().name_starts_with("Dan");
+}